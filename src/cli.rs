@@ -0,0 +1,334 @@
+use crate::export::export_to_obj;
+use crate::shapes::{
+    generate_formula, generate_shape, hollow_voxels, voxel_color_rgb, Axis, GeometricShape, Shape,
+    VoxelData, MAX_DIMENSION, MAX_FRACTAL_ITERATIONS, MAX_GEAR_TEETH, MAX_GRID_CELLS,
+    MAX_LETTER_SPACING, MAX_PRISM_SIDES, MAX_ROUNDNESS, MIN_DIMENSION, MIN_GEAR_TEETH,
+    MIN_PRISM_SIDES, MIN_ROUNDNESS,
+};
+use strum::IntoEnumIterator;
+
+const USAGE: &str = "\
+Usage: voxel_sculptor [OPTIONS]
+
+Without options the interactive editor is started.
+
+Options:
+  --shape <NAME>     cube, sphere, cylinder, cone, square-pyramid, tube,
+                     prism, formula, noise-blob, text, stairs, ramp,
+                     torus, capsule, octahedron, tetrahedron, helix,
+                     arch, torus-knot, menger-sponge, sierpinski,
+                     superellipsoid, terrain, gear, star or plus
+                     (default: cube)
+  --width <N>        Width in voxels (default: 8)
+  --height <N>       Height in voxels (default: 8)
+  --depth <N>        Depth in voxels (default: 8)
+  --axis <AXIS>      Main axis of cylinders, cones, tubes and pyramids: x, y or z
+                     (default: y)
+  --wall <N>         Wall thickness of hollow spheres, cubes, cylinders, cones and
+                     tubes (default: 0, solid)
+  --sides <N>        Number of sides for prisms or points for stars, 3 to 12
+                     (default: 6)
+  --steps <N>        Number of stairs steps (default: 0, one per layer)
+  --iterations <N>   Fractal detail steps, 0 to 5 (default: 3)
+  --roundness <Y>,<XZ>
+                     Superellipsoid exponents, 0.05 to 3: near 0 boxy, 1 round,
+                     2 pointed (default: 0.5,0.5)
+  --top-radius <X>   Cone top radius as a fraction of the base, 0 to 1 (default: 0)
+  --arms <N>         Arm thickness of the plus shape (default: 2)
+  --gear <T>,<D>,<B> Gear teeth, tooth depth and bore radius in voxels
+                     (default: 12,2,2)
+  --seed <N>         Seed for noise-blob and terrain (default: 1)
+  --frequency <X>    Terrain hills across the grid (default: 3)
+  --amplitude <X>    Fraction of the height terrain hills span, 0 to 1 (default: 0.7)
+  --threshold <X>    Density cutoff for noise-blob, higher is thinner (default: 0.45)
+  --text <TEXT>      String for the text shape (default: HELLO)
+  --spacing <N>      Empty columns between text characters, 0 to 8 (default: 1)
+  --turns <X>        Full turns of a helix over its height (default: 3)
+  --coil-radius <X>  Radius of the tube of helices and torus knots (default: 1)
+  --knot <P>,<Q>     Torus knot windings around and through the ring (default: 2,3)
+  --formula <EXPR>   Formula shape, filled where positive with x, y, z in -1..1
+                     (default: \"0.9 - (x*x + y*y + z*z)\")
+  --hollow           Keep only the outer shell
+  --export <PATH>    Write the shape as an OBJ file
+  -h, --help         Print this help";
+
+// Options collected from the command line
+#[derive(Debug, Default)]
+struct CliArgs {
+    shape: Shape,
+    hollow: bool,
+    export: Option<String>,
+    help: bool,
+}
+
+// Runs batch mode and returns the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            return 1;
+        }
+    };
+
+    if args.help {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    if args.shape.cell_count() > MAX_GRID_CELLS {
+        eprintln!(
+            "Error: the grid has {} cells, more than the limit of {}",
+            args.shape.cell_count(),
+            MAX_GRID_CELLS
+        );
+        return 1;
+    }
+
+    if args.shape.kind == GeometricShape::Formula {
+        if let Err(e) = generate_formula(&args.shape) {
+            eprintln!("Error: invalid formula: {}", e);
+            return 1;
+        }
+    }
+
+    let mut voxels = generate_shape(&args.shape);
+    if args.hollow {
+        voxels = hollow_voxels(&voxels);
+    }
+    let voxel_data = VoxelData {
+        shape: args.shape,
+        voxels,
+    };
+    println!(
+        "Generated {} {}x{}x{} with {} voxels",
+        voxel_data.shape.kind,
+        voxel_data.shape.width,
+        voxel_data.shape.height,
+        voxel_data.shape.depth,
+        voxel_data.voxels.len()
+    );
+
+    if let Some(path) = &args.export {
+        if let Err(e) = export_to_obj(&voxel_data, path, voxel_color_rgb(), None, true) {
+            eprintln!("Error: export to {} failed: {}", path, e);
+            return 1;
+        }
+        println!("Exported shape to {}", path);
+    }
+
+    0
+}
+
+fn parse_args(args: &[String]) -> Result<CliArgs, String> {
+    let mut parsed = CliArgs::default();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => parsed.help = true,
+            "--hollow" => parsed.hollow = true,
+            "--shape" => parsed.shape.kind = parse_shape(next_value(&mut iter, arg)?)?,
+            "--width" => parsed.shape.width = parse_dimension(next_value(&mut iter, arg)?, arg)?,
+            "--height" => parsed.shape.height = parse_dimension(next_value(&mut iter, arg)?, arg)?,
+            "--depth" => parsed.shape.depth = parse_dimension(next_value(&mut iter, arg)?, arg)?,
+            "--axis" => parsed.shape.axis = parse_axis(next_value(&mut iter, arg)?)?,
+            "--wall" => {
+                let value = next_value(&mut iter, arg)?;
+                parsed.shape.wall_thickness = value
+                    .parse()
+                    .map_err(|_| format!("{} expects a number, got '{}'", arg, value))?;
+            }
+            "--sides" => {
+                let value = next_value(&mut iter, arg)?;
+                let sides: u32 = value
+                    .parse()
+                    .map_err(|_| format!("{} expects a number, got '{}'", arg, value))?;
+                if !(MIN_PRISM_SIDES..=MAX_PRISM_SIDES).contains(&sides) {
+                    return Err(format!(
+                        "{} must be between {} and {}",
+                        arg, MIN_PRISM_SIDES, MAX_PRISM_SIDES
+                    ));
+                }
+                parsed.shape.sides = sides;
+            }
+            "--turns" => parsed.shape.turns = parse_number(next_value(&mut iter, arg)?, arg)?,
+            "--coil-radius" => {
+                parsed.shape.coil_radius = parse_number(next_value(&mut iter, arg)?, arg)?
+            }
+            "--steps" => {
+                let value = next_value(&mut iter, arg)?;
+                parsed.shape.steps = value
+                    .parse()
+                    .map_err(|_| format!("{} expects a number, got '{}'", arg, value))?;
+            }
+            "--knot" => {
+                let value = next_value(&mut iter, arg)?;
+                (parsed.shape.knot_p, parsed.shape.knot_q) = value
+                    .split_once(',')
+                    .and_then(|(p, q)| Some((p.trim().parse().ok()?, q.trim().parse().ok()?)))
+                    .filter(|&(p, q)| p > 0 && q > 0)
+                    .ok_or_else(|| format!("{} expects two positive numbers like 2,3", arg))?;
+            }
+            "--iterations" => {
+                let value = next_value(&mut iter, arg)?;
+                let iterations: u32 = value
+                    .parse()
+                    .map_err(|_| format!("{} expects a number, got '{}'", arg, value))?;
+                if iterations > MAX_FRACTAL_ITERATIONS {
+                    return Err(format!(
+                        "{} must be at most {}",
+                        arg, MAX_FRACTAL_ITERATIONS
+                    ));
+                }
+                parsed.shape.iterations = iterations;
+            }
+            "--roundness" => {
+                let value = next_value(&mut iter, arg)?;
+                (parsed.shape.roundness_y, parsed.shape.roundness_xz) = value
+                    .split_once(',')
+                    .and_then(|(y, xz)| Some((y.trim().parse().ok()?, xz.trim().parse().ok()?)))
+                    .filter(|&(y, xz): &(f32, f32)| {
+                        [y, xz]
+                            .iter()
+                            .all(|e| (MIN_ROUNDNESS..=MAX_ROUNDNESS).contains(e))
+                    })
+                    .ok_or_else(|| {
+                        format!(
+                            "{} expects two numbers from {} to {} like 0.5,1",
+                            arg, MIN_ROUNDNESS, MAX_ROUNDNESS
+                        )
+                    })?;
+            }
+            "--seed" => {
+                let value = next_value(&mut iter, arg)?;
+                parsed.shape.seed = value
+                    .parse()
+                    .map_err(|_| format!("{} expects a number, got '{}'", arg, value))?;
+            }
+            "--top-radius" => {
+                let value = next_value(&mut iter, arg)?;
+                parsed.shape.top_radius = value
+                    .parse()
+                    .ok()
+                    .filter(|radius| (0.0..=1.0).contains(radius))
+                    .ok_or_else(|| {
+                        format!("{} expects a number from 0 to 1, got '{}'", arg, value)
+                    })?;
+            }
+            "--arms" => {
+                let value = next_value(&mut iter, arg)?;
+                parsed.shape.arm_thickness = value
+                    .parse()
+                    .ok()
+                    .filter(|&thickness| thickness > 0)
+                    .ok_or_else(|| format!("{} expects a positive number, got '{}'", arg, value))?;
+            }
+            "--gear" => {
+                let value = next_value(&mut iter, arg)?;
+                let numbers: Vec<u32> = value
+                    .split(',')
+                    .map(|part| part.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("{} expects three numbers like 12,2,2", arg))?;
+                let [teeth, depth, bore] = numbers[..] else {
+                    return Err(format!("{} expects three numbers like 12,2,2", arg));
+                };
+                if !(MIN_GEAR_TEETH..=MAX_GEAR_TEETH).contains(&teeth) {
+                    return Err(format!(
+                        "{} needs {} to {} teeth",
+                        arg, MIN_GEAR_TEETH, MAX_GEAR_TEETH
+                    ));
+                }
+                (
+                    parsed.shape.teeth,
+                    parsed.shape.tooth_depth,
+                    parsed.shape.bore_radius,
+                ) = (teeth, depth, bore);
+            }
+            "--frequency" => {
+                parsed.shape.frequency = parse_number(next_value(&mut iter, arg)?, arg)?
+            }
+            "--amplitude" => {
+                let value = next_value(&mut iter, arg)?;
+                parsed.shape.amplitude = value
+                    .parse()
+                    .ok()
+                    .filter(|amplitude| (0.0..=1.0).contains(amplitude))
+                    .ok_or_else(|| {
+                        format!("{} expects a number from 0 to 1, got '{}'", arg, value)
+                    })?;
+            }
+            "--threshold" => {
+                let value = next_value(&mut iter, arg)?;
+                parsed.shape.threshold = value
+                    .parse()
+                    .map_err(|_| format!("{} expects a number, got '{}'", arg, value))?;
+            }
+            "--formula" => parsed.shape.expression = next_value(&mut iter, arg)?.to_string(),
+            "--spacing" => {
+                let value = next_value(&mut iter, arg)?;
+                let spacing: u32 = value
+                    .parse()
+                    .map_err(|_| format!("{} expects a number, got '{}'", arg, value))?;
+                if spacing > MAX_LETTER_SPACING {
+                    return Err(format!("{} must be at most {}", arg, MAX_LETTER_SPACING));
+                }
+                parsed.shape.letter_spacing = spacing;
+            }
+            "--text" => parsed.shape.text = next_value(&mut iter, arg)?.to_string(),
+            "--export" => parsed.export = Some(next_value(&mut iter, arg)?.to_string()),
+            other => return Err(format!("unknown option '{}'", other)),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn next_value<'a>(
+    iter: &mut impl Iterator<Item = &'a String>,
+    flag: &str,
+) -> Result<&'a str, String> {
+    iter.next()
+        .map(String::as_str)
+        .ok_or_else(|| format!("{} needs a value", flag))
+}
+
+// Matches shape names case-insensitively, ignoring '-' and '_' ("square-pyramid" == "SquarePyramid")
+fn parse_shape(name: &str) -> Result<GeometricShape, String> {
+    let wanted: String = name
+        .chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .collect::<String>()
+        .to_lowercase();
+    GeometricShape::iter()
+        .find(|shape| shape.to_string().to_lowercase() == wanted)
+        .ok_or_else(|| format!("unknown shape '{}'", name))
+}
+
+fn parse_number(value: &str, flag: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite() && *number > 0.0)
+        .ok_or_else(|| format!("{} expects a positive number, got '{}'", flag, value))
+}
+
+fn parse_axis(name: &str) -> Result<Axis, String> {
+    Axis::iter()
+        .find(|axis| axis.to_string().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown axis '{}', expected x, y or z", name))
+}
+
+fn parse_dimension(value: &str, flag: &str) -> Result<u32, String> {
+    let dimension: u32 = value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got '{}'", flag, value))?;
+    if !(MIN_DIMENSION..=MAX_DIMENSION).contains(&dimension) {
+        return Err(format!(
+            "{} must be between {} and {}",
+            flag, MIN_DIMENSION, MAX_DIMENSION
+        ));
+    }
+    Ok(dimension)
+}
@@ -0,0 +1,902 @@
+use crate::shapes::{
+    palette_color, palette_index, translate_voxels, HeightGradient, SceneData, Shape, VoxelCoord,
+    VoxelData, VoxelGrid, MAX_DIMENSION, NEIGHBOR_OFFSETS, UNPAINTED,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const VXL_MAGIC: &[u8; 4] = b"VXLR";
+const VXL_VERSION: u8 = 1;
+// Longest layer name accepted, so a corrupt length can't ask for gigabytes of memory
+const VXL_MAX_NAME_BYTES: usize = 64 * 1024;
+
+// Corners of each cube face, counter-clockwise when seen from outside.
+// Same order as NEIGHBOR_OFFSETS: +X, -X, +Y, -Y, +Z, -Z
+const FACE_CORNERS: [[VoxelCoord; 4]; 6] = [
+    [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)],
+    [(0, 0, 0), (0, 0, 1), (0, 1, 1), (0, 1, 0)],
+    [(0, 1, 0), (0, 1, 1), (1, 1, 1), (1, 1, 0)],
+    [(0, 0, 0), (1, 0, 0), (1, 0, 1), (0, 0, 1)],
+    [(0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1)],
+    [(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 0, 0)],
+];
+
+// Every voxel face not covered by a neighbor, as (voxel, index into NEIGHBOR_OFFSETS, palette
+// index). Voxels are visited in sorted order so exported files are the same on every run
+fn exposed_faces(voxel_data: &VoxelData) -> Vec<(VoxelCoord, usize, u8)> {
+    let voxels = &voxel_data.voxels;
+    let mut faces = Vec::new();
+    for voxel in voxels.to_sorted_vec() {
+        let color = voxels.color(&voxel).unwrap_or(UNPAINTED);
+        for (direction, empty) in voxels.neighbors_empty(voxel).into_iter().enumerate() {
+            if empty {
+                faces.push((voxel, direction, color));
+            }
+        }
+    }
+    faces
+}
+
+// Rectangle of exposed faces that all point in `direction`. `voxel` is the voxel with the
+// smallest coordinates it covers and `size` its extent in voxels along X, Y and Z,
+// 1 along the face normal. All the voxels it covers have palette index `color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quad {
+    pub voxel: VoxelCoord,
+    pub direction: usize,
+    pub size: VoxelCoord,
+    pub color: u8,
+}
+
+impl Quad {
+    // Corners in the same counter-clockwise order as FACE_CORNERS
+    pub fn corners(&self) -> [VoxelCoord; 4] {
+        let (x, y, z) = self.voxel;
+        let (sx, sy, sz) = self.size;
+        FACE_CORNERS[self.direction].map(|(cx, cy, cz)| (x + cx * sx, y + cy * sy, z + cz * sz))
+    }
+}
+
+// One quad per exposed face
+fn unit_quads(voxel_data: &VoxelData) -> Vec<Quad> {
+    exposed_faces(voxel_data)
+        .into_iter()
+        .map(|(voxel, direction, color)| Quad {
+            voxel,
+            direction,
+            size: (1, 1, 1),
+            color,
+        })
+        .collect()
+}
+
+// Merges exposed faces into as few rectangles as the usual greedy sweep finds: per direction
+// and slice, each rectangle grows along the first in-plane axis as far as it can, then along
+// the second while whole rows fit. Faces of different palette colors are never merged, and
+// with `split_layers` neither are unpainted faces of different Y layers, so per-layer
+// materials stay exact. Quads can meet in T-junctions, but they cover exactly the exposed
+// faces, so the surface stays closed.
+pub fn greedy_mesh(voxel_data: &VoxelData, split_layers: bool) -> Vec<Quad> {
+    // Exposed faces grouped by (direction, position along the normal, layer, color) as
+    // in-plane cells
+    let mut slices = BTreeMap::<_, BTreeSet<(i32, i32)>>::new();
+    for (voxel, direction, color) in exposed_faces(voxel_data) {
+        let coords = [voxel.0, voxel.1, voxel.2];
+        let (normal, u, v) = plane_axes(direction);
+        // Painted faces keep their palette color on every layer
+        let layer = if split_layers && color == UNPAINTED { voxel.1 } else { 0 };
+        slices
+            .entry((direction, coords[normal], layer, color))
+            .or_default()
+            .insert((coords[v], coords[u]));
+    }
+
+    let mut quads = Vec::new();
+    for ((direction, depth, _, color), mut cells) in slices {
+        let (normal, u, v) = plane_axes(direction);
+        // Cells are ordered by (v, u), so the first one is always a rectangle's corner
+        while let Some(&(start_v, start_u)) = cells.first() {
+            let mut width = 1;
+            while cells.contains(&(start_v, start_u + width)) {
+                width += 1;
+            }
+            let mut height = 1;
+            while (0..width).all(|du| cells.contains(&(start_v + height, start_u + du))) {
+                height += 1;
+            }
+            for dv in 0..height {
+                for du in 0..width {
+                    cells.remove(&(start_v + dv, start_u + du));
+                }
+            }
+
+            let mut voxel = [0; 3];
+            let mut size = [1; 3];
+            voxel[normal] = depth;
+            voxel[u] = start_u;
+            voxel[v] = start_v;
+            size[u] = width;
+            size[v] = height;
+            quads.push(Quad {
+                voxel: (voxel[0], voxel[1], voxel[2]),
+                direction,
+                size: (size[0], size[1], size[2]),
+                color,
+            });
+        }
+    }
+    quads
+}
+
+// Axis of the face normal and the two in-plane axes, as indices 0 = X, 1 = Y, 2 = Z
+fn plane_axes(direction: usize) -> (usize, usize, usize) {
+    match direction / 2 {
+        0 => (0, 1, 2),
+        1 => (1, 0, 2),
+        _ => (2, 0, 1),
+    }
+}
+
+// Faces to write: merged into large quads when optimizing, otherwise one per voxel face
+fn mesh_quads(voxel_data: &VoxelData, optimize: bool, split_layers: bool) -> Vec<Quad> {
+    if optimize {
+        greedy_mesh(voxel_data, split_layers)
+    } else {
+        unit_quads(voxel_data)
+    }
+}
+
+// One "o" group of an OBJ export: the voxels of `data` moved by `offset` voxels
+struct ObjPart<'a> {
+    name: String,
+    data: &'a VoxelData,
+    offset: VoxelCoord,
+}
+
+// Writes the voxels as a Wavefront OBJ file at `path`. With a gradient or painted voxels, a
+// .mtl file is written next to it with one material per palette color, `base_color` (sRGB)
+// standing in for unpainted voxels. With a gradient, unpainted faces use a material per
+// layer instead. `optimize` merges coplanar faces into larger quads (see `greedy_mesh`).
+pub fn export_to_obj(
+    voxel_data: &VoxelData,
+    path: &str,
+    base_color: [u8; 3],
+    gradient: Option<&HeightGradient>,
+    optimize: bool,
+) -> io::Result<()> {
+    let part = ObjPart {
+        name: voxel_data.shape.kind.to_string(),
+        data: voxel_data,
+        offset: (0, 0, 0),
+    };
+    write_obj_file(&[part], voxel_data, path, base_color, gradient, optimize)
+}
+
+// Writes the visible objects of the scene as one OBJ file, each as its own "o" group placed
+// like in the viewport. With `merge` they become a single object instead, without the
+// faces where objects touch.
+pub fn export_scene_to_obj(
+    scene: &SceneData,
+    path: &str,
+    base_color: [u8; 3],
+    gradient: Option<&HeightGradient>,
+    merge: bool,
+    optimize: bool,
+) -> io::Result<()> {
+    if merge {
+        return export_to_obj(&scene.merged(), path, base_color, gradient, optimize);
+    }
+    let parts: Vec<ObjPart> = scene
+        .objects
+        .iter()
+        .filter(|object| object.visible)
+        .map(|object| ObjPart {
+            name: object.name.clone(),
+            data: &object.data,
+            offset: object.offset,
+        })
+        .collect();
+    write_obj_file(&parts, scene.origin(), path, base_color, gradient, optimize)
+}
+
+// Writes the voxels as a Wavefront OBJ mesh. Faces shared by two voxels are skipped
+// and corners are shared, so the result is a single closed surface.
+pub fn export_obj_to_writer<W: Write>(
+    voxel_data: &VoxelData,
+    writer: &mut W,
+    optimize: bool,
+) -> io::Result<()> {
+    let part = ObjPart {
+        name: voxel_data.shape.kind.to_string(),
+        data: voxel_data,
+        offset: (0, 0, 0),
+    };
+    write_obj(&[part], voxel_data, writer, None, false, optimize)
+}
+
+fn write_obj_file(
+    parts: &[ObjPart],
+    origin: &VoxelData,
+    path: &str,
+    base_color: [u8; 3],
+    gradient: Option<&HeightGradient>,
+    optimize: bool,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let painted = parts.iter().any(|part| part.data.voxels.is_painted());
+    if gradient.is_some() || painted {
+        let mtl_path = Path::new(path).with_extension("mtl");
+        let mtl_name = mtl_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("exported_shape.mtl")
+            .to_string();
+        let mut mtl_writer = BufWriter::new(File::create(&mtl_path)?);
+        write_mtl(parts, base_color, gradient, &mut mtl_writer)?;
+        mtl_writer.flush()?;
+        let by_layer = gradient.is_some();
+        write_obj(parts, origin, &mut writer, Some(&mtl_name), by_layer, optimize)?;
+    } else {
+        write_obj(parts, origin, &mut writer, None, false, optimize)?;
+    }
+    writer.flush()
+}
+
+// Writes one "color_<palette index>" material per color the voxels use. With a gradient,
+// unpainted voxels get one "height_<grid height>_<y>" material per layer of each grid
+// height instead.
+fn write_mtl<W: Write>(
+    parts: &[ObjPart],
+    base_color: [u8; 3],
+    gradient: Option<&HeightGradient>,
+    writer: &mut W,
+) -> io::Result<()> {
+    writeln!(writer, "# Exported by 3D Shape Sculptor")?;
+    let mut colors: BTreeSet<u8> = parts
+        .iter()
+        .flat_map(|part| part.data.voxels.colored().map(|(_, color)| color))
+        .collect();
+    if let Some(gradient) = gradient {
+        colors.remove(&UNPAINTED);
+        let heights: BTreeSet<u32> = parts.iter().map(|part| part.data.shape.height).collect();
+        for &height in &heights {
+            for y in 0..height as i32 {
+                let name = format!("height_{}_{}", height, y);
+                write_material(writer, &name, gradient.color_at(y, height))?;
+            }
+        }
+    }
+    for color in colors {
+        let name = format!("color_{}", color);
+        write_material(writer, &name, palette_color(color, base_color))?;
+    }
+    Ok(())
+}
+
+fn write_material<W: Write>(writer: &mut W, name: &str, [r, g, b]: [u8; 3]) -> io::Result<()> {
+    writeln!(writer, "newmtl {}", name)?;
+    writeln!(
+        writer,
+        "Kd {:.4} {:.4} {:.4}",
+        r as f32 / 255.0,
+        g as f32 / 255.0,
+        b as f32 / 255.0
+    )
+}
+
+// Shared OBJ writer. Vertices are placed in `origin`'s grid, in grid units. With `mtllib`
+// set, each object's faces are grouped by palette color under "usemtl color_<index>", and
+// with `by_layer` its unpainted faces by layer under "usemtl height_<grid height>_<y>".
+// Every face references one of six normals (the NEIGHBOR_OFFSETS directions, written once
+// up front) and texture coordinates in voxel units, so a texture repeats once per voxel.
+fn write_obj<W: Write>(
+    parts: &[ObjPart],
+    origin: &VoxelData,
+    writer: &mut W,
+    mtllib: Option<&str>,
+    by_layer: bool,
+    optimize: bool,
+) -> io::Result<()> {
+    writeln!(writer, "# Exported by 3D Shape Sculptor")?;
+    if let Some(mtllib) = mtllib {
+        writeln!(writer, "mtllib {}", mtllib)?;
+    }
+    for (nx, ny, nz) in NEIGHBOR_OFFSETS {
+        writeln!(writer, "vn {} {} {}", nx, ny, nz)?;
+    }
+
+    // Vertices and texture coordinates are shared across objects, so indices keep counting
+    // up between groups
+    let mut vertex_indices: HashMap<VoxelCoord, usize> = HashMap::new();
+    let mut uv_indices: HashMap<(i32, i32), usize> = HashMap::new();
+    for part in parts {
+        let (dx, dy, dz) = part.offset;
+        let mut new_vertices: Vec<VoxelCoord> = Vec::new();
+        let mut new_uvs: Vec<(i32, i32)> = Vec::new();
+        // Faces keyed by (color, layer), so each material is selected only once. Each corner
+        // is a (vertex, texture coordinate, normal) index triple
+        let mut faces: BTreeMap<(u8, i32), Vec<[[usize; 3]; 4]>> = BTreeMap::new();
+
+        for quad in mesh_quads(part.data, optimize, by_layer) {
+            let (_, u, v) = plane_axes(quad.direction);
+            let start = [quad.voxel.0, quad.voxel.1, quad.voxel.2];
+            let mut face = [[0; 3]; 4];
+            for (slot, (cx, cy, cz)) in face.iter_mut().zip(quad.corners()) {
+                let corner = (cx + dx, cy + dy, cz + dz);
+                let next_index = vertex_indices.len() + 1; // OBJ indices are 1-based
+                let vertex = *vertex_indices.entry(corner).or_insert_with(|| {
+                    new_vertices.push(corner);
+                    next_index
+                });
+
+                let coords = [cx, cy, cz];
+                let uv = (coords[u] - start[u], coords[v] - start[v]);
+                let next_index = uv_indices.len() + 1;
+                let texture = *uv_indices.entry(uv).or_insert_with(|| {
+                    new_uvs.push(uv);
+                    next_index
+                });
+
+                *slot = [vertex, texture, quad.direction + 1];
+            }
+            let layer = if by_layer && quad.color == UNPAINTED { quad.voxel.1 } else { 0 };
+            faces.entry((quad.color, layer)).or_default().push(face);
+        }
+
+        writeln!(writer, "o {}", part.name)?;
+        for &corner in &new_vertices {
+            let [x, y, z] = origin.corner_position(corner).to_array();
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for &(u, v) in &new_uvs {
+            writeln!(writer, "vt {} {}", u, v)?;
+        }
+        for ((color, layer), material_faces) in faces {
+            if mtllib.is_some() {
+                if by_layer && color == UNPAINTED {
+                    writeln!(writer, "usemtl height_{}_{}", part.data.shape.height, layer)?;
+                } else {
+                    writeln!(writer, "usemtl color_{}", color)?;
+                }
+            }
+            for face in material_faces {
+                write!(writer, "f")?;
+                for [vertex, texture, normal] in face {
+                    write!(writer, " {}/{}/{}", vertex, texture, normal)?;
+                }
+                writeln!(writer)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Parses a voxel list with one "x y z" triple of integers per line (commas also work).
+// Blank lines and lines starting with '#' are skipped. The voxels are shifted so the
+// smallest coordinate on each axis becomes 0, and the grid is sized to fit them.
+// The remaining shape parameters are copied from `base`.
+pub fn parse_voxel_list(text: &str, base: &Shape) -> Result<VoxelData, String> {
+    let mut voxels = VoxelGrid::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let values: Vec<i32> = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|part| !part.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Line {}: '{}' is not a list of integers", number + 1, line))?;
+        match values[..] {
+            [x, y, z] => {
+                voxels.insert((x, y, z));
+            }
+            _ => {
+                return Err(format!(
+                    "Line {}: expected 3 integers, found {}",
+                    number + 1,
+                    values.len()
+                ))
+            }
+        }
+    }
+
+    let Some((min, max)) = voxels.bounding_box() else {
+        return Err("The voxel list is empty".into());
+    };
+    let extents = [max.0 - min.0 + 1, max.1 - min.1 + 1, max.2 - min.2 + 1];
+    if extents.iter().any(|&extent| extent > MAX_DIMENSION as i32) {
+        return Err(format!(
+            "The voxels span {}x{}x{}, more than the {} voxel limit per axis",
+            extents[0], extents[1], extents[2], MAX_DIMENSION
+        ));
+    }
+
+    Ok(VoxelData {
+        shape: Shape {
+            width: extents[0] as u32,
+            height: extents[1] as u32,
+            depth: extents[2] as u32,
+            ..base.clone()
+        },
+        voxels: translate_voxels(&voxels, -min.0, -min.1, -min.2),
+    })
+}
+
+// --- .vxl scene files ---
+
+// One named layer of a .vxl file; every voxel carries its own RGBA color
+#[derive(Debug, Clone, PartialEq)]
+pub struct VxlLayer {
+    pub name: String,
+    pub visible: bool,
+    pub voxels: Vec<(VoxelCoord, [u8; 4])>,
+}
+
+// Saves layers in the compact .vxl format: "VXLR", a version byte, then per layer the name
+// (u32 length + UTF-8), a visibility byte, a u32 voxel count and per voxel 3 x i16
+// coordinates + RGBA. All integers are little-endian; a 32^3 grid fits in about 330 KB.
+pub fn save_vxl(layers: &[VxlLayer], path: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_vxl(layers, &mut writer)?;
+    writer.flush()
+}
+
+pub fn load_vxl(path: &str) -> io::Result<Vec<VxlLayer>> {
+    read_vxl(&mut BufReader::new(File::open(path)?))
+}
+
+pub fn write_vxl<W: Write>(layers: &[VxlLayer], writer: &mut W) -> io::Result<()> {
+    writer.write_all(VXL_MAGIC)?;
+    writer.write_all(&[VXL_VERSION])?;
+    for layer in layers {
+        if layer.name.len() > VXL_MAX_NAME_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Layer name is longer than {} bytes", VXL_MAX_NAME_BYTES),
+            ));
+        }
+        writer.write_all(&(layer.name.len() as u32).to_le_bytes())?;
+        writer.write_all(layer.name.as_bytes())?;
+        writer.write_all(&[layer.visible as u8])?;
+        writer.write_all(&(layer.voxels.len() as u32).to_le_bytes())?;
+        for &((x, y, z), rgba) in &layer.voxels {
+            for coord in [x, y, z] {
+                let coord = i16::try_from(coord).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Coordinate {} does not fit the .vxl format", coord),
+                    )
+                })?;
+                writer.write_all(&coord.to_le_bytes())?;
+            }
+            writer.write_all(&rgba)?;
+        }
+    }
+    Ok(())
+}
+
+// Reads layers until the end of the input. A wrong magic number, an unknown version or an
+// overlong layer name is reported as InvalidData, a file cut off in the middle of a layer as UnexpectedEof.
+pub fn read_vxl<R: Read>(reader: &mut R) -> io::Result<Vec<VxlLayer>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut header = [0u8; 5];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| invalid("Not a .vxl file (header too short)"))?;
+    if &header[..4] != VXL_MAGIC {
+        return Err(invalid("Not a .vxl file (wrong magic number)"));
+    }
+    if header[4] != VXL_VERSION {
+        return Err(invalid(&format!("Unsupported .vxl version {}", header[4])));
+    }
+
+    let mut layers = Vec::new();
+    loop {
+        // A clean end of file can only come before a layer starts
+        let mut name_length = [0u8; 4];
+        match reader.read(&mut name_length[..1])? {
+            0 => break,
+            _ => reader.read_exact(&mut name_length[1..])?,
+        }
+        let name_length = u32::from_le_bytes(name_length) as usize;
+        if name_length > VXL_MAX_NAME_BYTES {
+            return Err(invalid(&format!("Layer name of {} bytes is too long", name_length)));
+        }
+        let mut name = vec![0u8; name_length];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8(name).map_err(|_| invalid("Layer name is not valid UTF-8"))?;
+
+        let mut visible = [0u8; 1];
+        reader.read_exact(&mut visible)?;
+        let mut count = [0u8; 4];
+        reader.read_exact(&mut count)?;
+
+        let count = u32::from_le_bytes(count) as usize;
+        let mut voxels = Vec::with_capacity(count.min(1 << 20));
+        let mut record = [0u8; 10];
+        for _ in 0..count {
+            reader.read_exact(&mut record)?;
+            let coord = |i: usize| i16::from_le_bytes([record[i], record[i + 1]]) as i32;
+            let voxel = (coord(0), coord(2), coord(4));
+            voxels.push((voxel, [record[6], record[7], record[8], record[9]]));
+        }
+
+        layers.push(VxlLayer {
+            name,
+            visible: visible[0] != 0,
+            voxels,
+        });
+    }
+    Ok(layers)
+}
+
+// One layer per scene object, with the object's offset applied to its voxels.
+// `color` gives the RGB color of a voxel from its layer, its object's grid height and its
+// palette index
+pub fn scene_to_vxl_layers(
+    scene: &SceneData,
+    color: impl Fn(i32, u32, u8) -> [u8; 3],
+) -> Vec<VxlLayer> {
+    scene
+        .objects
+        .iter()
+        .map(|object| {
+            let (dx, dy, dz) = object.offset;
+            let height = object.data.shape.height;
+            VxlLayer {
+                name: object.name.clone(),
+                visible: object.visible,
+                voxels: object
+                    .data
+                    .voxels
+                    .to_sorted_vec()
+                    .into_iter()
+                    .map(|(x, y, z)| {
+                        let paint = object.data.voxels.color(&(x, y, z)).unwrap_or(UNPAINTED);
+                        let [r, g, b] = color(y, height, paint);
+                        ((x + dx, y + dy, z + dz), [r, g, b, 255])
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+// Turns every non-empty layer into a scene object. Voxels whose color is in the PALETTE are
+// painted with it, the others are left unpainted. Each object's grid is fitted to its voxels
+// and the offset keeps it where it was. Other shape settings are copied from `base`.
+// Returns None if the layers hold no voxels at all.
+pub fn vxl_layers_to_scene(layers: &[VxlLayer], base: &Shape) -> Option<SceneData> {
+    let mut objects = Vec::new();
+    for layer in layers {
+        let voxels: VoxelGrid = layer
+            .voxels
+            .iter()
+            .map(|&(voxel, [r, g, b, _])| (voxel, palette_index([r, g, b])))
+            .collect();
+        let Some((min, max)) = voxels.bounding_box() else {
+            continue;
+        };
+        let data = VoxelData {
+            shape: Shape {
+                width: (max.0 - min.0 + 1) as u32,
+                height: (max.1 - min.1 + 1) as u32,
+                depth: (max.2 - min.2 + 1) as u32,
+                ..base.clone()
+            },
+            voxels: translate_voxels(&voxels, -min.0, -min.1, -min.2),
+        };
+        objects.push((layer, data, min));
+    }
+
+    let mut objects = objects.into_iter();
+    let (layer, data, offset) = objects.next()?;
+    let mut scene = SceneData::new(data);
+    let describe = |scene: &mut SceneData, index: usize, layer: &VxlLayer, offset| {
+        let object = &mut scene.objects[index];
+        object.name = layer.name.clone();
+        object.visible = layer.visible;
+        object.offset = offset;
+    };
+    describe(&mut scene, 0, layer, offset);
+    for (layer, data, offset) in objects {
+        let index = scene.add_object(data);
+        describe(&mut scene, index, layer, offset);
+    }
+    Some(scene)
+}
+
+// Writes the voxels as a PLY mesh, ASCII or binary_little_endian. Every exposed voxel face
+// is a quad and corners are shared between faces of the same color, so a single-color mesh
+// is watertight; vertices carry the voxel's palette color, `base_color` (sRGB) for unpainted
+// voxels.
+pub fn export_to_ply(
+    voxel_data: &VoxelData,
+    path: &str,
+    base_color: [u8; 3],
+    binary: bool,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_ply(voxel_data, &mut writer, base_color, binary)?;
+    writer.flush()
+}
+
+pub fn write_ply<W: Write>(
+    voxel_data: &VoxelData,
+    writer: &mut W,
+    base_color: [u8; 3],
+    binary: bool,
+) -> io::Result<()> {
+    // The header needs the element counts, so the whole mesh is built first. A corner where
+    // colors meet gets one vertex per color
+    let mut vertex_indices: HashMap<(VoxelCoord, u8), u32> = HashMap::new();
+    let mut vertices: Vec<([f32; 3], [u8; 3])> = Vec::new();
+    let mut faces: Vec<[u32; 4]> = Vec::new();
+    for quad in unit_quads(voxel_data) {
+        let mut face = [0; 4];
+        for (slot, corner) in face.iter_mut().zip(quad.corners()) {
+            *slot = *vertex_indices.entry((corner, quad.color)).or_insert_with(|| {
+                let position = voxel_data.corner_position(corner).to_array();
+                vertices.push((position, palette_color(quad.color, base_color)));
+                vertices.len() as u32 - 1
+            });
+        }
+        faces.push(face);
+    }
+
+    let format = if binary {
+        "binary_little_endian"
+    } else {
+        "ascii"
+    };
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {} 1.0", format)?;
+    writeln!(writer, "comment Exported by 3D Shape Sculptor")?;
+    writeln!(writer, "element vertex {}", vertices.len())?;
+    for property in ["float x", "float y", "float z"] {
+        writeln!(writer, "property {}", property)?;
+    }
+    for property in ["uchar red", "uchar green", "uchar blue"] {
+        writeln!(writer, "property {}", property)?;
+    }
+    writeln!(writer, "element face {}", faces.len())?;
+    writeln!(writer, "property list uchar int vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    if binary {
+        for (position, color) in &vertices {
+            for value in position {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(color)?;
+        }
+        for face in &faces {
+            writer.write_all(&[face.len() as u8])?;
+            for &index in face {
+                writer.write_all(&(index as i32).to_le_bytes())?;
+            }
+        }
+    } else {
+        for ([x, y, z], [r, g, b]) in &vertices {
+            writeln!(writer, "{} {} {} {} {} {}", x, y, z, r, g, b)?;
+        }
+        for [a, b, c, d] in &faces {
+            writeln!(writer, "4 {} {} {} {}", a, b, c, d)?;
+        }
+    }
+    Ok(())
+}
+
+// Writes the voxels as a glTF 2.0 asset: `path` holds the JSON and a .bin file next to it
+// holds positions, normals and indices. Hidden faces are skipped like in the OBJ export,
+// and every face gets its own four vertices so normals stay flat. Each palette color gets
+// its own primitive and material, `base_color` (sRGB) standing in for unpainted voxels.
+// `optimize` merges coplanar faces like the OBJ export.
+#[cfg(feature = "gltf-export")]
+pub fn export_to_gltf(
+    voxel_data: &VoxelData,
+    path: &str,
+    base_color: [u8; 3],
+    optimize: bool,
+) -> io::Result<()> {
+    let bin_path = Path::new(path).with_extension("bin");
+    let bin_name = bin_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("exported_shape.bin")
+        .to_string();
+
+    let (document, buffer) = gltf_document(voxel_data, base_color, optimize, Some(bin_name))?;
+    std::fs::write(&bin_path, &buffer)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &document)?;
+    writer.flush()
+}
+
+// Writes the same asset as `export_to_gltf` as a single binary .glb file, with the buffer
+// embedded after the JSON
+#[cfg(feature = "gltf-export")]
+pub fn export_to_glb(
+    voxel_data: &VoxelData,
+    path: &str,
+    base_color: [u8; 3],
+    optimize: bool,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_glb(voxel_data, &mut writer, base_color, optimize)?;
+    writer.flush()
+}
+
+// GLB layout: a 12-byte header (magic, version, total length) followed by a JSON chunk and
+// a BIN chunk, each with its length and type first and padded to four bytes
+#[cfg(feature = "gltf-export")]
+pub fn write_glb<W: Write>(
+    voxel_data: &VoxelData,
+    writer: &mut W,
+    base_color: [u8; 3],
+    optimize: bool,
+) -> io::Result<()> {
+    const GLB_VERSION: u32 = 2;
+    const CHUNK_JSON: u32 = 0x4E4F534A;
+    const CHUNK_BIN: u32 = 0x004E4942;
+
+    let (document, mut buffer) = gltf_document(voxel_data, base_color, optimize, None)?;
+    let mut json = serde_json::to_vec(&document)?;
+    // The JSON chunk is padded with spaces and the BIN chunk with zeros
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    while buffer.len() % 4 != 0 {
+        buffer.push(0);
+    }
+
+    let total = 12 + 8 + json.len() + 8 + buffer.len();
+    writer.write_all(b"glTF")?;
+    writer.write_all(&GLB_VERSION.to_le_bytes())?;
+    writer.write_all(&(total as u32).to_le_bytes())?;
+    for (chunk_type, data) in [(CHUNK_JSON, &json), (CHUNK_BIN, &buffer)] {
+        writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        writer.write_all(&chunk_type.to_le_bytes())?;
+        writer.write_all(data)?;
+    }
+    Ok(())
+}
+
+// Builds the glTF JSON and its binary buffer. `buffer_uri` names the external .bin file;
+// None leaves the buffer without a uri, as GLB expects for its embedded BIN chunk. An empty
+// model is an error, since glTF has no valid way to hold an empty mesh
+#[cfg(feature = "gltf-export")]
+fn gltf_document(
+    voxel_data: &VoxelData,
+    base_color: [u8; 3],
+    optimize: bool,
+    buffer_uri: Option<String>,
+) -> io::Result<(serde_json::Value, Vec<u8>)> {
+    use serde_json::json;
+
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+    const TRIANGLES: u32 = 4;
+
+    if voxel_data.voxels.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The model has no voxels to export",
+        ));
+    }
+
+    // Faces grouped by palette color, one primitive each
+    let mut groups: BTreeMap<u8, Vec<Quad>> = BTreeMap::new();
+    for quad in mesh_quads(voxel_data, optimize, false) {
+        groups.entry(quad.color).or_default().push(quad);
+    }
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut primitives = Vec::new();
+    let mut materials = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    for (index, (color, quads)) in groups.into_iter().enumerate() {
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        for quad in quads {
+            let (nx, ny, nz) = NEIGHBOR_OFFSETS[quad.direction];
+            let base = positions.len() as u32;
+            for corner in quad.corners() {
+                positions.push(voxel_data.corner_position(corner).to_array());
+                normals.push([nx as f32, ny as f32, nz as f32]);
+            }
+            // Two counter-clockwise triangles per quad
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        // POSITION accessors must declare their bounds
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for position in &positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+
+        // Positions, normals and indices of each primitive follow each other in the buffer
+        let offset = buffer.len();
+        for value in positions.iter().chain(normals.iter()).flatten() {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in &indices {
+            buffer.extend_from_slice(&index.to_le_bytes());
+        }
+        let vertex_bytes = positions.len() * 12;
+        let index_bytes = indices.len() * 4;
+
+        // One buffer view and one accessor per attribute, so their indices line up
+        let view = accessors.len();
+        buffer_views.extend([
+            json!({ "buffer": 0, "byteOffset": offset, "byteLength": vertex_bytes, "target": ARRAY_BUFFER }),
+            json!({ "buffer": 0, "byteOffset": offset + vertex_bytes, "byteLength": vertex_bytes, "target": ARRAY_BUFFER }),
+            json!({ "buffer": 0, "byteOffset": offset + 2 * vertex_bytes, "byteLength": index_bytes, "target": ELEMENT_ARRAY_BUFFER }),
+        ]);
+        accessors.extend([
+            json!({ "bufferView": view, "componentType": FLOAT, "count": positions.len(), "type": "VEC3", "min": min, "max": max }),
+            json!({ "bufferView": view + 1, "componentType": FLOAT, "count": normals.len(), "type": "VEC3" }),
+            json!({ "bufferView": view + 2, "componentType": UNSIGNED_INT, "count": indices.len(), "type": "SCALAR" }),
+        ]);
+        primitives.push(json!({
+            "attributes": { "POSITION": view, "NORMAL": view + 1 },
+            "indices": view + 2,
+            "material": index,
+            "mode": TRIANGLES,
+        }));
+
+        // glTF colors are linear, the editor colors are sRGB
+        let rgb = palette_color(color, base_color);
+        let [r, g, b] = rgb.map(|c| srgb_to_linear(c as f32 / 255.0));
+        materials.push(json!({
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [r, g, b, 1.0],
+                "metallicFactor": 0.1,
+                "roughnessFactor": 0.8,
+            },
+        }));
+    }
+
+    let mut gltf_buffer = json!({ "byteLength": buffer.len() });
+    if let Some(uri) = buffer_uri {
+        gltf_buffer["uri"] = json!(uri);
+    }
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "3D Shape Sculptor" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "name": voxel_data.shape.kind.to_string() }],
+        "meshes": [{ "primitives": primitives }],
+        "materials": materials,
+        "buffers": [gltf_buffer],
+        "bufferViews": buffer_views,
+        "accessors": accessors,
+    });
+
+    Ok((document, buffer))
+}
+
+#[cfg(feature = "gltf-export")]
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,224 @@
+// Small math expression language for the Formula shape: numbers, the variables x, y and z,
+// + - * / with the usual precedence, unary minus, parentheses and the functions
+// sin, cos, sqrt and abs. A single trailing comparison is allowed: "a > b" evaluates
+// to a - b and "a < b" to b - a, so it is positive exactly where the comparison holds.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f32),
+    Variable(Variable),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Sin,
+    Cos,
+    Sqrt,
+    Abs,
+}
+
+impl Expr {
+    pub fn eval(&self, x: f32, y: f32, z: f32) -> f32 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Variable(Variable::X) => x,
+            Expr::Variable(Variable::Y) => y,
+            Expr::Variable(Variable::Z) => z,
+            Expr::Negate(inner) => -inner.eval(x, y, z),
+            Expr::Binary(op, left, right) => {
+                let (a, b) = (left.eval(x, y, z), right.eval(x, y, z));
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Subtract => a - b,
+                    BinaryOp::Multiply => a * b,
+                    BinaryOp::Divide => a / b,
+                }
+            }
+            Expr::Call(function, argument) => {
+                let value = argument.eval(x, y, z);
+                match function {
+                    Function::Sin => value.sin(),
+                    Function::Cos => value.cos(),
+                    Function::Sqrt => value.sqrt(),
+                    Function::Abs => value.abs(),
+                }
+            }
+        }
+    }
+}
+
+// Parses a whole expression; errors name the character position (starting at 1)
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+    };
+    let expr = parser.comparison()?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(expr),
+        Some(c) => Err(parser.error(&format!("unexpected '{}'", c))),
+    }
+}
+
+// Recursive descent parser, one method per precedence level
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    // comparison := expression (('>' | '<') expression)?
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.expression()?;
+        let greater = match self.next_symbol() {
+            Some('>') => true,
+            Some('<') => false,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.expression()?;
+        Ok(if greater {
+            Expr::Binary(BinaryOp::Subtract, Box::new(left), Box::new(right))
+        } else {
+            Expr::Binary(BinaryOp::Subtract, Box::new(right), Box::new(left))
+        })
+    }
+
+    // expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+        loop {
+            let op = match self.next_symbol() {
+                Some('+') => BinaryOp::Add,
+                Some('-') => BinaryOp::Subtract,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.term()?));
+        }
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = match self.next_symbol() {
+                Some('*') => BinaryOp::Multiply,
+                Some('/') => BinaryOp::Divide,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    // unary := '-' unary | primary
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.next_symbol() == Some('-') {
+            self.position += 1;
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    // primary := number | variable | function '(' expression ')' | '(' expression ')'
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next_symbol() {
+            Some('(') => {
+                self.position += 1;
+                let expr = self.expression()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => self.name(),
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of formula")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expr, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse().map(Expr::Number).map_err(|_| {
+            self.position = start;
+            self.error(&format!("invalid number '{}'", text))
+        })
+    }
+
+    fn name(&mut self) -> Result<Expr, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+            self.position += 1;
+        }
+        let name: String = self.chars[start..self.position].iter().collect();
+        let function = match name.as_str() {
+            "x" => return Ok(Expr::Variable(Variable::X)),
+            "y" => return Ok(Expr::Variable(Variable::Y)),
+            "z" => return Ok(Expr::Variable(Variable::Z)),
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "sqrt" => Function::Sqrt,
+            "abs" => Function::Abs,
+            _ => {
+                self.position = start;
+                return Err(self.error(&format!("unknown name '{}'", name)));
+            }
+        };
+        self.expect('(')?;
+        let argument = self.expression()?;
+        self.expect(')')?;
+        Ok(Expr::Call(function, Box::new(argument)))
+    }
+
+    fn expect(&mut self, wanted: char) -> Result<(), String> {
+        if self.next_symbol() == Some(wanted) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", wanted)))
+        }
+    }
+
+    // Next character after any whitespace, without consuming it
+    fn next_symbol(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.peek()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position + 1)
+    }
+}
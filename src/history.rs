@@ -0,0 +1,136 @@
+use crate::shapes::{Shape, VoxelCoord, VoxelData, VoxelGrid};
+use bevy::prelude::Resource;
+use std::collections::HashMap;
+
+// Steps kept per object; the oldest are dropped first
+pub const MAX_UNDO_STEPS: usize = 100;
+
+// What one edit changed: every cell whose contents differ, holding (before, after) palette
+// indices with None for an empty cell, and the grid shape before and after
+#[derive(Debug, Clone, PartialEq)]
+struct Change {
+    cells: Vec<(VoxelCoord, Option<u8>, Option<u8>)>,
+    shape: (Shape, Shape),
+}
+
+impl Change {
+    fn between(before: &VoxelData, after: &VoxelData) -> Self {
+        let mut cells: Vec<_> = before
+            .voxels
+            .colored()
+            .filter(|&(cell, color)| after.voxels.color(&cell) != Some(color))
+            .map(|(cell, color)| (cell, Some(color), after.voxels.color(&cell)))
+            .collect();
+        cells.extend(
+            after
+                .voxels
+                .colored()
+                .filter(|(cell, _)| !before.voxels.contains(cell))
+                .map(|(cell, color)| (cell, None, Some(color))),
+        );
+        Self {
+            cells,
+            shape: (before.shape.clone(), after.shape.clone()),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.cells.is_empty() && self.shape.0 == self.shape.1
+    }
+
+    // Turns the state after the change back into the one before it, or the other way round
+    fn apply(&self, data: &mut VoxelData, backwards: bool) {
+        for &(cell, before, after) in &self.cells {
+            let color = if backwards { before } else { after };
+            set_cell(&mut data.voxels, cell, color);
+        }
+        let (before, after) = &self.shape;
+        let shape = if backwards { before } else { after };
+        data.shape = shape.clone();
+    }
+}
+
+fn set_cell(voxels: &mut VoxelGrid, cell: VoxelCoord, color: Option<u8>) {
+    voxels.remove(&cell);
+    if let Some(color) = color {
+        voxels.insert_colored(cell, color);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Stacks {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+// Undo and redo for the voxels of each scene object, keyed by object id. Edits are recorded
+// as the difference to the previous state, so only the latest state is kept in full
+#[derive(Resource, Debug, Clone, Default)]
+pub struct History {
+    // The object being edited and its state when last recorded
+    current: Option<(u32, VoxelData)>,
+    stacks: HashMap<u32, Stacks>,
+}
+
+impl History {
+    // Records `data` as the new state of object `id`. The first state recorded after
+    // switching objects starts from there instead of counting as an edit
+    pub fn record(&mut self, id: u32, data: &VoxelData) {
+        if let Some((current_id, previous)) = &self.current {
+            let change = Change::between(previous, data);
+            if *current_id == id && !change.is_empty() {
+                let stacks = self.stacks.entry(id).or_default();
+                stacks.undo.push(change);
+                if stacks.undo.len() > MAX_UNDO_STEPS {
+                    stacks.undo.remove(0);
+                }
+                stacks.redo.clear();
+            }
+        }
+        self.current = Some((id, data.clone()));
+    }
+
+    pub fn can_undo(&self, id: u32) -> bool {
+        self.stacks
+            .get(&id)
+            .is_some_and(|stacks| !stacks.undo.is_empty())
+    }
+
+    pub fn can_redo(&self, id: u32) -> bool {
+        self.stacks
+            .get(&id)
+            .is_some_and(|stacks| !stacks.redo.is_empty())
+    }
+
+    // The state of object `id` before its last edit, if there is one to undo
+    pub fn undo(&mut self, id: u32) -> Option<VoxelData> {
+        self.step(id, true)
+    }
+
+    // The state an undo of object `id` went back from, if there is one to redo
+    pub fn redo(&mut self, id: u32) -> Option<VoxelData> {
+        self.step(id, false)
+    }
+
+    // Forgets every object's history, e.g. when a new scene is loaded
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn step(&mut self, id: u32, backwards: bool) -> Option<VoxelData> {
+        let (current_id, data) = self.current.as_mut()?;
+        let stacks = self.stacks.get_mut(&id)?;
+        if *current_id != id {
+            return None;
+        }
+        let (from, to) = if backwards {
+            (&mut stacks.undo, &mut stacks.redo)
+        } else {
+            (&mut stacks.redo, &mut stacks.undo)
+        };
+        let change = from.pop()?;
+        change.apply(data, backwards);
+        to.push(change);
+        Some(data.clone())
+    }
+}
@@ -0,0 +1,373 @@
+use crate::shapes::{Shape, VoxelCoord, VoxelData, VoxelGrid, MAX_DIMENSION, MIN_DIMENSION};
+use std::collections::HashSet;
+use std::path::Path;
+
+// Offset of the voxelization rays from the voxel centers, in voxels
+const RAY_NUDGE: (f32, f32) = (0.000_137, 0.000_291);
+
+// Loads a MagicaVoxel .vox or Wavefront .obj file, picked by extension. OBJ meshes are
+// voxelized: with `fit` set they are scaled uniformly to fit in that (width, height, depth),
+// otherwise one mesh unit becomes one voxel. Shape settings not implied by the file are
+// copied from `base`.
+pub fn import_file(
+    path: &str,
+    base: &Shape,
+    fit: Option<(u32, u32, u32)>,
+) -> Result<VoxelData, String> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "vox" => {
+            let bytes =
+                std::fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+            parse_vox(&bytes, base)
+        }
+        "obj" => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read {}: {}", path, e))?;
+            voxelize_obj(&text, base, fit)
+        }
+        _ => Err(format!(
+            "Unsupported file type '{}', expected .vox or .obj",
+            path
+        )),
+    }
+}
+
+// --- MagicaVoxel ---
+
+// Reads the first model of a .vox file. MagicaVoxel is Z-up, so its Z becomes our Y and
+// its Y is flipped into our Z to keep the model from being mirrored.
+pub fn parse_vox(bytes: &[u8], base: &Shape) -> Result<VoxelData, String> {
+    let mut reader = ByteReader { bytes, position: 0 };
+    if reader.take(4)? != b"VOX " {
+        return Err("Not a MagicaVoxel file (missing 'VOX ' header)".into());
+    }
+    reader.u32()?; // Version
+
+    let mut size: Option<(u32, u32, u32)> = None;
+    let mut voxels: Option<Vec<VoxelCoord>> = None;
+    while reader.position < bytes.len() && voxels.is_none() {
+        let id = reader.take(4)?;
+        let content_size = reader.u32()? as usize;
+        reader.u32()?; // Size of the children, which follow inline
+        match id {
+            // MAIN only wraps the other chunks
+            b"MAIN" => {}
+            b"SIZE" => {
+                let mut content = ByteReader {
+                    bytes: reader.take(content_size)?,
+                    position: 0,
+                };
+                size = Some((content.u32()?, content.u32()?, content.u32()?));
+            }
+            b"XYZI" => {
+                let (size_x, size_y, size_z) = size.ok_or("XYZI chunk before SIZE chunk")?;
+                let mut content = ByteReader {
+                    bytes: reader.take(content_size)?,
+                    position: 0,
+                };
+                let count = content.u32()? as usize;
+                let mut model = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let voxel = content.take(4)?; // x, y, z, color index
+                    let (x, y, z) = (voxel[0] as u32, voxel[1] as u32, voxel[2] as u32);
+                    if x >= size_x || y >= size_y || z >= size_z {
+                        return Err(format!(
+                            "Voxel ({}, {}, {}) lies outside the model size",
+                            x, y, z
+                        ));
+                    }
+                    model.push((x as i32, z as i32, (size_y - 1 - y) as i32));
+                }
+                voxels = Some(model);
+            }
+            _ => {
+                reader.take(content_size)?;
+            }
+        }
+    }
+
+    let (size_x, size_y, size_z) = size.ok_or("The file contains no SIZE chunk")?;
+    let voxels = voxels.ok_or("The file contains no XYZI chunk")?;
+    let (width, height, depth) = (size_x, size_z, size_y);
+    check_dimensions(width, height, depth)?;
+    if voxels.is_empty() {
+        return Err("The model contains no voxels".into());
+    }
+
+    Ok(VoxelData {
+        shape: Shape {
+            width,
+            height,
+            depth,
+            ..base.clone()
+        },
+        voxels: voxels.into(),
+    })
+}
+
+// Little-endian reader that reports truncated files as errors
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or("The file is truncated")?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+// --- OBJ ---
+
+// Voxelizes a triangle mesh. Closed meshes are filled: a voxel is filled when its center is
+// inside the mesh, found by counting crossings of a ray along +X through each row of voxel
+// centers. Open meshes (a single quad, a terrain patch) have no inside, so every voxel one
+// of their triangles passes through is filled instead. Polygons are split into triangle
+// fans; normals, texture coordinates and materials are ignored.
+pub fn voxelize_obj(
+    text: &str,
+    base: &Shape,
+    fit: Option<(u32, u32, u32)>,
+) -> Result<VoxelData, String> {
+    let triangles = parse_obj_triangles(text)?;
+
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for vertex in triangles.iter().flatten() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(vertex[axis]);
+            max[axis] = max[axis].max(vertex[axis]);
+        }
+    }
+    let extent = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
+
+    // Voxels per mesh unit
+    let scale = match fit {
+        Some((width, height, depth)) => {
+            let target = [width as f32, height as f32, depth as f32];
+            (0..3)
+                .filter(|&axis| extent[axis] > f32::EPSILON)
+                .map(|axis| target[axis] / extent[axis])
+                .fold(f32::MAX, f32::min)
+        }
+        None => 1.0,
+    };
+    if scale == f32::MAX {
+        return Err("The mesh has no volume".into());
+    }
+
+    // Small tolerance so exact fits don't round up to an extra layer
+    let size = |axis: usize| ((extent[axis] * scale - 1e-3).ceil().max(1.0)) as u32;
+    let (width, height, depth) = (size(0), size(1), size(2));
+    check_dimensions(width, height, depth)?;
+
+    // Triangles in grid space, where voxel (x, y, z) spans x..x+1 and so on
+    let grid_triangles: Vec<[[f32; 3]; 3]> = triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|vertex| [0, 1, 2].map(|axis| (vertex[axis] - min[axis]) * scale))
+        })
+        .collect();
+
+    if !is_closed(&triangles) {
+        return Ok(VoxelData {
+            shape: Shape {
+                width,
+                height,
+                depth,
+                ..base.clone()
+            },
+            voxels: voxelize_surface(&grid_triangles, (width, height, depth)),
+        });
+    }
+
+    let mut voxels = VoxelGrid::new();
+    let mut crossings: Vec<f32> = Vec::new();
+    for y in 0..height as i32 {
+        for z in 0..depth as i32 {
+            // Nudged off the center so the ray never runs exactly through a triangle edge
+            // (quad diagonals of axis-aligned meshes pass right through voxel centers)
+            let center_y = y as f32 + 0.5 + RAY_NUDGE.0;
+            let center_z = z as f32 + 0.5 + RAY_NUDGE.1;
+            crossings.clear();
+            crossings.extend(
+                grid_triangles
+                    .iter()
+                    .filter_map(|triangle| ray_x_intersection(triangle, center_y, center_z)),
+            );
+            crossings.sort_by(|a, b| a.total_cmp(b));
+
+            // Centers between the first and second crossing are inside, and so on
+            for x in 0..width as i32 {
+                let center_x = x as f32 + 0.5;
+                let before = crossings
+                    .iter()
+                    .filter(|&&crossing| crossing < center_x)
+                    .count();
+                if before % 2 == 1 {
+                    voxels.insert((x, y, z));
+                }
+            }
+        }
+    }
+
+    if voxels.is_empty() {
+        return Err("The mesh produced no voxels; is it closed?".into());
+    }
+
+    Ok(VoxelData {
+        shape: Shape {
+            width,
+            height,
+            depth,
+            ..base.clone()
+        },
+        voxels,
+    })
+}
+
+// Reads every face of an OBJ file as triangles of vertex positions
+fn parse_obj_triangles(text: &str) -> Result<Vec<[[f32; 3]; 3]>, String> {
+    let mut vertices: Vec<[f32; 3]> = Vec::new();
+    let mut triangles = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("v") => {
+                let values: Vec<f32> = parts
+                    .take(3)
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| {
+                        format!("Line {}: invalid vertex '{}'", number + 1, line.trim())
+                    })?;
+                match values[..] {
+                    [x, y, z] => vertices.push([x, y, z]),
+                    _ => return Err(format!("Line {}: a vertex needs 3 coordinates", number + 1)),
+                }
+            }
+            Some("f") => {
+                let corners: Vec<[f32; 3]> = parts
+                    .map(|part| {
+                        // "v", "v/vt", "v//vn" or "v/vt/vn"; negative indices count from the end
+                        let index: i64 = part.split('/').next().unwrap_or("").parse().ok()?;
+                        let resolved = if index < 0 {
+                            vertices.len() as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        vertices.get(usize::try_from(resolved).ok()?).copied()
+                    })
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| {
+                        format!("Line {}: invalid face '{}'", number + 1, line.trim())
+                    })?;
+                if corners.len() < 3 {
+                    return Err(format!(
+                        "Line {}: a face needs at least 3 vertices",
+                        number + 1
+                    ));
+                }
+                for i in 1..corners.len() - 1 {
+                    triangles.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if triangles.is_empty() {
+        return Err("The OBJ file contains no faces".into());
+    }
+    Ok(triangles)
+}
+
+// True when every edge is shared by an even number of triangles, so the mesh has no border
+// and an inside. Vertices are compared exactly, as OBJ faces reuse the same vertex lines
+fn is_closed(triangles: &[[[f32; 3]; 3]]) -> bool {
+    let mut open_edges: HashSet<([u32; 3], [u32; 3])> = HashSet::new();
+    for triangle in triangles {
+        let corners = triangle.map(|vertex| vertex.map(f32::to_bits));
+        for i in 0..3 {
+            let (a, b) = (corners[i], corners[(i + 1) % 3]);
+            let edge = if a < b { (a, b) } else { (b, a) };
+            // A second triangle on the same edge closes it, a third opens it again
+            if !open_edges.remove(&edge) {
+                open_edges.insert(edge);
+            }
+        }
+    }
+    open_edges.is_empty()
+}
+
+// Every voxel touched by one of the triangles (in grid space), found by sampling each
+// triangle at less than half a voxel apart
+fn voxelize_surface(triangles: &[[[f32; 3]; 3]], size: (u32, u32, u32)) -> VoxelGrid {
+    let limits = [size.0 as i32 - 1, size.1 as i32 - 1, size.2 as i32 - 1];
+    let mut voxels = VoxelGrid::new();
+    for [a, b, c] in triangles {
+        let length = |p: &[f32; 3], q: &[f32; 3]| {
+            ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2) + (p[2] - q[2]).powi(2)).sqrt()
+        };
+        let longest = length(a, b).max(length(b, c)).max(length(c, a));
+        let steps = (longest * 2.0).ceil().max(1.0) as u32;
+        for i in 0..=steps {
+            for j in 0..=steps - i {
+                let (u, v) = (i as f32 / steps as f32, j as f32 / steps as f32);
+                let point = [0, 1, 2].map(|axis| {
+                    let value = a[axis] + u * (b[axis] - a[axis]) + v * (c[axis] - a[axis]);
+                    (value.floor() as i32).clamp(0, limits[axis])
+                });
+                voxels.insert((point[0], point[1], point[2]));
+            }
+        }
+    }
+    voxels
+}
+
+// X coordinate where the ray through (y, z) parallel to the X axis crosses the triangle
+fn ray_x_intersection(triangle: &[[f32; 3]; 3], y: f32, z: f32) -> Option<f32> {
+    let [a, b, c] = triangle;
+    // Barycentric coordinates of (y, z) in the triangle projected onto the YZ plane
+    let denominator = (b[1] - a[1]) * (c[2] - a[2]) - (c[1] - a[1]) * (b[2] - a[2]);
+    if denominator.abs() < f32::EPSILON {
+        return None; // Parallel to the ray
+    }
+    let u = ((y - a[1]) * (c[2] - a[2]) - (c[1] - a[1]) * (z - a[2])) / denominator;
+    let v = ((b[1] - a[1]) * (z - a[2]) - (y - a[1]) * (b[2] - a[2])) / denominator;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some(a[0] + u * (b[0] - a[0]) + v * (c[0] - a[0]))
+}
+
+// --- Helpers ---
+
+fn check_dimensions(width: u32, height: u32, depth: u32) -> Result<(), String> {
+    let allowed = MIN_DIMENSION..=MAX_DIMENSION;
+    if !allowed.contains(&width) || !allowed.contains(&height) || !allowed.contains(&depth) {
+        return Err(format!(
+            "The model is {}x{}x{}, each side must be between {} and {} voxels",
+            width, height, depth, MIN_DIMENSION, MAX_DIMENSION
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,378 @@
+use bevy::prelude::{ButtonInput, KeyCode, Resource};
+use std::collections::HashMap;
+use std::path::Path;
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, IntoStaticStr};
+
+// Optional file next to the executable's working directory that overrides the default keys
+pub const KEYBINDINGS_PATH: &str = "keybindings.toml";
+
+// Everything that can be bound to a key. The snake_case names are the keys used in
+// keybindings.toml.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Action {
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    Raise,
+    Lower,
+    ZoomIn,
+    ZoomOut,
+    Frame,
+    ResetView,
+    ViewFront,
+    ViewBack,
+    ViewLeft,
+    ViewRight,
+    ViewTop,
+    ViewBottom,
+    Regenerate,
+    ToggleHollow,
+    ShapeCube,
+    ShapeSphere,
+    ShapeCylinder,
+    ShapeCone,
+    ShapePyramid,
+    ShapeTube,
+    Export,
+    Screenshot,
+    MoveLeft,
+    MoveRight,
+    MoveForward,
+    MoveBack,
+    MoveUp,
+    MoveDown,
+    Copy,
+    Cut,
+    Paste,
+    Duplicate,
+    Undo,
+    Redo,
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+
+    // Description shown in the Shortcuts section
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::OrbitLeft => "Orbit left",
+            Action::OrbitRight => "Orbit right",
+            Action::OrbitUp => "Orbit up",
+            Action::OrbitDown => "Orbit down",
+            Action::Raise => "Raise view",
+            Action::Lower => "Lower view",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::Frame => "Frame shape",
+            Action::ResetView => "Reset view",
+            Action::ViewFront => "Front view",
+            Action::ViewBack => "Back view",
+            Action::ViewLeft => "Left view",
+            Action::ViewRight => "Right view",
+            Action::ViewTop => "Top view",
+            Action::ViewBottom => "Bottom view",
+            Action::Regenerate => "Regenerate",
+            Action::ToggleHollow => "Toggle hollow",
+            Action::ShapeCube => "Cube",
+            Action::ShapeSphere => "Sphere",
+            Action::ShapeCylinder => "Cylinder",
+            Action::ShapeCone => "Cone",
+            Action::ShapePyramid => "Square pyramid",
+            Action::ShapeTube => "Tube",
+            Action::Export => "Export OBJ",
+            Action::Screenshot => "Screenshot",
+            Action::MoveLeft => "Move selection -X",
+            Action::MoveRight => "Move selection +X",
+            Action::MoveForward => "Move selection -Z",
+            Action::MoveBack => "Move selection +Z",
+            Action::MoveUp => "Move selection up",
+            Action::MoveDown => "Move selection down",
+            Action::Copy => "Copy selection",
+            Action::Cut => "Cut selection",
+            Action::Paste => "Paste tool",
+            Action::Duplicate => "Duplicate selection",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+        }
+    }
+}
+
+// A key, optionally with Ctrl held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub key: KeyCode,
+    pub ctrl: bool,
+}
+
+impl KeyBinding {
+    // Parses names like "G", "F12", "Numpad1" or "Ctrl+E" (case doesn't matter)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (ctrl, name) = match text.split_once('+') {
+            Some((modifier, name)) if modifier.trim().eq_ignore_ascii_case("ctrl") => {
+                (true, name.trim())
+            }
+            _ => (false, text),
+        };
+        KEY_NAMES
+            .iter()
+            .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+            .map(|&(_, key)| KeyBinding { key, ctrl })
+            .ok_or_else(|| format!("unknown key '{}'", text))
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = KEY_NAMES
+            .iter()
+            .find(|&&(_, key)| key == self.key)
+            .map_or("?", |&(name, _)| name);
+        if self.ctrl {
+            write!(f, "Ctrl+{}", name)
+        } else {
+            f.write_str(name)
+        }
+    }
+}
+
+// Which keys trigger which action. Every action has at least one binding unless the
+// file explicitly unbinds it with an empty list.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let key = |key| KeyBinding { key, ctrl: false };
+        let ctrl = |key| KeyBinding { key, ctrl: true };
+        let bindings = Action::iter()
+            .map(|action| {
+                let keys = match action {
+                    Action::OrbitLeft => vec![key(KeyCode::KeyA)],
+                    Action::OrbitRight => vec![key(KeyCode::KeyD)],
+                    Action::OrbitUp => vec![key(KeyCode::KeyW)],
+                    Action::OrbitDown => vec![key(KeyCode::KeyS)],
+                    Action::Raise => vec![key(KeyCode::KeyE)],
+                    Action::Lower => vec![key(KeyCode::KeyQ)],
+                    Action::ZoomIn => vec![key(KeyCode::Equal), key(KeyCode::NumpadAdd)],
+                    Action::ZoomOut => vec![key(KeyCode::Minus), key(KeyCode::NumpadSubtract)],
+                    Action::Frame => vec![key(KeyCode::KeyF)],
+                    Action::ResetView => vec![key(KeyCode::KeyR), key(KeyCode::Home)],
+                    Action::ViewFront => vec![key(KeyCode::Numpad1)],
+                    Action::ViewBack => vec![key(KeyCode::Numpad2)],
+                    Action::ViewLeft => vec![key(KeyCode::Numpad3)],
+                    Action::ViewRight => vec![key(KeyCode::Numpad4)],
+                    Action::ViewTop => vec![key(KeyCode::Numpad5)],
+                    Action::ViewBottom => vec![key(KeyCode::Numpad6)],
+                    Action::Regenerate => vec![key(KeyCode::KeyG), key(KeyCode::Enter)],
+                    Action::ToggleHollow => vec![key(KeyCode::KeyH)],
+                    Action::ShapeCube => vec![key(KeyCode::Digit1)],
+                    Action::ShapeSphere => vec![key(KeyCode::Digit2)],
+                    Action::ShapeCylinder => vec![key(KeyCode::Digit3)],
+                    Action::ShapeCone => vec![key(KeyCode::Digit4)],
+                    Action::ShapePyramid => vec![key(KeyCode::Digit5)],
+                    Action::ShapeTube => vec![key(KeyCode::Digit6)],
+                    Action::Export => vec![ctrl(KeyCode::KeyE)],
+                    Action::Screenshot => vec![key(KeyCode::F12)],
+                    Action::MoveLeft => vec![key(KeyCode::ArrowLeft)],
+                    Action::MoveRight => vec![key(KeyCode::ArrowRight)],
+                    Action::MoveForward => vec![key(KeyCode::ArrowUp)],
+                    Action::MoveBack => vec![key(KeyCode::ArrowDown)],
+                    Action::MoveUp => vec![key(KeyCode::PageUp)],
+                    Action::MoveDown => vec![key(KeyCode::PageDown)],
+                    Action::Copy => vec![ctrl(KeyCode::KeyC)],
+                    Action::Cut => vec![ctrl(KeyCode::KeyX)],
+                    Action::Paste => vec![ctrl(KeyCode::KeyV)],
+                    Action::Duplicate => vec![ctrl(KeyCode::KeyD)],
+                    Action::Undo => vec![ctrl(KeyCode::KeyZ)],
+                    Action::Redo => vec![ctrl(KeyCode::KeyY)],
+                };
+                (action, keys)
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl KeyMap {
+    // Reads `path` on top of the defaults. A missing file just means the defaults; bad
+    // entries are reported and skipped so a typo never stops the app from starting.
+    pub fn load(path: &Path) -> Self {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                eprintln!("Warning: cannot read {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        let (keymap, warnings) = Self::parse(&text);
+        for warning in warnings {
+            eprintln!("Warning: {}: {}", path.display(), warning);
+        }
+        keymap
+    }
+
+    // Applies `action = "Key"` or `action = ["Key", "Ctrl+Key"]` lines to the defaults and
+    // returns the problems found along the way
+    pub fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut keymap = Self::default();
+        let table: toml::Table = match text.parse() {
+            Ok(table) => table,
+            Err(e) => {
+                return (
+                    keymap,
+                    vec![format!("not valid TOML, using the defaults: {}", e)],
+                )
+            }
+        };
+
+        let mut warnings = Vec::new();
+        for (name, value) in table {
+            let Some(action) = Action::iter().find(|action| action.name() == name) else {
+                warnings.push(format!("unknown action '{}'", name));
+                continue;
+            };
+            let names = match value {
+                toml::Value::String(name) => vec![name],
+                toml::Value::Array(values) => values
+                    .into_iter()
+                    .filter_map(|value| value.as_str().map(str::to_string))
+                    .collect(),
+                _ => {
+                    warnings.push(format!("'{}' must be a key name or a list of them", name));
+                    continue;
+                }
+            };
+            match names.iter().map(|name| KeyBinding::parse(name)).collect() {
+                Ok(bindings) => {
+                    keymap.bindings.insert(action, bindings);
+                }
+                Err(e) => warnings.push(format!("{} for '{}'", e, name)),
+            }
+        }
+        (keymap, warnings)
+    }
+
+    pub fn bindings(&self, action: Action) -> &[KeyBinding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // True on the frame one of the action's keys went down
+    pub fn just_pressed(&self, action: Action, keys: &ButtonInput<KeyCode>) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| keys.just_pressed(binding.key) && binding.ctrl == ctrl_held(keys))
+    }
+
+    // True while one of the action's keys is held
+    pub fn pressed(&self, action: Action, keys: &ButtonInput<KeyCode>) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| keys.pressed(binding.key) && binding.ctrl == ctrl_held(keys))
+    }
+
+    // All keys of an action for display, e.g. "R / Home"
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self
+            .bindings(action)
+            .iter()
+            .map(|b| b.to_string())
+            .collect();
+        if names.is_empty() {
+            "unbound".to_string()
+        } else {
+            names.join(" / ")
+        }
+    }
+}
+
+// Plain bindings don't fire while Ctrl is held, so Ctrl+E doesn't also trigger E
+fn ctrl_held(keys: &ButtonInput<KeyCode>) -> bool {
+    keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+}
+
+// Names accepted in keybindings.toml and shown in the UI
+const KEY_NAMES: [(&str, KeyCode); 74] = [
+    ("A", KeyCode::KeyA),
+    ("B", KeyCode::KeyB),
+    ("C", KeyCode::KeyC),
+    ("D", KeyCode::KeyD),
+    ("E", KeyCode::KeyE),
+    ("F", KeyCode::KeyF),
+    ("G", KeyCode::KeyG),
+    ("H", KeyCode::KeyH),
+    ("I", KeyCode::KeyI),
+    ("J", KeyCode::KeyJ),
+    ("K", KeyCode::KeyK),
+    ("L", KeyCode::KeyL),
+    ("M", KeyCode::KeyM),
+    ("N", KeyCode::KeyN),
+    ("O", KeyCode::KeyO),
+    ("P", KeyCode::KeyP),
+    ("Q", KeyCode::KeyQ),
+    ("R", KeyCode::KeyR),
+    ("S", KeyCode::KeyS),
+    ("T", KeyCode::KeyT),
+    ("U", KeyCode::KeyU),
+    ("V", KeyCode::KeyV),
+    ("W", KeyCode::KeyW),
+    ("X", KeyCode::KeyX),
+    ("Y", KeyCode::KeyY),
+    ("Z", KeyCode::KeyZ),
+    ("0", KeyCode::Digit0),
+    ("1", KeyCode::Digit1),
+    ("2", KeyCode::Digit2),
+    ("3", KeyCode::Digit3),
+    ("4", KeyCode::Digit4),
+    ("5", KeyCode::Digit5),
+    ("6", KeyCode::Digit6),
+    ("7", KeyCode::Digit7),
+    ("8", KeyCode::Digit8),
+    ("9", KeyCode::Digit9),
+    ("Numpad0", KeyCode::Numpad0),
+    ("Numpad1", KeyCode::Numpad1),
+    ("Numpad2", KeyCode::Numpad2),
+    ("Numpad3", KeyCode::Numpad3),
+    ("Numpad4", KeyCode::Numpad4),
+    ("Numpad5", KeyCode::Numpad5),
+    ("Numpad6", KeyCode::Numpad6),
+    ("Numpad7", KeyCode::Numpad7),
+    ("Numpad8", KeyCode::Numpad8),
+    ("Numpad9", KeyCode::Numpad9),
+    ("NumpadAdd", KeyCode::NumpadAdd),
+    ("NumpadSubtract", KeyCode::NumpadSubtract),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("Equal", KeyCode::Equal),
+    ("Minus", KeyCode::Minus),
+    ("Space", KeyCode::Space),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Delete", KeyCode::Delete),
+    ("Up", KeyCode::ArrowUp),
+    ("Down", KeyCode::ArrowDown),
+    ("Left", KeyCode::ArrowLeft),
+    ("Right", KeyCode::ArrowRight),
+];
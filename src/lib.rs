@@ -0,0 +1,31 @@
+// Voxel shape generation, editing and file formats behind 3D Shape Sculptor, usable without
+// the editor. Generate a model with `generate_shape`, then write it with one of the
+// `export_to_*` functions:
+//
+//     let shape = Shape { kind: GeometricShape::Sphere, ..Default::default() };
+//     let voxel_data = VoxelData { voxels: generate_shape(&shape), shape };
+//     export_to_obj(&voxel_data, "sphere.obj", voxel_color_rgb(), None, false)?;
+//
+// Coordinates are grid cells (x, y, z) in 0..width, 0..height and 0..depth with Y up;
+// exported meshes are centered on the origin with one unit per voxel.
+
+pub mod export;
+pub mod expr;
+mod font;
+pub mod history;
+pub mod import;
+pub mod keymap;
+pub mod morph;
+pub mod noise;
+pub mod presets;
+pub mod sculpt;
+pub mod shapes;
+
+#[cfg(feature = "gltf-export")]
+pub use export::{export_to_glb, export_to_gltf};
+pub use export::{export_scene_to_obj, export_to_obj, export_to_ply, load_vxl, save_vxl};
+pub use import::import_file;
+pub use shapes::{
+    generate_shape, GeometricShape, SceneData, SceneShape, Shape, VoxelCoord, VoxelData,
+    VoxelGrid,
+};
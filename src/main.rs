@@ -1,70 +1,655 @@
 use bevy::{
-    input::mouse::MouseButton, // Keep specific MouseButton if needed, but prelude usually covers it
+    input::mouse::{MouseButton, MouseMotion}, // Keep specific MouseButton if needed, but prelude usually covers it
     input::keyboard::KeyCode, // Keep specific KeyCode if needed
+    pbr::wireframe::{Wireframe, WireframePlugin},
     prelude::*,
-    window::{PresentMode, WindowTheme},
+    render::{
+        camera::ScalingMode,
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        settings::{RenderCreation, WgpuFeatures, WgpuSettings},
+        view::screenshot::ScreenshotManager,
+        RenderPlugin,
+    },
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+    window::{PresentMode, PrimaryWindow, WindowTheme},
 };
-use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use bevy_egui::{EguiContexts, EguiPlugin};
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
 // use core::fmt; // REMOVE THIS LINE
-use strum::IntoEnumIterator;
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU};
+use std::path::{Path, PathBuf};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use strum_macros::{Display, EnumIter};
 
+mod cli;
+mod ui;
+
+// The shape system lives in the library; these keep `crate::shapes` and friends working
+// for the editor modules
+use voxel_sculptor::{
+    export, expr, history, import, keymap, morph, noise, presets, sculpt, shapes,
+};
+
+use export::{greedy_mesh, Quad};
+use history::History;
+use keymap::{Action, KeyMap, KEYBINDINGS_PATH};
+use morph::{MorphAnimation, MorphVoxel};
+use presets::PresetLibrary;
+use sculpt::{
+    brush_cells, extrusion, face_region, fill_empty_region, in_grid, line_cells, pick_voxel,
+    plane_cells, recolor_region, BrushShape, Clipboard, MoveMode, Selection,
+};
+use shapes::{
+    apply_noise, apply_symmetry, compose_scene, count_exposed_faces, hollow_voxels,
+    palette_color, voxel_color_rgb, BoolOp, GeometricShape, HeightGradient, SceneData,
+    SceneShape, Shape, Symmetry, VoxelCoord, VoxelData, VoxelGrid, DEFAULT_MAX_DIMENSION,
+    MAX_GRID_CELLS, NEIGHBOR_OFFSETS, PALETTE, PINK_COLOR_HEX, UNPAINTED,
+};
+use ui::{export_scene, request_regeneration, screenshot_toast_system, step_history, ui_system};
+
 // --- Constants ---
-const PINK_COLOR_HEX: &str = "AC1754";
-const MIN_DIMENSION: u32 = 1;
-const MAX_DIMENSION: u32 = 32;
+const CAMERA_START_POSITION: Vec3 = Vec3::new(0.0, 10.0, 35.0);
+const SCREENSHOT_DIR: &str = "screenshots";
+const SCREENSHOT_TOAST_SECONDS: f32 = 3.0;
+const EXPORT_PATH: &str = "exported_shape.obj";
+const PLY_EXPORT_PATH: &str = "exported_shape.ply";
+#[cfg(feature = "gltf-export")]
+const GLTF_EXPORT_PATH: &str = "exported_shape.gltf";
+#[cfg(feature = "gltf-export")]
+const GLB_EXPORT_PATH: &str = "exported_shape.glb";
+// Default for UserInput::confirm_cells; every voxel is an entity, so tens of thousands stutter
+const DEFAULT_CONFIRM_CELLS: u64 = 30_000;
+// Grids with more cells than this get a warning in the UI that generating will be slow
+const LARGE_GRID_WARNING_CELLS: u64 = 32_768;
+// Objects with more voxels than this are drawn as one greedy mesh per layer instead of one
+// entity per voxel
+const LARGE_MODEL_VOXELS: usize = 10_000;
+// Fly mode: movement in units per second, Shift multiplier, look speed in radians per pixel
+const FLY_SPEED: f32 = 10.0;
+const FLY_FAST_MULTIPLIER: f32 = 3.0;
+const FLY_LOOK_SENSITIVITY: f32 = 0.003;
+// How long the view preset buttons take to swing the camera around
+const VIEW_SNAP_SECONDS: f32 = 0.2;
+// Tint of the selection overlay and how far it reaches past the selected cells
+const SELECTION_OVERLAY_COLOR: Color = Color::rgba(1.0, 0.85, 0.2, 0.25);
+const SELECTION_OVERLAY_MARGIN: f32 = 0.04;
+// A left press that moves further than this before release is a camera drag, not a sculpt click
+const CLICK_MAX_PIXELS: f32 = 4.0;
+// The keys that move the selection a voxel at a time, and which way
+const SELECTION_MOVES: [(Action, VoxelCoord); 6] = [
+    (Action::MoveLeft, (-1, 0, 0)),
+    (Action::MoveRight, (1, 0, 0)),
+    (Action::MoveDown, (0, -1, 0)),
+    (Action::MoveUp, (0, 1, 0)),
+    (Action::MoveForward, (0, 0, -1)),
+    (Action::MoveBack, (0, 0, 1)),
+];
+// Orbit keys: turn speed in radians per second, raise/lower speed in units per second and
+// zoom as the fraction of the distance covered per second
+const KEY_ORBIT_SPEED: f32 = 1.5;
+const KEY_RAISE_SPEED: f32 = 10.0;
+const KEY_ZOOM_SPEED: f32 = 1.0;
+// Emissive strength (nits) the Emissive material preset uses; bright enough to glow at the
+// default camera exposure
+const EMISSIVE_PRESET_STRENGTH: f32 = 2000.0;
+// Fraction of the depth range voxel edges are moved towards the camera
+const VOXEL_EDGE_DEPTH_BIAS: f32 = -0.001;
+// Slider range and default for ImportScale::Resolution
+const MIN_IMPORT_RESOLUTION: u32 = 4;
+const MAX_IMPORT_RESOLUTION: u32 = 32;
+const DEFAULT_IMPORT_RESOLUTION: u32 = 16;
+const EMPTY_SHAPE_ERROR: &str = "No voxels were created for this shape";
 
 // --- Resources ---
 
+// How the mouse and keyboard move the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumIter)]
+enum CameraMode {
+    // Orbit around the model with the mouse
+    #[default]
+    Orbit,
+    // Move freely with WASD/QE and look around by dragging with the right mouse button
+    Fly,
+}
+
+// How the camera projects the scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumIter)]
+enum CameraProjection {
+    #[default]
+    Perspective,
+    // No foreshortening, so voxel rows line up; zooming changes the projection scale
+    Orthographic,
+}
+
+// Fixed orbit angles offered by the view preset buttons
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumIter)]
+enum ViewPreset {
+    Front,
+    Top,
+    Right,
+    Isometric,
+}
+
+impl ViewPreset {
+    // (alpha, beta) as used by PanOrbitCamera: alpha rotates around Y, beta tilts up from the
+    // XZ plane
+    fn angles(self) -> (f32, f32) {
+        match self {
+            ViewPreset::Front => (0.0, 0.0),
+            ViewPreset::Top => (0.0, FRAC_PI_2),
+            ViewPreset::Right => (FRAC_PI_2, 0.0),
+            // Looking down the cube diagonal, so all three axes are foreshortened equally
+            ViewPreset::Isometric => (FRAC_PI_4, (1.0 / 2.0_f32.sqrt()).atan()),
+        }
+    }
+}
+
+// What a click in the Slice View does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumIter)]
+enum SliceTool {
+    // Add or remove the clicked voxel
+    #[default]
+    Toggle,
+    // Remove the inner wall of the enclosed air pocket at (or next to) the clicked cell
+    ClearPocket,
+    // Fill the empty region connected to the clicked cell, within the model's extents
+    FloodFill,
+    // Remove the cluster of voxels connected to the clicked one
+    EraseRegion,
+    // Give the clicked voxel the paint color
+    Paint,
+}
+
+// What a left click on the model does in the 3D view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumIter)]
+enum SculptTool {
+    // Clicks only orbit the camera
+    #[default]
+    Off,
+    // Place a voxel on the clicked face; Shift-click erases instead
+    Add,
+    // Remove the clicked voxel
+    Erase,
+    // Add or remove a sphere or cube of voxels wherever the cursor is dragged
+    Brush,
+    // Two clicks pick the opposite corners of a selection box
+    Select,
+    // Fill the empty space connected to the clicked face, or recolor the connected voxels
+    // of the clicked voxel's color
+    Fill,
+    // Two clicks draw a straight line of voxels between them
+    Line,
+    // Two clicks draw an axis-aligned rectangle of voxels with them as corners
+    Plane,
+    // Give the voxels clicked or dragged over the paint color
+    Paint,
+    // Make the clicked voxel's color the paint color; Alt-click does this with any tool
+    Eyedropper,
+    // Place the copied voxels with their box's corner at the clicked cell
+    Paste,
+    // Pull the clicked face, and the flat patch of exposed faces around it, outward
+    Extrude,
+}
+
+// How "Import" sizes an OBJ mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter)]
+enum ImportScale {
+    // One mesh unit becomes one voxel
+    MeshUnits,
+    // Scaled uniformly to fit the current dimensions
+    #[default]
+    CurrentSize,
+    // Scaled uniformly so the longest side is `UserInput::import_resolution` voxels
+    Resolution,
+}
+
+// Sun light and voxel material as edited in the "Lighting & Material" section
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LightingSettings {
+    // Direction the light comes from, in degrees
+    light_yaw: f32,
+    light_pitch: f32,
+    illuminance: f32, // Lux
+    shadows: bool,
+    base_color: [u8; 3], // sRGB
+    roughness: f32,
+    metallic: f32,
+    // 1.0 is opaque; anything lower blends the voxels with what is behind them
+    opacity: f32,
+    emissive: [u8; 3], // sRGB
+    // Brightness of the emissive color in nits; the camera exposure scales 1.0 to near black
+    emissive_strength: f32,
+}
+
+impl Default for LightingSettings {
+    fn default() -> Self {
+        // Matches the light at (15, 20, 10) and the pink material used before these were editable
+        Self {
+            light_yaw: 56.3,
+            light_pitch: 48.0,
+            illuminance: light_consts::lux::AMBIENT_DAYLIGHT,
+            shadows: true,
+            base_color: voxel_color_rgb(),
+            roughness: 0.8,
+            metallic: 0.1,
+            opacity: 1.0,
+            emissive: [0, 0, 0],
+            emissive_strength: 0.0,
+        }
+    }
+}
+
+impl LightingSettings {
+    // Copies the material part of the settings into `material`, tinted `color`
+    fn apply_to_material(&self, material: &mut StandardMaterial, color: [u8; 3]) {
+        let [r, g, b] = color;
+        material.base_color = Color::rgba_u8(r, g, b, (self.opacity * 255.0).round() as u8);
+        material.alpha_mode = if self.opacity < 1.0 { AlphaMode::Blend } else { AlphaMode::Opaque };
+        material.perceptual_roughness = self.roughness;
+        material.metallic = self.metallic;
+        let [r, g, b] = self.emissive;
+        material.emissive = Color::rgb_u8(r, g, b) * self.emissive_strength;
+    }
+
+    // The part of the settings the voxel materials depend on, apart from their color
+    fn material_key(&self) -> (f32, f32, f32, [u8; 3], f32) {
+        (self.roughness, self.metallic, self.opacity, self.emissive, self.emissive_strength)
+    }
+}
+
+// Quick starting points for the material sliders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumIter)]
+enum MaterialPreset {
+    Matte,
+    Glossy,
+    Glass,
+    Emissive,
+}
+
+impl MaterialPreset {
+    // Sets the material sliders; the light and the voxel color are left alone
+    fn apply(self, settings: &mut LightingSettings) {
+        let default = LightingSettings::default();
+        let (roughness, metallic, opacity) = match self {
+            MaterialPreset::Matte => (default.roughness, default.metallic, 1.0),
+            MaterialPreset::Glossy => (0.15, 0.3, 1.0),
+            MaterialPreset::Glass => (0.05, 0.0, 0.35),
+            MaterialPreset::Emissive => (0.8, 0.0, 1.0),
+        };
+        settings.roughness = roughness;
+        settings.metallic = metallic;
+        settings.opacity = opacity;
+        if self == MaterialPreset::Emissive {
+            settings.emissive = settings.base_color;
+            settings.emissive_strength = EMISSIVE_PRESET_STRENGTH;
+        } else {
+            settings.emissive = default.emissive;
+            settings.emissive_strength = default.emissive_strength;
+        }
+    }
+}
+
 // Stores the user's input from the GUI
 #[derive(Resource, Debug, Clone)]
 struct UserInput {
-    width: u32,
-    depth: u32,
-    height: u32,
-    shape: GeometricShape,
+    shape: Shape,
+    // Proportions (width, depth, height) the dimension sliders keep while the aspect lock is on
+    aspect_lock: Option<[u32; 3]>,
+    // Upper end of the dimension sliders, at most MAX_DIMENSION
+    max_dimension: u32,
+    // Grids with more cells than this wait for the user to confirm before generating
+    confirm_cells: u64,
+    // A change would generate such a grid and waits for that confirmation
+    large_grid_pending: bool,
+    // Additional shapes merged into the main one at their offsets
+    scene_shapes: Vec<SceneShape>,
+    // Mirror planes applied after generation
+    symmetry: Symmetry,
+    // Keep only the outer shell of the shape
+    hollow: bool,
+    // Name typed in the Presets section
+    preset_name: String,
+    // Surface erosion strength (0.0-1.0) and the seed that picks the removed voxels
+    erosion: f32,
+    erosion_seed: u64,
+    // Shape and operation used by the "Combine" button
+    second_shape: Shape,
+    // Where Combine places the second shape, relative to the center of the grid
+    second_offset: VoxelCoord,
+    bool_op: BoolOp,
+    // Target size for "Resize Canvas" as (width, height, depth)
+    canvas_size: (u32, u32, u32),
+    // Text box contents for "Paste voxel list"
+    paste_text: String,
+    // File for "Import" and how OBJ meshes are sized; `import_resolution` is the longest
+    // side in voxels for ImportScale::Resolution
+    import_path: String,
+    import_scale: ImportScale,
+    import_resolution: u32,
+    // Export all visible objects as a single OBJ object
+    merge_on_export: bool,
+    // Merge coplanar faces into larger quads when exporting
+    optimize_mesh: bool,
+    // Write PLY files as binary_little_endian instead of ASCII
+    ply_binary: bool,
+    // File for "Save .vxl" / "Load .vxl"
+    vxl_path: String,
+    // Edge length of each rendered cube; below 1.0 leaves gaps between voxels
+    voxel_size: f32,
+    // Draw the triangle wireframe over the voxels
+    wireframe: bool,
+    // Outline every surface voxel in black so neighboring cubes can be told apart
+    show_edges: bool,
     // Flag to trigger regeneration when inputs change
     needs_regeneration: bool,
+    // Scene environment, pushed into ClearColor and AmbientLight every frame
+    background_color: [u8; 3], // sRGB
+    ambient_brightness: f32,
+    lighting: LightingSettings,
+    // Tint voxels by layer instead of using the material color
+    height_gradient: bool,
+    gradient: HeightGradient,
+    camera_mode: CameraMode,
+    // Slowly orbit the camera around the focus point
+    turntable: bool,
+    turntable_speed: f32, // Degrees per second
+    hide_ui_in_screenshot: bool,
+    // A .png file, or a folder that gets a timestamped file per screenshot
+    screenshot_path: String,
+    // Floating top-down view of one Y layer
+    show_slice_view: bool,
+    slice_y: i32,
+    slice_tool: SliceTool,
+    // PALETTE index that voxels added in the Slice View and painted voxels get
+    paint_color: u8,
+    sculpt_tool: SculptTool,
+    brush_shape: BrushShape,
+    brush_radius: u32,
+    // The brush removes voxels instead of adding them
+    brush_erase: bool,
+    // The Fill tool recolors voxels instead of filling empty space
+    fill_recolor: bool,
+    // Planes through the grid center that sculpt edits are mirrored across. Separate from
+    // `symmetry`, which regenerates the shape when changed
+    mirror_edits: Symmetry,
+    move_mode: MoveMode,
+    // How far Duplicate places the copy from the selection
+    duplicate_offset: VoxelCoord,
+    // Layers of voxels an extrusion adds
+    extrude_distance: u32,
+    // Show only the layers from `layer_clip_min` to `layer_clip_y` (or only `layer_clip_y`)
+    // to look inside the model
+    layer_clip: bool,
+    layer_clip_y: i32,
+    layer_clip_min: i32,
+    single_layer: bool,
+    // Last error to show in the UI, cleared by the user
+    error_message: Option<String>,
+    // Outcome of the last edit or file operation that has nothing else to show it, e.g. how
+    // many voxels a Slice View tool changed; replaced by the next one
+    status_message: Option<String>,
 }
 
 impl Default for UserInput {
     fn default() -> Self {
         Self {
-            width: 8,
-            depth: 8,
-            height: 8,
-            shape: GeometricShape::Cube,
+            shape: Shape::default(),
+            aspect_lock: None,
+            max_dimension: DEFAULT_MAX_DIMENSION,
+            confirm_cells: DEFAULT_CONFIRM_CELLS,
+            large_grid_pending: false,
+            scene_shapes: Vec::new(),
+            symmetry: Symmetry::default(),
+            hollow: false,
+            preset_name: String::new(),
+            erosion: 0.0,
+            erosion_seed: 1,
+            second_shape: Shape {
+                kind: GeometricShape::Sphere,
+                ..default()
+            },
+            second_offset: (0, 0, 0),
+            bool_op: BoolOp::Difference,
+            canvas_size: (8, 8, 8),
+            paste_text: String::new(),
+            import_path: String::new(),
+            import_scale: ImportScale::default(),
+            import_resolution: DEFAULT_IMPORT_RESOLUTION,
+            merge_on_export: false,
+            ply_binary: true,
+            optimize_mesh: true,
+            vxl_path: "scene.vxl".into(),
+            voxel_size: 1.0,
+            wireframe: false,
+            show_edges: false,
             needs_regeneration: true, // Regenerate on startup
+            background_color: [0, 0, 0],
+            ambient_brightness: 0.8,
+            lighting: LightingSettings::default(),
+            height_gradient: false,
+            gradient: HeightGradient::default(),
+            camera_mode: CameraMode::Orbit,
+            turntable: false,
+            turntable_speed: 20.0,
+            hide_ui_in_screenshot: true,
+            screenshot_path: SCREENSHOT_DIR.into(),
+            show_slice_view: false,
+            slice_y: 0,
+            slice_tool: SliceTool::default(),
+            paint_color: UNPAINTED,
+            sculpt_tool: SculptTool::default(),
+            brush_shape: BrushShape::default(),
+            brush_radius: 2,
+            brush_erase: false,
+            fill_recolor: false,
+            mirror_edits: Symmetry::default(),
+            move_mode: MoveMode::default(),
+            duplicate_offset: (2, 0, 0),
+            extrude_distance: 1,
+            layer_clip: false,
+            layer_clip_y: 0,
+            layer_clip_min: 0,
+            single_layer: false,
+            error_message: None,
+            status_message: None,
+        }
+    }
+}
+
+// Tracks a pending screenshot; capture is delayed a frame when the UI has to be hidden first
+#[derive(Resource, Default)]
+struct ScreenshotState {
+    requested: bool,
+    ui_hidden: bool,
+    // Filled by the capture callback, which runs on the render thread: the saved file or an error
+    outcome: Arc<Mutex<Option<Result<PathBuf, String>>>>,
+}
+
+// File export running on the async compute pool, so big meshes don't freeze the window.
+// Only one runs at a time, which also keeps two clicks from writing the same file at once.
+#[derive(Resource, Default)]
+struct ExportJob {
+    running: Option<RunningExport>,
+}
+
+struct RunningExport {
+    task: Task<io::Result<()>>,
+    // Main output file, shown in the UI
+    path: String,
+    // Every file the export writes, removed again when it is cancelled
+    files: Vec<PathBuf>,
+    // Writing can't be interrupted, so a cancelled export keeps running until the task
+    // returns and is cleaned up then. Until that, no new export can start
+    cancelled: bool,
+}
+
+impl ExportJob {
+    fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    // Main output file of the running export
+    fn path(&self) -> Option<&str> {
+        self.running.as_ref().map(|running| running.path.as_str())
+    }
+
+    fn is_cancelling(&self) -> bool {
+        self.running.as_ref().is_some_and(|running| running.cancelled)
+    }
+
+    // Runs `write` on a worker thread; `files` lists what it creates. Does nothing while
+    // another export is running.
+    fn start(
+        &mut self,
+        path: &str,
+        files: Vec<PathBuf>,
+        write: impl FnOnce() -> io::Result<()> + Send + 'static,
+    ) {
+        if self.is_running() {
+            return;
+        }
+        let task = AsyncComputeTaskPool::get().spawn(async move { write() });
+        self.running = Some(RunningExport {
+            task,
+            path: path.to_string(),
+            files,
+            cancelled: false,
+        });
+    }
+
+    // Abandons the running export; whatever it wrote is deleted once it has finished
+    fn cancel(&mut self) {
+        if let Some(running) = self.running.as_mut() {
+            running.cancelled = true;
         }
     }
 }
 
+fn remove_files(files: &[PathBuf]) {
+    for file in files {
+        // The file may not have been created yet
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+// Outcome of the last screenshot, shown as a toast until the timer runs out
+#[derive(Resource)]
+struct ScreenshotResult {
+    message: String,
+    success: bool,
+    timer: Timer,
+}
+
+impl ScreenshotResult {
+    fn new(result: Result<PathBuf, String>) -> Self {
+        let (message, success) = match result {
+            Ok(path) => (format!("Screenshot saved to {}", path.display()), true),
+            Err(e) => (e, false),
+        };
+        Self {
+            message,
+            success,
+            timer: Timer::from_seconds(SCREENSHOT_TOAST_SECONDS, TimerMode::Once),
+        }
+    }
+}
+
+// Statistics about the current model, cached so the UI doesn't recompute them every frame
+#[derive(Resource, Debug, Default)]
+struct VoxelStats {
+    voxel_count: usize,
+    bounds: Option<(VoxelCoord, VoxelCoord)>,
+    exposed_faces: usize,
+    // Quads the optimized (greedy) exporters write instead of one per exposed face
+    greedy_quads: usize,
+}
+
+// Set whenever the voxels of the edited object or the layout of the scene (objects added,
+// removed, moved, shown or hidden) actually change. Bevy's change detection also fires on
+// mutable borrows that change nothing, which would redraw the whole scene, so the model is
+// only synced, measured and redrawn while this is set; update_voxels clears it
+#[derive(Resource, Debug)]
+struct VoxelDirty(bool);
+
+// Voxel entities currently in the scene, keyed by object id and grid coordinate,
+// with the translation each was last placed at
+#[derive(Resource, Default)]
+struct SpawnedVoxels {
+    entities: HashMap<(u32, VoxelCoord), (Entity, Vec3)>,
+    // Large objects drawn as layer meshes, keyed by object id
+    meshed: HashMap<u32, MeshedObject>,
+}
+
+// Layer mesh entities of a large object, with the voxels they were built from and the
+// translation of the object's grid origin
+struct MeshedObject {
+    voxels: VoxelGrid,
+    grid_height: u32,
+    layers: Vec<Entity>,
+    placed: Vec3,
+}
+
 // Holds the handle for the pink material
 #[derive(Resource)]
 struct VoxelMaterial(Handle<StandardMaterial>);
 
+// The directional light, so its settings can be changed from the UI
+#[derive(Resource)]
+struct SunLight(Entity);
+
 // Holds the handle for the cube mesh
 #[derive(Resource)]
 struct VoxelMesh(Handle<Mesh>);
 
-// --- Components ---
+// Starting camera placement, restored by the ResetCamera event
+#[derive(Resource, Debug, Clone, Copy)]
+struct CameraDefault {
+    transform: Transform,
+    focus: Vec3,
+}
 
-// Marker component for voxel entities, used for cleanup
-#[derive(Component)]
-struct Voxel;
+// Projection and view preset state shared by the UI and the camera systems
+#[derive(Resource, Debug, Default)]
+struct CameraView {
+    projection: CameraProjection,
+    // Set by the UI, picked up by view_snap_system
+    requested_preset: Option<ViewPreset>,
+    snap: Option<ViewSnap>,
+}
+
+// A view preset swing in progress, from one (alpha, beta) pair to another
+#[derive(Debug, Clone, Copy)]
+struct ViewSnap {
+    from: (f32, f32),
+    to: (f32, f32),
+    elapsed: f32,
+}
 
-// --- Enums ---
+// --- Events ---
+
+// Sent by the R/Home keys and the "Reset View" button
+#[derive(Event, Debug, Clone, Copy, Default)]
+struct ResetCamera;
+
+// --- Components ---
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter)]
-enum GeometricShape {
-    Cube,
-    Sphere,
-    Cylinder,
-    Cone,
-    SquarePyramid,
+// Marks voxel entities; keeps the grid layer and the height of its object's grid
+// for layer clipping and the height gradient, and the palette color it is painted with
+#[derive(Component)]
+struct Voxel {
+    y: i32,
+    grid_height: u32,
+    color: u8,
 }
 
+// Marks the translucent block drawn over the selection box
+#[derive(Component)]
+struct SelectionOverlay;
+
 // --- Systems ---
 
 // Setup function to initialize resources and scene
@@ -72,8 +657,13 @@ fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    asset_server: Res<AssetServer>,
+    mut gizmo_configs: ResMut<GizmoConfigStore>,
 ) {
+    // Voxel edges are drawn exactly on the cube faces; pull them slightly towards the camera
+    // so they don't flicker
+    let (gizmo_config, _) = gizmo_configs.config_mut::<DefaultGizmoConfigGroup>();
+    gizmo_config.depth_bias = VOXEL_EDGE_DEPTH_BIAS;
+
     // Parse the pink color from hex
     let pink_color_bytes = hex::decode(PINK_COLOR_HEX).expect("Invalid hex color");
     let pink_color = Color::rgb_u8(
@@ -97,10 +687,14 @@ fn setup(
 
   
     // Spawn camera entity
+    let camera_default = CameraDefault {
+        transform: Transform::from_translation(CAMERA_START_POSITION).looking_at(Vec3::ZERO, Vec3::Y),
+        focus: Vec3::ZERO,
+    };
+    commands.insert_resource(camera_default);
     commands.spawn((
         Camera3dBundle {
-            transform: Transform::from_xyz(0.0, 10.0, 35.0)
-                       .looking_at(Vec3::ZERO, Vec3::Y),
+            transform: camera_default.transform,
             ..default()
         },
         // Add PanOrbitCamera component to the same entity
@@ -122,211 +716,1528 @@ fn setup(
     });
 
     // Add a directional light source
-    commands.spawn(DirectionalLightBundle {
+    let sun = commands.spawn(DirectionalLightBundle {
         directional_light: DirectionalLight {
             shadows_enabled: true,
             ..default()
         },
         transform: Transform::from_xyz(15.0, 20.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
         ..default()
-    });
+    }).id();
+    commands.insert_resource(SunLight(sun));
 
-    // Initialize user input resource
+    // Initialize user input and voxel resources
     commands.insert_resource(UserInput::default());
+    commands.insert_resource(VoxelData::default());
+    commands.insert_resource(VoxelDirty(true));
+    commands.insert_resource(SceneData::default());
+    commands.insert_resource(MorphAnimation::default());
+    commands.insert_resource(VoxelStats::default());
+    commands.insert_resource(SpawnedVoxels::default());
+    commands.insert_resource(ScreenshotState::default());
+    commands.insert_resource(ExportJob::default());
+    commands.insert_resource(CameraView::default());
+    commands.insert_resource(Selection::default());
+    // A unit cube scaled over the selection box while there is one. It has its own mesh:
+    // the shared voxel mesh shrinks with the voxel gap setting
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Cuboid::from_size(Vec3::ONE)),
+            material: materials.add(StandardMaterial {
+                base_color: SELECTION_OVERLAY_COLOR,
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        SelectionOverlay,
+    ));
+    commands.insert_resource(Clipboard::default());
+    commands.insert_resource(History::default());
+    commands.insert_resource(KeyMap::load(Path::new(KEYBINDINGS_PATH)));
+    commands.insert_resource(PresetLibrary::load(PresetLibrary::default_path()));
 }
 
 
-fn ui_system(mut contexts: EguiContexts, mut user_input: ResMut<UserInput>) {
-    egui::Window::new("Sculptor Controls").show(contexts.ctx_mut(), |ui| {
-        let mut changed = false;
+// Keyboard shortcuts as bound in the KeyMap. Nothing happens while a text field has focus,
+// and the orbit keys rest in fly mode, which uses WASD/QE itself.
+#[allow(clippy::too_many_arguments)]
+fn handle_keyboard_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    keymap: Res<KeyMap>,
+    time: Res<Time>,
+    mut contexts: EguiContexts,
+    mut user_input: ResMut<UserInput>,
+    scene: Res<SceneData>,
+    mut export_job: ResMut<ExportJob>,
+    mut screenshot: ResMut<ScreenshotState>,
+    mut reset_camera: EventWriter<ResetCamera>,
+    camera_view: Res<CameraView>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
 
-        ui.heading("Dimensions");
+    if keymap.just_pressed(Action::Screenshot, &keys) {
+        screenshot.requested = true;
+    }
+    if keymap.just_pressed(Action::Export, &keys) {
+        export_scene(&user_input, &scene, &mut export_job);
+    }
 
-        // Width Slider
-        let mut current_width = user_input.width;
-        ui.add(egui::Slider::new(&mut current_width, MIN_DIMENSION..=MAX_DIMENSION).text("Width"));
-        if current_width != user_input.width {
-            user_input.width = current_width;
-            changed = true;
+    let shapes = [
+        (Action::ShapeCube, GeometricShape::Cube),
+        (Action::ShapeSphere, GeometricShape::Sphere),
+        (Action::ShapeCylinder, GeometricShape::Cylinder),
+        (Action::ShapeCone, GeometricShape::Cone),
+        (Action::ShapePyramid, GeometricShape::SquarePyramid),
+        (Action::ShapeTube, GeometricShape::Tube),
+    ];
+    for (action, kind) in shapes {
+        if keymap.just_pressed(action, &keys) && user_input.shape.kind != kind {
+            user_input.shape.kind = kind;
+            request_regeneration(&mut user_input);
         }
+    }
+    if keymap.just_pressed(Action::Regenerate, &keys) {
+        user_input.needs_regeneration = true;
+    }
+    if keymap.just_pressed(Action::ToggleHollow, &keys) {
+        user_input.hollow = !user_input.hollow;
+        request_regeneration(&mut user_input);
+    }
 
-        // Depth Slider
-        let mut current_depth = user_input.depth;
-        ui.add(egui::Slider::new(&mut current_depth, MIN_DIMENSION..=MAX_DIMENSION).text("Depth"));
-        if current_depth != user_input.depth {
-            user_input.depth = current_depth;
-            changed = true;
-        }
+    if keymap.just_pressed(Action::ResetView, &keys) {
+        reset_camera.send(ResetCamera);
+        return;
+    }
 
-        // Height Slider
-        let mut current_height = user_input.height;
-        ui.add(egui::Slider::new(&mut current_height, MIN_DIMENSION..=MAX_DIMENSION).text("Height"));
-        if current_height != user_input.height {
-            user_input.height = current_height;
-            changed = true;
+    let Ok(mut camera) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    // (alpha, beta) pairs; alpha rotates around Y, beta tilts up from the XZ plane
+    let views = [
+        (Action::ViewFront, (0.0, 0.0)),
+        (Action::ViewBack, (PI, 0.0)),
+        (Action::ViewLeft, (-FRAC_PI_2, 0.0)),
+        (Action::ViewRight, (FRAC_PI_2, 0.0)),
+        (Action::ViewTop, (0.0, FRAC_PI_2)),
+        (Action::ViewBottom, (0.0, -FRAC_PI_2)),
+    ];
+    let view = views
+        .into_iter()
+        .find(|&(action, _)| keymap.just_pressed(action, &keys))
+        .map(|(_, view)| view);
+    if let Some((alpha, beta)) = view {
+        camera.target_focus = Vec3::ZERO;
+        camera.target_alpha = nearest_angle(camera.target_alpha, alpha);
+        camera.target_beta = beta;
+        frame_zoom(&mut camera, &user_input, &camera_view);
+    }
+
+    if keymap.just_pressed(Action::Frame, &keys) {
+        camera.target_focus = Vec3::ZERO;
+        frame_zoom(&mut camera, &user_input, &camera_view);
+    }
+
+    if user_input.camera_mode != CameraMode::Orbit {
+        return;
+    }
+    let axis = |positive, negative| {
+        let held = |action| if keymap.pressed(action, &keys) { 1.0 } else { 0.0 };
+        held(positive) - held(negative)
+    };
+    let delta = time.delta_seconds();
+    let turn = axis(Action::OrbitRight, Action::OrbitLeft);
+    let tilt = axis(Action::OrbitUp, Action::OrbitDown);
+    let raise = axis(Action::Raise, Action::Lower);
+    let zoom = axis(Action::ZoomIn, Action::ZoomOut);
+    if turn != 0.0 {
+        camera.target_alpha += turn * KEY_ORBIT_SPEED * delta;
+    }
+    if tilt != 0.0 {
+        let limit = FRAC_PI_2 - 0.01;
+        let beta = camera.target_beta + tilt * KEY_ORBIT_SPEED * delta;
+        camera.target_beta = beta.clamp(-limit, limit);
+    }
+    if raise != 0.0 {
+        camera.target_focus.y += raise * KEY_RAISE_SPEED * delta;
+    }
+    if zoom != 0.0 {
+        let factor = 1.0 - zoom * KEY_ZOOM_SPEED * delta;
+        match camera_view.projection {
+            CameraProjection::Perspective => camera.target_radius *= factor,
+            CameraProjection::Orthographic => camera.target_scale *= factor,
         }
+    }
+}
 
-        ui.separator();
-        ui.heading("Shape");
+// Moves the camera back to its starting position. PanOrbitCamera owns the transform, so
+// the default transform is converted to the orbit angles and radius it would produce.
+fn reset_camera_system(
+    mut events: EventReader<ResetCamera>,
+    camera_default: Res<CameraDefault>,
+    mut user_input: ResMut<UserInput>,
+    camera_view: Res<CameraView>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let Ok(mut camera) = camera_query.get_single_mut() else {
+        return;
+    };
 
-        // Shape Dropdown (Combo Box)
-        let selected_shape_label = user_input.shape.to_string();
-        egui::ComboBox::from_label("Select Shape")
-            .selected_text(selected_shape_label)
-            .show_ui(ui, |ui| {
-                for shape in GeometricShape::iter() {
-                    if ui
-                        .selectable_value(&mut user_input.shape, shape, shape.to_string())
-                        .clicked()
-                    {
-                        changed = true;
-                    }
-                }
+    // The starting view is an orbit view, so leave fly mode
+    user_input.camera_mode = CameraMode::Orbit;
+    camera.enabled = true;
+
+    let offset = camera_default.transform.translation - camera_default.focus;
+    let radius = offset.length();
+    camera.target_focus = camera_default.focus;
+    camera.target_alpha = nearest_angle(camera.target_alpha, offset.x.atan2(offset.z));
+    camera.target_beta = (offset.y / radius).asin();
+    camera.target_radius = radius;
+    if camera_view.projection == CameraProjection::Orthographic {
+        camera.target_scale = orthographic_scale(&user_input);
+    }
+}
+
+// Returns the angle equivalent to `target` that is closest to `current`,
+// so snapping after a few full orbits doesn't spin the camera around
+fn nearest_angle(current: f32, target: f32) -> f32 {
+    let delta = (target - current).rem_euclid(TAU);
+    if delta > PI {
+        current + delta - TAU
+    } else {
+        current + delta
+    }
+}
+
+// Distance at which the model's bounding sphere fits the default 45° field of view
+fn framing_distance(user_input: &UserInput) -> f32 {
+    let extents = Vec3::new(
+        user_input.shape.width as f32,
+        user_input.shape.height as f32,
+        user_input.shape.depth as f32,
+    );
+    let bounding_radius = extents.length() / 2.0;
+    let half_fov = FRAC_PI_4 / 2.0;
+    bounding_radius / half_fov.sin() * 1.1
+}
+
+// Height of the orthographic view that fits the model's bounding sphere with the same margin
+// as framing_distance
+fn orthographic_scale(user_input: &UserInput) -> f32 {
+    let extents = Vec3::new(
+        user_input.shape.width as f32,
+        user_input.shape.height as f32,
+        user_input.shape.depth as f32,
+    );
+    extents.length() * 1.1
+}
+
+// Zooms so the whole model fits: dolly distance in perspective, projection scale in
+// orthographic mode
+fn frame_zoom(camera: &mut PanOrbitCamera, user_input: &UserInput, camera_view: &CameraView) {
+    camera.target_radius = framing_distance(user_input);
+    if camera_view.projection == CameraProjection::Orthographic {
+        camera.target_scale = orthographic_scale(user_input);
+    }
+}
+
+// Swaps the camera's Projection when the UI toggle changes. PanOrbitCamera zooms an
+// orthographic camera by its scale, which starts out fitted to the model.
+fn update_projection(
+    camera_view: Res<CameraView>,
+    user_input: Res<UserInput>,
+    mut camera_query: Query<(&mut Projection, &mut PanOrbitCamera)>,
+) {
+    if !camera_view.is_changed() {
+        return;
+    }
+    let Ok((mut projection, mut camera)) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    match (camera_view.projection, &*projection) {
+        (CameraProjection::Orthographic, Projection::Perspective(_)) => {
+            let scale = orthographic_scale(&user_input);
+            *projection = Projection::Orthographic(OrthographicProjection {
+                scale,
+                scaling_mode: ScalingMode::FixedVertical(1.0),
+                ..default()
             });
+            camera.scale = Some(scale);
+            camera.target_scale = scale;
+            // The near plane sits at the camera, so back off far enough to not cut into the model
+            camera.target_radius = camera.target_radius.max(framing_distance(&user_input));
+        }
+        (CameraProjection::Perspective, Projection::Orthographic(_)) => {
+            *projection = Projection::Perspective(default());
+        }
+        _ => return,
+    }
+    camera.force_update = true;
+}
+
+// Swings the orbit angles to a requested view preset over VIEW_SNAP_SECONDS, keeping the focus
+// point and zoom. Dragging the view or switching to fly mode cancels the swing.
+fn view_snap_system(
+    time: Res<Time>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut camera_view: ResMut<CameraView>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    let Ok(mut camera) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    if let Some(preset) = camera_view.requested_preset {
+        camera_view.requested_preset = None;
+        let (alpha, beta) = preset.angles();
+        let from = (
+            camera.alpha.unwrap_or(camera.target_alpha),
+            camera.beta.unwrap_or(camera.target_beta),
+        );
+        let to = (nearest_angle(from.0, alpha), beta);
+        camera_view.snap = Some(ViewSnap { from, to, elapsed: 0.0 });
+    }
+    if camera_view.snap.is_none() {
+        return;
+    }
+    if !camera.enabled || mouse.pressed(camera.button_orbit) {
+        camera_view.snap = None;
+        return;
+    }
+    let Some(snap) = camera_view.snap.as_mut() else {
+        return;
+    };
+
+    snap.elapsed += time.delta_seconds();
+    let t = (snap.elapsed / VIEW_SNAP_SECONDS).min(1.0);
+    let eased = t * t * (3.0 - 2.0 * t);
+    let alpha = snap.from.0 + (snap.to.0 - snap.from.0) * eased;
+    let beta = snap.from.1 + (snap.to.1 - snap.from.1) * eased;
+    // Set the current angles too, so PanOrbitCamera's own smoothing doesn't drag the swing out
+    camera.alpha = Some(alpha);
+    camera.target_alpha = alpha;
+    camera.beta = Some(beta);
+    camera.target_beta = beta;
+    camera.force_update = true;
+    if t >= 1.0 {
+        camera_view.snap = None;
+    }
+}
+
+// Orbits the camera while the turntable is on, pausing while the user drags the view
+fn turntable_system(
+    time: Res<Time>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    user_input: Res<UserInput>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    if !user_input.turntable {
+        return;
+    }
+    let Ok(mut camera) = camera_query.get_single_mut() else {
+        return;
+    };
+    if mouse.pressed(camera.button_orbit) || mouse.pressed(camera.button_pan) {
+        return;
+    }
+
+    camera.target_alpha += user_input.turntable_speed.to_radians() * time.delta_seconds();
+}
+
+// Fly mode: WASD moves along the view, Q/E down and up, Shift speeds up and dragging with
+// the right mouse button looks around. The orbit controls are switched off meanwhile and
+// re-aimed at a point in front of the camera when going back to orbit mode.
+fn fly_camera_system(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    user_input: Res<UserInput>,
+    mut contexts: EguiContexts,
+    mut camera_query: Query<(&mut Transform, &mut PanOrbitCamera)>,
+) {
+    let look_delta: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+    let Ok((mut transform, mut camera)) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    if user_input.camera_mode == CameraMode::Orbit {
+        if !camera.enabled {
+            // Orbit around the point the fly camera was looking at, as far away as before
+            let radius = camera.radius.unwrap_or(camera.target_radius);
+            let focus = transform.translation + transform.forward() * radius;
+            let offset = transform.translation - focus;
+            let alpha = offset.x.atan2(offset.z);
+            let beta = (offset.y / radius).clamp(-1.0, 1.0).asin();
+            camera.focus = focus;
+            camera.target_focus = focus;
+            camera.alpha = Some(alpha);
+            camera.target_alpha = alpha;
+            camera.beta = Some(beta);
+            camera.target_beta = beta;
+            camera.radius = Some(radius);
+            camera.target_radius = radius;
+            camera.enabled = true;
+            camera.force_update = true;
+        }
+        return;
+    }
+    camera.enabled = false;
+
+    if mouse.pressed(MouseButton::Right) && !contexts.ctx_mut().is_pointer_over_area() {
+        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        let yaw = yaw - look_delta.x * FLY_LOOK_SENSITIVITY;
+        let pitch = (pitch - look_delta.y * FLY_LOOK_SENSITIVITY)
+            .clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+        transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0);
+    }
+
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let mut direction = Vec3::ZERO;
+    for (key, step) in [
+        (KeyCode::KeyW, *transform.forward()),
+        (KeyCode::KeyS, -*transform.forward()),
+        (KeyCode::KeyD, *transform.right()),
+        (KeyCode::KeyA, -*transform.right()),
+        (KeyCode::KeyE, *transform.up()),
+        (KeyCode::KeyQ, -*transform.up()),
+    ] {
+        if keys.pressed(key) {
+            direction += step;
+        }
+    }
+    let mut speed = FLY_SPEED;
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        speed *= FLY_FAST_MULTIPLIER;
+    }
+    transform.translation += direction.normalize_or_zero() * speed * time.delta_seconds();
+}
+
+// Captures the primary window into the PNG file or folder chosen in the UI
+fn screenshot_system(
+    mut commands: Commands,
+    mut screenshot: ResMut<ScreenshotState>,
+    user_input: Res<UserInput>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+    toast: Option<ResMut<ScreenshotResult>>,
+    time: Res<Time>,
+) {
+    if let Some(mut toast) = toast {
+        if toast.timer.tick(time.delta()).finished() {
+            commands.remove_resource::<ScreenshotResult>();
+        }
+    }
+    // Report what an earlier capture did
+    if let Some(outcome) = screenshot.outcome.lock().unwrap().take() {
+        commands.insert_resource(ScreenshotResult::new(outcome));
+    }
+
+    if !screenshot.requested {
+        return;
+    }
+
+    // Skip the UI for one frame so it isn't part of the capture
+    if user_input.hide_ui_in_screenshot && !screenshot.ui_hidden {
+        screenshot.ui_hidden = true;
+        return;
+    }
+    screenshot.requested = false;
+    screenshot.ui_hidden = false;
+
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
 
-        if changed {
-            user_input.needs_regeneration = true;
+    let target = Path::new(user_input.screenshot_path.trim());
+    let is_file = target
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    let dir = if is_file { target.parent().unwrap_or(Path::new("")) } else { target };
+    if !dir.as_os_str().is_empty() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            let error = format!("Cannot create {}: {}", dir.display(), e);
+            commands.insert_resource(ScreenshotResult::new(Err(error)));
+            return;
         }
+    }
+    let path = if is_file { target.to_path_buf() } else { screenshot_path(dir) };
+
+    let outcome_slot = screenshot.outcome.clone();
+    let result = screenshot_manager.take_screenshot(window, move |image| {
+        let saved = image
+            .try_into_dynamic()
+            .map_err(|e| e.to_string())
+            .and_then(|image| image.to_rgb8().save(&path).map_err(|e| e.to_string()));
+        let outcome = match saved {
+            Ok(()) => Ok(path),
+            Err(e) => Err(format!("Cannot save screenshot {}: {}", path.display(), e)),
+        };
+        *outcome_slot.lock().unwrap() = Some(outcome);
     });
+    if result.is_err() {
+        let error = "A screenshot is already being captured".to_string();
+        commands.insert_resource(ScreenshotResult::new(Err(error)));
+    }
 }
 
+// Picks sculpt_<timestamp>.png in `dir`, adding a counter if that name is taken
+fn screenshot_path(dir: &Path) -> PathBuf {
+    let stem = format!("sculpt_{}", timestamp());
+    let mut path = dir.join(format!("{}.png", stem));
+    let mut counter = 1;
+    while path.exists() {
+        path = dir.join(format!("{}_{}.png", stem, counter));
+        counter += 1;
+    }
+    path
+}
+
+// Current UTC time formatted as 2024-05-01_123456
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let (days, time_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Convert days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60
+    )
+}
+
+// Regenerates the voxel data whenever the user changes the inputs
 fn generate_shape_system(
-    mut commands: Commands,
     mut user_input: ResMut<UserInput>,
-    voxel_query: Query<Entity, With<Voxel>>,
-    voxel_material: Res<VoxelMaterial>,
-    voxel_mesh: Res<VoxelMesh>,
+    mut voxel_data: ResMut<VoxelData>,
+    mut morph: ResMut<MorphAnimation>,
+    mut dirty: ResMut<VoxelDirty>,
 ) {
     if !user_input.needs_regeneration {
         return;
     }
 
+    let cells = user_input.shape.cell_count();
+    if cells > MAX_GRID_CELLS {
+        let shape = &user_input.shape;
+        let message = format!(
+            "A {}x{}x{} grid has {} cells, more than the limit of {}",
+            shape.width, shape.height, shape.depth, cells, MAX_GRID_CELLS
+        );
+        user_input.error_message = Some(message);
+        user_input.needs_regeneration = false;
+        return;
+    }
+
+    // Syntax errors in a formula are shown instead of silently producing an empty shape
+    if user_input.shape.kind.uses_expression() {
+        if let Err(e) = expr::parse(&user_input.shape.expression) {
+            user_input.error_message = Some(format!("Invalid formula: {}", e));
+            user_input.needs_regeneration = false;
+            return;
+        }
+    }
+
+    // A new shape ends any morph so the model itself is shown again
+    if morph.is_active() {
+        morph.stop();
+    }
+
     println!("Regenerating shape: {:?}", *user_input);
 
-    // 1. Despawn existing voxels
-    for entity in voxel_query.iter() {
-        commands.entity(entity).despawn_recursive();
-    }
-
-    // 2. Get parameters
-    let w = user_input.width as f32;
-    let d = user_input.depth as f32;
-    let h = user_input.height as f32;
-
-    let center_x = w / 2.0 - 0.5;
-    let center_y = h / 2.0 - 0.5;
-    let center_z = d / 2.0 - 0.5;
-
-    let radius_x = w / 2.0;
-    let radius_y = h / 2.0; //sphere uses it as radius
-    let radius_z = d / 2.0;
-
-    // 3. Iterate through potential voxel grid positions
-    for y_idx in 0..user_input.height {
-        for z_idx in 0..user_input.depth {
-            for x_idx in 0..user_input.width {
-                // Voxel center coordinates (relative to grid origin 0,0,0)
-                let vx = x_idx as f32 + 0.5;
-                let vy = y_idx as f32 + 0.5;
-                let vz = z_idx as f32 + 0.5;
-
-                // Voxel position in world space (relative to center of bounding box)
-                let pos_x = x_idx as f32 - center_x;
-                let pos_y = y_idx as f32 - center_y;
-                let pos_z = z_idx as f32 - center_z;
-
-                let mut should_spawn = false;
-                match user_input.shape {
-                    GeometricShape::Cube => {
-                        should_spawn = true;
-                    }
-                    GeometricShape::Sphere => {
-                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
-                        let norm_y = if radius_y > 0.0 { (vy - h / 2.0) / radius_y } else { 0.0 };
-                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
-                        if norm_x.powi(2) + norm_y.powi(2) + norm_z.powi(2) <= 1.0 {
-                            should_spawn = true;
-                        }
-                    }
-                    GeometricShape::Cylinder => {
-                        // Y-axis aligned cylinder
-                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
-                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
-                        if norm_x.powi(2) + norm_z.powi(2) <= 1.0 && y_idx < user_input.height {
-                            should_spawn = true;
-                        }
-                    }
-                    GeometricShape::Cone => {
-                        // Y-axis aligned cone, apex pointing up (+Y)
-                        if h <= 0.0 { continue; }
-                        let scale_factor = (1.0 - (vy / h)).max(0.0); // Ensure scale factor is not negative
+    let scene = compose_scene(&user_input.shape, &user_input.scene_shapes);
+    voxel_data.voxels = apply_symmetry(&scene.voxels, &scene.shape, user_input.symmetry);
+    voxel_data.shape = scene.shape;
+    apply_noise(&mut voxel_data.voxels, user_input.erosion, user_input.erosion_seed);
+    if user_input.hollow {
+        voxel_data.voxels = hollow_voxels(&voxel_data.voxels);
+    }
+    if voxel_data.voxels.is_empty() {
+        user_input.error_message = Some(EMPTY_SHAPE_ERROR.into());
+    }
+    dirty.0 = true;
 
-                        let scaled_radius_x = radius_x * scale_factor;
-                        let scaled_radius_z = radius_z * scale_factor;
+    // Reset the flag
+    user_input.needs_regeneration = false;
+}
 
-                        let norm_x = if scaled_radius_x > 0.01 { (vx - w/2.0) / scaled_radius_x } else { 0.0 };
-                        let norm_z = if scaled_radius_z > 0.01 { (vz - d/2.0) / scaled_radius_z } else { 0.0 };
+// Applies the sculpt tool where the cursor ray meets the selected object, outlining the cell
+// it would change while hovering. Add and Erase edit on a left click, so a drag still moves
+// the camera; the brush paints along a left drag. During a stroke the ray is cast against
+// the voxels from before it, so added voxels don't pile up towards the camera.
+#[allow(clippy::too_many_arguments)]
+fn sculpt_system(
+    mouse: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut contexts: EguiContexts,
+    mut user_input: ResMut<UserInput>,
+    scene: Res<SceneData>,
+    mut voxel_data: ResMut<VoxelData>,
+    mut selection: ResMut<Selection>,
+    clipboard: Res<Clipboard>,
+    mut dirty: ResMut<VoxelDirty>,
+    mut gizmos: Gizmos,
+    mut pressed_at: Local<Option<Vec2>>,
+    mut stroke: Local<Option<VoxelGrid>>,
+    mut draw_start: Local<Option<(SculptTool, VoxelCoord)>>,
+) {
+    if !mouse.pressed(MouseButton::Left) {
+        *stroke = None;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    let tool = match user_input.sculpt_tool {
+        SculptTool::Off => SculptTool::Off,
+        _ if alt => SculptTool::Eyedropper,
+        SculptTool::Add if shift => SculptTool::Erase,
+        tool => tool,
+    };
+    if tool == SculptTool::Off {
+        *pressed_at = None;
+        *stroke = None;
+        return;
+    }
+    let Some(cursor) = windows.get_single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+    if contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    // The ray in the selected object's grid, where voxel (x, y, z) is centered at (x, y, z)
+    let grid_origin = scene.origin().centered_position(scene.selected_object().offset);
+    let voxels = stroke.as_ref().unwrap_or(&voxel_data.voxels);
+    let hit = pick_voxel(voxels, &voxel_data.shape, ray.origin - grid_origin, *ray.direction);
+    let erasing = match tool {
+        SculptTool::Brush => user_input.brush_erase,
+        tool => tool == SculptTool::Erase,
+    };
+    // Tools that act on the clicked voxel itself rather than the cell in front of it
+    let on_voxel = erasing
+        || matches!(tool, SculptTool::Paint | SculptTool::Eyedropper | SculptTool::Extrude)
+        || (tool == SculptTool::Fill && user_input.fill_recolor);
+    let target = if tool == SculptTool::Select {
+        hit.map(|hit| if hit.on_floor() { hit.adjacent() } else { hit.voxel })
+    } else if on_voxel {
+        hit.filter(|hit| !hit.on_floor()).map(|hit| hit.voxel)
+    } else {
+        hit.map(|hit| hit.adjacent()).filter(|&cell| in_grid(cell, &voxel_data.shape))
+    };
+    if let Some((x, y, z)) = target {
+        let center = grid_origin + Vec3::new(x as f32, y as f32, z as f32);
+        let outline = match tool {
+            SculptTool::Select => Color::YELLOW,
+            SculptTool::Eyedropper => Color::CYAN,
+            SculptTool::Paint => {
+                let base = user_input.lighting.base_color;
+                let [r, g, b] = palette_color(user_input.paint_color, base);
+                Color::rgb_u8(r, g, b)
+            }
+            _ if erasing => Color::RED,
+            _ => Color::WHITE,
+        };
+        let radius = user_input.brush_radius as f32;
+        match (tool, user_input.brush_shape) {
+            (SculptTool::Brush, BrushShape::Sphere) => {
+                gizmos.sphere(center, Quat::IDENTITY, radius - 0.5, outline);
+            }
+            (SculptTool::Brush, BrushShape::Cube) => {
+                let size = Vec3::splat(2.0 * radius - 1.0);
+                gizmos.cuboid(Transform::from_translation(center).with_scale(size), outline);
+            }
+            _ => {
+                let size = Vec3::splat(1.05);
+                gizmos.cuboid(Transform::from_translation(center).with_scale(size), outline);
+            }
+        }
+        // The box the next click would complete
+        if let (SculptTool::Select, Some(anchor)) = (tool, selection.anchor) {
+            let (min, max) = Selection::from_corners(anchor, (x, y, z)).bounds.unwrap_or_default();
+            gizmos.cuboid(selection_box(grid_origin, min, max), Color::YELLOW);
+        }
+        // The voxels the next click would extrude
+        if let (SculptTool::Extrude, Some(hit)) = (tool, hit) {
+            let faces = face_region(voxels, hit.voxel, hit.normal);
+            let grown = extrusion(voxels, &faces, hit.normal, user_input.extrude_distance);
+            if let Some((min, max)) = grown.bounding_box() {
+                gizmos.cuboid(selection_box(grid_origin, min, max), outline);
+            }
+        }
+        // Where the next click would paste
+        if tool == SculptTool::Paste {
+            if let Some((min, max)) = clipboard.selection_at((x, y, z)).bounds {
+                gizmos.cuboid(selection_box(grid_origin, min, max), outline);
+            }
+        }
+        // The line or rectangle the next click would draw
+        match *draw_start {
+            Some((SculptTool::Line, start)) if tool == SculptTool::Line => {
+                for (x, y, z) in line_cells(start, (x, y, z)) {
+                    let center = grid_origin + Vec3::new(x as f32, y as f32, z as f32);
+                    gizmos.cuboid(Transform::from_translation(center), outline);
+                }
+            }
+            Some((SculptTool::Plane, start)) if tool == SculptTool::Plane => {
+                let cells = plane_cells(start, (x, y, z));
+                let (first, last) = (cells[0], cells[cells.len() - 1]);
+                gizmos.cuboid(selection_box(grid_origin, first, last), outline);
+            }
+            _ => {}
+        }
+    }
 
-                        // Check within base ellipse at this height and within overall height
-                        if norm_x.powi(2) + norm_z.powi(2) <= 1.0 && y_idx < user_input.height {
-                             should_spawn = true;
+    if mouse.just_pressed(MouseButton::Left) {
+        *pressed_at = Some(cursor);
+        if tool == SculptTool::Brush {
+            *stroke = Some(voxel_data.voxels.clone());
+        }
+    }
+    // Cells an edit touches, and their images across the mirror planes
+    let mirror = user_input.mirror_edits;
+    let mirrored = |cells: &[VoxelCoord], shape: &Shape| {
+        apply_symmetry(cells, shape, mirror).to_sorted_vec()
+    };
+    if tool == SculptTool::Brush {
+        if let (Some(_), Some(center)) = (stroke.as_ref(), target) {
+            let (radius, brush) = (user_input.brush_radius, user_input.brush_shape);
+            let cells = mirrored(&brush_cells(center, radius, brush), &voxel_data.shape);
+            let mut changed = false;
+            for cell in cells {
+                changed |= if erasing {
+                    voxel_data.voxels.remove(&cell)
+                } else {
+                    in_grid(cell, &voxel_data.shape)
+                        && voxel_data.voxels.insert_colored(cell, user_input.paint_color)
+                };
+            }
+            dirty.0 |= changed;
+        }
+        return;
+    }
+    if tool == SculptTool::Paint {
+        let dragging = mouse.pressed(MouseButton::Left) && pressed_at.is_some();
+        if let (true, Some(cell)) = (dragging, target) {
+            let color = user_input.paint_color;
+            for cell in mirrored(&[cell], &voxel_data.shape) {
+                dirty.0 |= voxel_data.voxels.paint(&cell, color);
+            }
+        }
+        if !mouse.pressed(MouseButton::Left) {
+            *pressed_at = None;
+        }
+        return;
+    }
+    if !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    let clicked = pressed_at.take().is_some_and(|start| start.distance(cursor) <= CLICK_MAX_PIXELS);
+    if let (true, Some(cell)) = (clicked, target) {
+        let color = user_input.paint_color;
+        let VoxelData { shape, voxels } = &mut *voxel_data;
+        let mirror_images = mirrored(&[cell], shape);
+        match tool {
+            SculptTool::Add => {
+                voxels.extend(mirror_images.into_iter().map(|cell| (cell, color)));
+            }
+            SculptTool::Erase => {
+                for cell in &mirror_images {
+                    voxels.remove(cell);
+                }
+            }
+            SculptTool::Select => {
+                selection.click(cell);
+                return;
+            }
+            SculptTool::Eyedropper => {
+                if let Some(picked) = voxels.color(&cell) {
+                    user_input.paint_color = picked;
+                }
+                return;
+            }
+            SculptTool::Extrude => {
+                let Some(normal) = hit.map(|hit| hit.normal) else {
+                    return;
+                };
+                let faces = face_region(voxels, cell, normal);
+                let grown = extrusion(voxels, &faces, normal, user_input.extrude_distance);
+                for (cell, color) in grown.colored() {
+                    for image in mirrored(&[cell], shape) {
+                        if in_grid(image, shape) {
+                            voxels.insert_colored(image, color);
                         }
                     }
-                    GeometricShape::SquarePyramid => {
-                        // Y-axis aligned pyramid, apex pointing up (+Y)
-                        if h <= 0.0 { continue; }
-                        let scale_factor = (1.0 - (vy / h)).max(0.0); // Ensure scale factor is not negative
+                }
+            }
+            SculptTool::Paste => {
+                if clipboard.is_empty() {
+                    return;
+                }
+                clipboard.paste(voxels, shape, cell);
+                *selection = clipboard.selection_at(cell);
+            }
+            SculptTool::Fill if user_input.fill_recolor => {
+                for start in mirror_images {
+                    recolor_region(voxels, start, color);
+                }
+            }
+            SculptTool::Fill => {
+                // Bounded by the selection box if there is one, so filling around an open
+                // model doesn't flood the whole grid
+                let bounds = selection.bounds.or(Selection::all(shape).bounds);
+                let Some(bounds) = bounds else {
+                    return;
+                };
+                for start in mirror_images {
+                    fill_empty_region(voxels, start, bounds, color);
+                }
+            }
+            SculptTool::Line | SculptTool::Plane => {
+                let Some((_, start)) = draw_start.take().filter(|&(started, _)| started == tool)
+                else {
+                    *draw_start = Some((tool, cell));
+                    return;
+                };
+                let cells = match tool {
+                    SculptTool::Line => line_cells(start, cell),
+                    _ => plane_cells(start, cell),
+                };
+                let cells = mirrored(&cells, shape).into_iter();
+                voxels.extend(cells.filter(|&cell| in_grid(cell, shape)).map(|cell| (cell, color)));
+            }
+            SculptTool::Brush | SculptTool::Paint | SculptTool::Off => return,
+        }
+        dirty.0 = true;
+    }
+}
+
+// Selection shortcuts: moving the selection (or the whole selected object when nothing is
+// selected) a voxel per key press, and the clipboard keys
+#[allow(clippy::too_many_arguments)]
+fn selection_keys_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    keymap: Res<KeyMap>,
+    mut contexts: EguiContexts,
+    mut user_input: ResMut<UserInput>,
+    mut voxel_data: ResMut<VoxelData>,
+    mut selection: ResMut<Selection>,
+    mut clipboard: ResMut<Clipboard>,
+    mut dirty: ResMut<VoxelDirty>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let VoxelData { shape, voxels } = &mut *voxel_data;
+    for (action, delta) in SELECTION_MOVES {
+        if keymap.just_pressed(action, &keys) {
+            dirty.0 |= selection.nudge(voxels, shape, delta, user_input.move_mode);
+        }
+    }
+    if selection.bounds.is_some() {
+        if keymap.just_pressed(Action::Copy, &keys) {
+            *clipboard = selection.copy(voxels);
+        }
+        if keymap.just_pressed(Action::Cut, &keys) {
+            *clipboard = selection.copy(voxels);
+            dirty.0 |= selection.delete(voxels) > 0;
+        }
+        if keymap.just_pressed(Action::Duplicate, &keys) {
+            selection.duplicate(voxels, shape, user_input.duplicate_offset);
+            dirty.0 = true;
+        }
+    }
+    if keymap.just_pressed(Action::Paste, &keys) && !clipboard.is_empty() {
+        user_input.sculpt_tool = SculptTool::Paste;
+    }
+}
 
-                        let max_dist_x = radius_x * scale_factor;
-                        let max_dist_z = radius_z * scale_factor;
+// Transform of a unit cube stretched over the cells `min` to `max` of a grid whose voxel
+// (0, 0, 0) is centered at `grid_origin`
+fn selection_box(grid_origin: Vec3, min: VoxelCoord, max: VoxelCoord) -> Transform {
+    let min = Vec3::new(min.0 as f32, min.1 as f32, min.2 as f32);
+    let max = Vec3::new(max.0 as f32, max.1 as f32, max.2 as f32);
+    Transform::from_translation(grid_origin + (min + max) / 2.0)
+        .with_scale(max - min + Vec3::splat(1.0))
+}
 
-                         // Check within bounding box at this height and within overall height
-                        if (vx - w / 2.0).abs() <= max_dist_x && (vz - d / 2.0).abs() <= max_dist_z && y_idx < user_input.height {
-                             should_spawn = true;
-                        }
+// Shows the selection box as a translucent block over the selected object, slightly larger
+// than its cells so it doesn't flicker against the voxel faces
+fn update_selection_overlay(
+    selection: Res<Selection>,
+    scene: Res<SceneData>,
+    mut overlay_query: Query<(&mut Transform, &mut Visibility), With<SelectionOverlay>>,
+) {
+    let Ok((mut transform, mut visibility)) = overlay_query.get_single_mut() else {
+        return;
+    };
+    // Only written when they differ, so an unchanged overlay isn't marked changed every frame
+    let Some((min, max)) = selection.bounds else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+    let grid_origin = scene.origin().centered_position(scene.selected_object().offset);
+    let placed = selection_box(grid_origin, min, max);
+    transform.set_if_neq(placed.with_scale(placed.scale + Vec3::splat(SELECTION_OVERLAY_MARGIN)));
+    visibility.set_if_neq(Visibility::Inherited);
+}
+
+// Undo and redo keys, applied before generation so the restored voxels are synced and drawn
+// in the same frame
+#[allow(clippy::too_many_arguments)]
+fn undo_keys_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    keymap: Res<KeyMap>,
+    mut contexts: EguiContexts,
+    mut history: ResMut<History>,
+    scene: Res<SceneData>,
+    mut user_input: ResMut<UserInput>,
+    mut voxel_data: ResMut<VoxelData>,
+    mut dirty: ResMut<VoxelDirty>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    for (action, redo) in [(Action::Undo, false), (Action::Redo, true)] {
+        if keymap.just_pressed(action, &keys) {
+            step_history(redo, &mut history, &scene, &mut user_input, &mut voxel_data, &mut dirty);
+        }
+    }
+}
+
+// Records every edit of the selected object for undo. Edits made while the left button is
+// held, like brush strokes and slider drags, become one step when it is let go
+fn record_history(
+    mouse: Res<ButtonInput<MouseButton>>,
+    scene: Res<SceneData>,
+    voxel_data: Res<VoxelData>,
+    dirty: Res<VoxelDirty>,
+    mut history: ResMut<History>,
+    mut pending: Local<bool>,
+) {
+    *pending |= dirty.0;
+    if !*pending || mouse.pressed(MouseButton::Left) {
+        return;
+    }
+    *pending = false;
+    history.record(scene.selected_object().id, &voxel_data);
+}
+
+// Copies the edited VoxelData back into the selected scene object
+fn sync_selected_object(
+    voxel_data: Res<VoxelData>,
+    mut scene: ResMut<SceneData>,
+    dirty: Res<VoxelDirty>,
+) {
+    if !dirty.0 {
+        return;
+    }
+    let selected = scene.selected;
+    scene.objects[selected].data = voxel_data.clone();
+}
+
+// Syncs the voxel entities with the visible scene objects: only removed voxels are
+// despawned and only new ones spawned, so small edits don't rebuild the whole model.
+// Objects over LARGE_MODEL_VOXELS get one greedy mesh per layer instead, rebuilt when
+// their voxels change; they carry the Voxel component too, so layer clipping, colors and
+// wireframes treat them like voxels.
+#[allow(clippy::too_many_arguments)]
+fn update_voxels(
+    mut commands: Commands,
+    scene: Res<SceneData>,
+    mut morph: ResMut<MorphAnimation>,
+    time: Res<Time>,
+    mut spawned: ResMut<SpawnedVoxels>,
+    mut voxel_query: Query<(&mut Transform, &mut Voxel)>,
+    voxel_material: Res<VoxelMaterial>,
+    voxel_mesh: Res<VoxelMesh>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut dirty: ResMut<VoxelDirty>,
+) {
+    if morph.playing {
+        morph.step(time.delta_seconds());
+    }
+    if !dirty.0 && !morph.is_changed() {
+        return;
+    }
+    dirty.0 = false;
+
+    // 1. Where every wanted voxel goes; the first object's grid is centered on the origin.
+    // While morphing, the selected object shows the morph frame instead of its voxels.
+    let origin = scene.origin();
+    let mut wanted: HashMap<(u32, VoxelCoord), (Vec3, f32, u32, u8)> = HashMap::new();
+    let mut wanted_meshed: HashMap<u32, (&VoxelData, Vec3)> = HashMap::new();
+    for (index, object) in scene.objects.iter().enumerate() {
+        if !object.visible {
+            continue;
+        }
+        let (dx, dy, dz) = object.offset;
+        let morphing = index == scene.selected && morph.is_active();
+        if !morphing && object.data.voxels.len() > LARGE_MODEL_VOXELS {
+            let placed = origin.centered_position((dx, dy, dz));
+            wanted_meshed.insert(object.id, (&object.data, placed));
+            continue;
+        }
+        let frame = if morphing {
+            morph.frame()
+        } else {
+            let still = |&coord| MorphVoxel { coord, scale: 1.0, lift: 0.0 };
+            object.data.voxels.iter().map(still).collect()
+        };
+        for MorphVoxel { coord: (x, y, z), scale, lift } in frame {
+            let translation = origin.centered_position((x + dx, y + dy, z + dz)) + Vec3::Y * lift;
+            let color = object.data.voxels.color(&(x, y, z)).unwrap_or(UNPAINTED);
+            let height = object.data.shape.height;
+            wanted.insert((object.id, (x, y, z)), (translation, scale, height, color));
+        }
+    }
+
+    // 2. Despawn voxels that are no longer part of the scene
+    spawned.entities.retain(|key, (entity, _)| {
+        let keep = wanted.contains_key(key);
+        if !keep {
+            commands.entity(*entity).despawn_recursive();
+        }
+        keep
+    });
+
+    // 3. Spawn new voxels; kept ones only move if their object moved or its grid changed
+    for (key, (translation, scale, grid_height, color)) in wanted {
+        let scale = Vec3::splat(scale);
+        match spawned.entities.get_mut(&key) {
+            Some((entity, placed)) => {
+                if let Ok((mut transform, mut voxel)) = voxel_query.get_mut(*entity) {
+                    if *placed != translation {
+                        transform.translation = translation;
+                        *placed = translation;
+                    }
+                    if transform.scale != scale {
+                        transform.scale = scale;
+                    }
+                    if voxel.grid_height != grid_height {
+                        voxel.grid_height = grid_height;
+                    }
+                    if voxel.color != color {
+                        voxel.color = color;
                     }
                 }
-
-                // 5. Spawn the voxel entity if needed
-                if should_spawn {
-                    commands.spawn((
+            }
+            None => {
+                let entity = commands
+                    .spawn((
                         PbrBundle {
                             mesh: voxel_mesh.0.clone(),
                             material: voxel_material.0.clone(),
-                            transform: Transform::from_xyz(pos_x, pos_y, pos_z),
+                            transform: Transform::from_translation(translation).with_scale(scale),
                             ..default()
                         },
-                        Voxel,
-                    ));
+                        Voxel {
+                            y: key.1 .1,
+                            grid_height,
+                            color,
+                        },
+                    ))
+                    .id();
+                spawned.entities.insert(key, (entity, translation));
+            }
+        }
+    }
+
+    // 4. Layer meshes: drop those of objects that went away or changed, move the ones whose
+    // object moved and build the missing ones
+    spawned.meshed.retain(|id, meshed| {
+        let keep = wanted_meshed.get(id).is_some_and(|(data, _)| {
+            meshed.voxels == data.voxels && meshed.grid_height == data.shape.height
+        });
+        if !keep {
+            for &entity in &meshed.layers {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        keep
+    });
+    for (id, (data, placed)) in wanted_meshed {
+        if let Some(meshed) = spawned.meshed.get_mut(&id) {
+            if meshed.placed != placed {
+                for &entity in &meshed.layers {
+                    if let Ok((mut transform, _)) = voxel_query.get_mut(entity) {
+                        transform.translation = placed;
+                    }
                 }
+                meshed.placed = placed;
             }
+            continue;
+        }
+
+        // One mesh per layer and palette color, so each can get its own material
+        let mut layers: HashMap<(i32, u8), VoxelGrid> = HashMap::new();
+        for (voxel, color) in data.voxels.colored() {
+            layers.entry((voxel.1, color)).or_default().insert_colored(voxel, color);
+        }
+        let grid_height = data.shape.height;
+        let entities = layers
+            .into_iter()
+            .map(|((y, color), voxels)| {
+                // Meshed on its own, every layer is closed, so clipping away the layers above
+                // shows a solid cut instead of a hollow shell. Faces between colors are kept
+                let layer = VoxelData { shape: data.shape.clone(), voxels };
+                let mesh = meshes.add(quads_mesh(&greedy_mesh(&layer, false)));
+                commands
+                    .spawn((
+                        PbrBundle {
+                            mesh,
+                            material: voxel_material.0.clone(),
+                            transform: Transform::from_translation(placed),
+                            ..default()
+                        },
+                        Voxel { y, grid_height, color },
+                    ))
+                    .id()
+            })
+            .collect();
+        let meshed = MeshedObject {
+            voxels: data.voxels.clone(),
+            grid_height,
+            layers: entities,
+            placed,
+        };
+        spawned.meshed.insert(id, meshed);
+    }
+}
+
+// Triangle mesh of greedy quads, positioned so that voxel (0, 0, 0) is centered on the origin
+fn quads_mesh(quads: &[Quad]) -> Mesh {
+    let mut positions = Vec::with_capacity(quads.len() * 4);
+    let mut normals = Vec::with_capacity(quads.len() * 4);
+    let mut indices = Vec::with_capacity(quads.len() * 6);
+    for quad in quads {
+        let (nx, ny, nz) = NEIGHBOR_OFFSETS[quad.direction];
+        let base = positions.len() as u32;
+        for (x, y, z) in quad.corners() {
+            positions.push([x as f32 - 0.5, y as f32 - 0.5, z as f32 - 0.5]);
+            normals.push([nx as f32, ny as f32, nz as f32]);
         }
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
     }
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_indices(Indices::U32(indices))
+}
 
-    // Reset the flag
-    user_input.needs_regeneration = false;
+// Hides voxel entities outside the inspected layers. Only the rendering is filtered,
+// the voxel data (and so any export) keeps the full model.
+fn update_layer_visibility(
+    user_input: Res<UserInput>,
+    spawned: Res<SpawnedVoxels>,
+    morph: Res<MorphAnimation>,
+    mut voxel_query: Query<(&Voxel, &mut Visibility)>,
+    mut last_filter: Local<Option<(bool, i32, i32, bool)>>,
+) {
+    let filter = (
+        user_input.layer_clip,
+        user_input.layer_clip_min,
+        user_input.layer_clip_y,
+        user_input.single_layer,
+    );
+    if *last_filter == Some(filter) && !spawned.is_changed() && !morph.is_changed() {
+        return;
+    }
+    *last_filter = Some(filter);
+
+    for (voxel, mut visibility) in voxel_query.iter_mut() {
+        let visible = match filter {
+            (false, _, _, _) => true,
+            (true, _, layer, true) => voxel.y == layer,
+            (true, min, max, false) => (min..=max).contains(&voxel.y),
+        };
+        let wanted = if visible { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+}
+
+// Applies the background color and ambient brightness chosen in the UI
+fn update_environment(
+    user_input: Res<UserInput>,
+    mut clear_color: ResMut<ClearColor>,
+    mut ambient_light: ResMut<AmbientLight>,
+) {
+    let [r, g, b] = user_input.background_color;
+    let background = Color::rgb_u8(r, g, b);
+    if clear_color.0 != background {
+        clear_color.0 = background;
+    }
+    if ambient_light.brightness != user_input.ambient_brightness {
+        ambient_light.brightness = user_input.ambient_brightness;
+    }
 }
 
+// Pushes the "Lighting & Material" settings into the sun light and the voxel material,
+// only when they differ from what was applied last
+fn update_lighting_and_material(
+    user_input: Res<UserInput>,
+    sun: Res<SunLight>,
+    voxel_material: Res<VoxelMaterial>,
+    mut lights: Query<(&mut DirectionalLight, &mut Transform)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut applied: Local<Option<LightingSettings>>,
+) {
+    let settings = user_input.lighting;
+    if *applied == Some(settings) {
+        return;
+    }
+    *applied = Some(settings);
 
+    if let Ok((mut light, mut transform)) = lights.get_mut(sun.0) {
+        light.illuminance = settings.illuminance;
+        light.shadows_enabled = settings.shadows;
+        let (yaw, pitch) = (settings.light_yaw.to_radians(), settings.light_pitch.to_radians());
+        let direction = Vec3::new(pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos());
+        *transform = Transform::from_translation(direction * 25.0).looking_at(Vec3::ZERO, Vec3::Y);
+    }
+
+    if let Some(material) = materials.get_mut(&voxel_material.0) {
+        settings.apply_to_material(material, settings.base_color);
+    }
+}
+
+// Gradient and material settings the layer materials were last built for
+type LayerMaterialKey = (Option<HeightGradient>, (f32, f32, f32, [u8; 3], f32));
+
+// Gives painted voxels the material of their palette color. The others get the material of
+// their layer while the height gradient is on, and the shared voxel material otherwise.
+// One material per palette color and per (grid height, layer) is cached and updated in place.
+#[allow(clippy::too_many_arguments)]
+fn update_voxel_colors(
+    user_input: Res<UserInput>,
+    scene: Res<SceneData>,
+    spawned: Res<SpawnedVoxels>,
+    morph: Res<MorphAnimation>,
+    voxel_material: Res<VoxelMaterial>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut voxel_query: Query<(&Voxel, &mut Handle<StandardMaterial>)>,
+    mut layer_materials: Local<HashMap<(u32, i32), Handle<StandardMaterial>>>,
+    mut palette_materials: Local<Vec<Handle<StandardMaterial>>>,
+    mut applied: Local<Option<LayerMaterialKey>>,
+) {
+    let gradient = user_input.height_gradient.then_some(user_input.gradient);
+    let key = (gradient, user_input.lighting.material_key());
+    let settings_changed = *applied != Some(key);
+    if !settings_changed && !spawned.is_changed() && !morph.is_changed() {
+        return;
+    }
+    *applied = Some(key);
 
+    if settings_changed {
+        for (index, &color) in PALETTE.iter().enumerate() {
+            let mut material = StandardMaterial::default();
+            user_input.lighting.apply_to_material(&mut material, color);
+            match palette_materials.get(index) {
+                Some(handle) => {
+                    materials.insert(handle.id(), material);
+                }
+                None => palette_materials.push(materials.add(material)),
+            }
+        }
+    }
+
+    if let Some(gradient) = gradient {
+        let heights: HashSet<u32> = scene.objects.iter().map(|object| object.data.shape.height).collect();
+        for height in heights {
+            for y in 0..height as i32 {
+                if !settings_changed && layer_materials.contains_key(&(height, y)) {
+                    continue;
+                }
+                let mut material = StandardMaterial::default();
+                user_input.lighting.apply_to_material(&mut material, gradient.color_at(y, height));
+                match layer_materials.get(&(height, y)) {
+                    Some(handle) => {
+                        materials.insert(handle.id(), material);
+                    }
+                    None => {
+                        layer_materials.insert((height, y), materials.add(material));
+                    }
+                }
+            }
+        }
+    }
+
+    for (voxel, mut handle) in voxel_query.iter_mut() {
+        let wanted = match gradient {
+            _ if voxel.color != UNPAINTED => palette_materials
+                .get(voxel.color as usize)
+                .unwrap_or(&voxel_material.0),
+            Some(_) => layer_materials
+                .get(&(voxel.grid_height, voxel.y))
+                .unwrap_or(&voxel_material.0),
+            None => &voxel_material.0,
+        };
+        if *handle != *wanted {
+            *handle = wanted.clone();
+        }
+    }
+}
+
+// Adds or removes the Wireframe component on every voxel when the toggle changes, and
+// gives newly spawned voxels one while it is on
+fn update_wireframe(
+    mut commands: Commands,
+    user_input: Res<UserInput>,
+    voxel_query: Query<(Entity, Has<Wireframe>), With<Voxel>>,
+    added_query: Query<Entity, Added<Voxel>>,
+    mut applied: Local<Option<bool>>,
+) {
+    let wireframe = user_input.wireframe;
+    if *applied != Some(wireframe) {
+        *applied = Some(wireframe);
+        for (entity, has_wireframe) in voxel_query.iter() {
+            if wireframe && !has_wireframe {
+                commands.entity(entity).insert(Wireframe);
+            } else if !wireframe && has_wireframe {
+                commands.entity(entity).remove::<Wireframe>();
+            }
+        }
+    } else if wireframe {
+        for entity in added_query.iter() {
+            commands.entity(entity).insert(Wireframe);
+        }
+    }
+}
+
+// Outlines the visible voxels that have at least one empty neighbor; hidden inner voxels
+// would only cost time. Gizmos are immediate mode, so this redraws every frame.
+fn draw_voxel_edges(
+    user_input: Res<UserInput>,
+    spawned: Res<SpawnedVoxels>,
+    voxel_query: Query<(&Transform, &Visibility), With<Voxel>>,
+    mut gizmos: Gizmos,
+) {
+    if !user_input.show_edges {
+        return;
+    }
+    for (&(object, (x, y, z)), (entity, _)) in &spawned.entities {
+        let on_surface = NEIGHBOR_OFFSETS.iter().any(|&(dx, dy, dz)| {
+            !spawned.entities.contains_key(&(object, (x + dx, y + dy, z + dz)))
+        });
+        if !on_surface {
+            continue;
+        }
+        let Ok((transform, visibility)) = voxel_query.get(*entity) else {
+            continue;
+        };
+        if *visibility != Visibility::Hidden {
+            let outline = transform.with_scale(transform.scale * user_input.voxel_size);
+            gizmos.cuboid(outline, Color::BLACK);
+        }
+    }
+}
+
+// Resizes the shared voxel mesh when the voxel size slider moves, without respawning voxels
+fn update_voxel_size(
+    user_input: Res<UserInput>,
+    voxel_mesh: Res<VoxelMesh>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut last_voxel_size: Local<f32>,
+) {
+    if *last_voxel_size == user_input.voxel_size {
+        return;
+    }
+    *last_voxel_size = user_input.voxel_size;
+
+    let size = user_input.voxel_size;
+    meshes.insert(voxel_mesh.0.id(), Mesh::from(Cuboid::new(size, size, size)));
+}
+
+// Reports a finished export, or deletes its files if it was cancelled. Failures show up in
+// the error area of the UI
+fn export_job_system(mut job: ResMut<ExportJob>, mut user_input: ResMut<UserInput>) {
+    let Some(running) = job.bypass_change_detection().running.as_mut() else {
+        return;
+    };
+    let Some(result) = block_on(future::poll_once(&mut running.task)) else {
+        return;
+    };
+    let Some(running) = job.running.take() else {
+        return;
+    };
+    let path = running.path;
+    if running.cancelled {
+        remove_files(&running.files);
+        user_input.status_message = Some(format!("Cancelled export to {}", path));
+        return;
+    }
+    match result {
+        Ok(()) => user_input.status_message = Some(format!("Exported shape to {}", path)),
+        Err(e) => user_input.error_message = Some(format!("Export to {} failed: {}", path, e)),
+    }
+}
+
+// Recomputes the model statistics shown in the UI; only runs when the voxel data changes
+fn update_voxel_stats(
+    voxel_data: Res<VoxelData>,
+    mut stats: ResMut<VoxelStats>,
+    dirty: Res<VoxelDirty>,
+) {
+    if !dirty.0 {
+        return;
+    }
+
+    stats.voxel_count = voxel_data.voxels.len();
+    stats.bounds = voxel_data.voxels.bounding_box();
+    stats.exposed_faces = count_exposed_faces(&voxel_data);
+    stats.greedy_quads = greedy_mesh(&voxel_data, false).len();
+}
 
 fn main() {
+    // Any command line arguments switch to batch mode without opening a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
     App::new()
         .insert_resource(ClearColor(Color::BLACK))
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "3D Shape Sculptor".into(),
-                resolution: (1280.0, 720.0).into(),
-                present_mode: PresentMode::AutoVsync,
-                window_theme: Some(WindowTheme::Dark),
-                ..default()
-            }),
-            ..default()
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "3D Shape Sculptor".into(),
+                        resolution: (1280.0, 720.0).into(),
+                        present_mode: PresentMode::AutoVsync,
+                        window_theme: Some(WindowTheme::Dark),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                // Wireframes need line polygon mode
+                .set(RenderPlugin {
+                    render_creation: RenderCreation::Automatic(WgpuSettings {
+                        features: WgpuFeatures::POLYGON_MODE_LINE,
+                        ..default()
+                    }),
+                    ..default()
+                }),
+        )
+        .add_plugins(WireframePlugin)
         .add_plugins(EguiPlugin)
         .add_plugins(PanOrbitCameraPlugin)
         .add_systems(Startup, setup)
-        .add_systems(Update, ui_system)
-        .add_systems(Update, generate_shape_system)
+        .add_systems(Update, (ui_system, screenshot_toast_system))
+        .add_event::<ResetCamera>()
+        .add_systems(Update, handle_keyboard_input)
+        .add_systems(Update, reset_camera_system.after(handle_keyboard_input).after(ui_system))
+        .add_systems(Update, turntable_system)
+        .add_systems(Update, update_projection.after(ui_system))
+        .add_systems(Update, view_snap_system.after(ui_system).after(turntable_system))
+        .add_systems(Update, fly_camera_system)
+        .add_systems(Update, update_voxel_size)
+        .add_systems(Update, update_wireframe.after(update_voxels))
+        .add_systems(Update, draw_voxel_edges.after(update_layer_visibility))
+        .add_systems(Update, update_environment)
+        .add_systems(Update, update_lighting_and_material)
+        .add_systems(Update, screenshot_system.after(ui_system).after(handle_keyboard_input))
+        .add_systems(Update, export_job_system)
+        .add_systems(Update, sculpt_system.after(ui_system).before(generate_shape_system))
+        .add_systems(Update, selection_keys_system.after(ui_system).before(generate_shape_system))
+        .add_systems(Update, undo_keys_system.after(ui_system).before(generate_shape_system))
+        .add_systems(Update, update_selection_overlay.after(sculpt_system))
+        .add_systems(
+            Update,
+            (
+                generate_shape_system,
+                sync_selected_object,
+                record_history,
+                update_voxel_stats,
+                update_voxels,
+                update_layer_visibility,
+                update_voxel_colors,
+            )
+                .chain()
+                // Edits made in the UI are picked up before the flag is cleared
+                .after(ui_system),
+        )
         .run();
-}
\ No newline at end of file
+}
+
+// The systems here are private to the editor, so they are tested in place rather than in tests/
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.insert_resource(UserInput {
+            needs_regeneration: false,
+            ..default()
+        });
+        world.insert_resource(VoxelData::default());
+        world.insert_resource(MorphAnimation::default());
+        world.insert_resource(VoxelStats::default());
+        world.insert_resource(VoxelDirty(false));
+        world
+    }
+
+    #[test]
+    fn borrowing_voxel_data_does_not_mark_it_dirty() {
+        let mut world = world();
+        // A mutable borrow that keeps every voxel still trips Bevy's change detection
+        world.run_system_once(|mut voxel_data: ResMut<VoxelData>| {
+            voxel_data.voxels.retain(|_| true);
+        });
+        assert!(world.is_resource_changed::<VoxelData>());
+        world.run_system_once(update_voxel_stats);
+        assert!(!world.resource::<VoxelDirty>().0);
+        assert_eq!(world.resource::<VoxelStats>().voxel_count, 0);
+    }
+
+    #[test]
+    fn generating_marks_voxel_data_dirty() {
+        let mut world = world();
+        world.resource_mut::<UserInput>().needs_regeneration = true;
+        world.run_system_once(generate_shape_system);
+        assert!(world.resource::<VoxelDirty>().0);
+        assert!(!world.resource::<VoxelData>().voxels.is_empty());
+
+        world.run_system_once(update_voxel_stats);
+        let count = world.resource::<VoxelData>().voxels.len();
+        assert_eq!(world.resource::<VoxelStats>().voxel_count, count);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::shapes::{VoxelCoord, VoxelGrid};
+use bevy::prelude::Resource;
+use std::f32::consts::PI;
+
+// How far voxels present in both keyframes rise at the middle of the morph
+const BREATHING_HEIGHT: f32 = 0.15;
+
+// Morph between two keyframes of the selected object: voxels only in `from` shrink away,
+// voxels only in `to` grow in, shared voxels stay and bob up and down once
+#[derive(Resource, Debug, Clone)]
+pub struct MorphAnimation {
+    pub from: VoxelGrid,
+    pub to: VoxelGrid,
+    // Progress from 0 (all `from`) to 1 (all `to`)
+    pub t: f32,
+    pub playing: bool,
+    // Progress per second
+    pub speed: f32,
+}
+
+impl Default for MorphAnimation {
+    fn default() -> Self {
+        Self {
+            from: VoxelGrid::new(),
+            to: VoxelGrid::new(),
+            t: 0.0,
+            playing: false,
+            speed: 0.5,
+        }
+    }
+}
+
+// One voxel of a morph frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MorphVoxel {
+    pub coord: VoxelCoord,
+    pub scale: f32,
+    pub lift: f32,
+}
+
+impl MorphAnimation {
+    // Starts playing from the beginning; does nothing unless both keyframes are set
+    pub fn play(&mut self) {
+        if !self.from.is_empty() && !self.to.is_empty() {
+            self.t = 0.0;
+            self.playing = true;
+        }
+    }
+
+    // Stops and goes back to showing the model itself
+    pub fn stop(&mut self) {
+        self.t = 0.0;
+        self.playing = false;
+    }
+
+    // Advances by `delta` seconds, stopping on the last frame
+    pub fn step(&mut self, delta: f32) {
+        if !self.playing {
+            return;
+        }
+        self.t = (self.t + self.speed * delta).min(1.0);
+        if self.t >= 1.0 {
+            self.playing = false;
+        }
+    }
+
+    // The morph is shown instead of the model while playing and after it finished,
+    // until stopped
+    pub fn is_active(&self) -> bool {
+        self.playing || self.t > 0.0
+    }
+
+    // Every voxel of the union of both keyframes with its scale and vertical offset at `t`
+    pub fn frame(&self) -> Vec<MorphVoxel> {
+        let lift = BREATHING_HEIGHT * (self.t * PI).sin();
+
+        let mut frame: Vec<MorphVoxel> = self
+            .from
+            .iter()
+            .map(|&coord| {
+                if self.to.contains(&coord) {
+                    MorphVoxel {
+                        coord,
+                        scale: 1.0,
+                        lift,
+                    }
+                } else {
+                    MorphVoxel {
+                        coord,
+                        scale: 1.0 - self.t,
+                        lift: 0.0,
+                    }
+                }
+            })
+            .collect();
+        frame.extend(
+            self.to
+                .iter()
+                .filter(|coord| !self.from.contains(coord))
+                .map(|&coord| MorphVoxel {
+                    coord,
+                    scale: self.t,
+                    lift: 0.0,
+                }),
+        );
+        frame
+    }
+}
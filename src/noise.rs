@@ -0,0 +1,93 @@
+// Seeded value noise. Everything is derived from the seed alone, so the same seed
+// always produces the same field on every platform.
+
+const TABLE_SIZE: usize = 256;
+
+// One SplitMix64 step: advances `state` and returns the next pseudo-random value
+pub fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// 3D value noise: random values on an integer lattice, smoothly interpolated in between
+pub struct ValueNoise {
+    permutation: [u8; TABLE_SIZE],
+    values: [f32; TABLE_SIZE],
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64) -> Self {
+        let mut state = seed;
+
+        let mut permutation = [0u8; TABLE_SIZE];
+        for (i, slot) in permutation.iter_mut().enumerate() {
+            *slot = i as u8;
+        }
+        // Fisher-Yates shuffle
+        for i in (1..TABLE_SIZE).rev() {
+            let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+            permutation.swap(i, j);
+        }
+
+        let mut values = [0.0; TABLE_SIZE];
+        for value in values.iter_mut() {
+            // Top 24 bits give an evenly spread value in [0, 1)
+            *value = (splitmix64(&mut state) >> 40) as f32 / (1u64 << 24) as f32;
+        }
+
+        Self { permutation, values }
+    }
+
+    // Random value assigned to a lattice point
+    fn lattice(&self, x: i32, y: i32, z: i32) -> f32 {
+        let hash = |index: usize, coord: i32| {
+            self.permutation[(index + (coord & 0xFF) as usize) & 0xFF] as usize
+        };
+        let index = hash(hash(hash(0, x), y), z);
+        self.values[index]
+    }
+
+    // Noise value in [0, 1] at the given point
+    pub fn sample(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+
+        // Smoothstep weights hide the lattice grid
+        let fade = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, ty, tz) = (fade(x - x0), fade(y - y0), fade(z - z0));
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        let corner = |dx: i32, dy: i32, dz: i32| self.lattice(ix + dx, iy + dy, iz + dz);
+        let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), tx);
+        let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), tx);
+        let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), tx);
+        let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), tx);
+        let y0 = lerp(x00, x10, ty);
+        let y1 = lerp(x01, x11, ty);
+        lerp(y0, y1, tz)
+    }
+
+    // Sum of `octaves` layers at doubling frequency and halving weight, still in [0, 1]
+    pub fn fractal(&self, x: f32, y: f32, z: f32, octaves: u32) -> f32 {
+        let mut total = 0.0;
+        let mut weight = 1.0;
+        let mut weight_sum = 0.0;
+        let mut scale = 1.0;
+        for octave in 0..octaves {
+            // Shift each octave so their lattices don't line up
+            let shift = octave as f32 * 17.31;
+            total += self.sample(x * scale + shift, y * scale + shift, z * scale + shift) * weight;
+            weight_sum += weight;
+            weight *= 0.5;
+            scale *= 2.0;
+        }
+        if weight_sum > 0.0 {
+            total / weight_sum
+        } else {
+            0.0
+        }
+    }
+}
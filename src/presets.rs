@@ -0,0 +1,124 @@
+use crate::shapes::{GeometricShape, Shape};
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
+
+// Saved next to the executable so the presets follow the program, not the working directory
+pub const PRESETS_FILE: &str = "presets.json";
+
+// Side length of the presets written on first run
+const DEFAULT_PRESET_SIZE: u32 = 10;
+
+// A named set of shape parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub shape: Shape,
+    pub hollow: bool,
+}
+
+// One preset per shape at 10x10x10
+pub fn default_presets() -> Vec<Preset> {
+    GeometricShape::iter()
+        .map(|kind| Preset {
+            name: kind.to_string(),
+            shape: Shape {
+                kind,
+                width: DEFAULT_PRESET_SIZE,
+                height: DEFAULT_PRESET_SIZE,
+                depth: DEFAULT_PRESET_SIZE,
+                ..Shape::default()
+            },
+            hollow: false,
+        })
+        .collect()
+}
+
+pub fn presets_to_json(presets: &[Preset]) -> String {
+    // Serializing plain data to a string can't fail
+    serde_json::to_string_pretty(presets).unwrap_or_default()
+}
+
+pub fn presets_from_json(text: &str) -> Result<Vec<Preset>, String> {
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
+
+// The presets shown in the UI and the file they are kept in
+#[derive(Resource, Debug, Clone)]
+pub struct PresetLibrary {
+    pub presets: Vec<Preset>,
+    path: PathBuf,
+}
+
+impl PresetLibrary {
+    // Reads the presets file, creating it with the defaults on first run. An unreadable
+    // or corrupt file is left alone and the defaults are used instead.
+    pub fn load(path: PathBuf) -> Self {
+        let presets = match std::fs::read_to_string(&path) {
+            Ok(text) => presets_from_json(&text).unwrap_or_else(|e| {
+                eprintln!(
+                    "Warning: {} is not a valid presets file: {}",
+                    path.display(),
+                    e
+                );
+                default_presets()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let library = Self {
+                    presets: default_presets(),
+                    path,
+                };
+                if let Err(e) = library.write() {
+                    eprintln!("Warning: cannot create {}: {}", library.path.display(), e);
+                }
+                return library;
+            }
+            Err(e) => {
+                eprintln!("Warning: cannot read {}: {}", path.display(), e);
+                default_presets()
+            }
+        };
+        Self { presets, path }
+    }
+
+    // presets.json in the executable's folder, or in the working directory if that is unknown
+    pub fn default_path() -> PathBuf {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(PRESETS_FILE)))
+            .unwrap_or_else(|| Path::new(PRESETS_FILE).to_path_buf())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Preset> {
+        self.presets.iter().find(|preset| preset.name == name)
+    }
+
+    // Adds the preset, replacing one with the same name, and writes the file
+    pub fn save(&mut self, preset: Preset) -> io::Result<()> {
+        match self
+            .presets
+            .iter_mut()
+            .find(|existing| existing.name == preset.name)
+        {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+        self.write()
+    }
+
+    // Removes the named preset and writes the file
+    pub fn remove(&mut self, name: &str) -> io::Result<()> {
+        self.presets.retain(|preset| preset.name != name);
+        self.write()
+    }
+
+    fn write(&self) -> io::Result<()> {
+        std::fs::write(&self.path, presets_to_json(&self.presets))
+    }
+}
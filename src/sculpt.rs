@@ -0,0 +1,602 @@
+use crate::shapes::{
+    connected_region, translate_voxels, Shape, VoxelCoord, VoxelGrid, NEIGHBOR_OFFSETS, UNPAINTED,
+};
+use bevy::prelude::*;
+use std::collections::{HashSet, VecDeque};
+use strum_macros::{Display, EnumIter};
+
+pub const MAX_BRUSH_RADIUS: u32 = 8;
+pub const MAX_EXTRUDE_DISTANCE: u32 = 16;
+
+// Outline of the cells a brush dab covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumIter)]
+pub enum BrushShape {
+    #[default]
+    Sphere,
+    Cube,
+}
+
+// What a move does with voxels that would leave the grid or land on other voxels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumIter)]
+pub enum MoveMode {
+    // Voxels moved out of the grid are dropped, and moved voxels replace the ones they land on
+    #[default]
+    Clip,
+    // The box stays put and voxels pushed out of one side come back in on the other
+    Wrap,
+    // Moves only into empty cells inside the grid, otherwise nothing moves
+    Push,
+}
+
+// Where a ray from the cursor meets the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelHit {
+    // The voxel that was hit. A ray that reaches the bottom of the grid without hitting
+    // anything lands on the floor, a cell of the layer just below the grid (y = -1)
+    pub voxel: VoxelCoord,
+    // Unit offset from `voxel` to the cell in front of the face that was hit
+    pub normal: VoxelCoord,
+}
+
+impl VoxelHit {
+    // The cell in front of the hit face, where a new voxel goes
+    pub fn adjacent(&self) -> VoxelCoord {
+        let (x, y, z) = self.voxel;
+        let (dx, dy, dz) = self.normal;
+        (x + dx, y + dy, z + dz)
+    }
+
+    pub fn on_floor(&self) -> bool {
+        self.voxel.1 < 0
+    }
+}
+
+// Box of grid cells of the selected object, picked with two clicks of the Select tool.
+// Editing operations work on the cells inside it
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    // First corner, clicked and waiting for the second one
+    pub anchor: Option<VoxelCoord>,
+    // Smallest and largest cell of the box, both inside it
+    pub bounds: Option<(VoxelCoord, VoxelCoord)>,
+}
+
+impl Selection {
+    // The box spanning the cells `a` and `b`, given in any order
+    pub fn from_corners(a: VoxelCoord, b: VoxelCoord) -> Self {
+        Self {
+            anchor: None,
+            bounds: Some((
+                (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+                (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+            )),
+        }
+    }
+
+    // Every cell of `shape`'s grid
+    pub fn all(shape: &Shape) -> Self {
+        let max = (
+            shape.width as i32 - 1,
+            shape.height as i32 - 1,
+            shape.depth as i32 - 1,
+        );
+        Self::from_corners((0, 0, 0), max)
+    }
+
+    // A first click starts a new box at `cell`, a second one completes it
+    pub fn click(&mut self, cell: VoxelCoord) {
+        *self = match self.anchor {
+            Some(anchor) => Self::from_corners(anchor, cell),
+            None => Self {
+                anchor: Some(cell),
+                ..Self::from_corners(cell, cell)
+            },
+        };
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn contains(&self, cell: &VoxelCoord) -> bool {
+        self.bounds.is_some_and(|(min, max)| {
+            (min.0..=max.0).contains(&cell.0)
+                && (min.1..=max.1).contains(&cell.1)
+                && (min.2..=max.2).contains(&cell.2)
+        })
+    }
+
+    // Number of cells along each axis, (0, 0, 0) when nothing is selected
+    pub fn size(&self) -> (u32, u32, u32) {
+        self.bounds.map_or((0, 0, 0), |(min, max)| {
+            (
+                (max.0 - min.0 + 1) as u32,
+                (max.1 - min.1 + 1) as u32,
+                (max.2 - min.2 + 1) as u32,
+            )
+        })
+    }
+
+    // Every cell in the box
+    pub fn cells(&self) -> Vec<VoxelCoord> {
+        let Some((min, max)) = self.bounds else {
+            return Vec::new();
+        };
+        let mut cells = Vec::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    cells.push((x, y, z));
+                }
+            }
+        }
+        cells
+    }
+
+    // The voxels inside the box, with their colors
+    pub fn selected_voxels(&self, voxels: &VoxelGrid) -> VoxelGrid {
+        voxels.filtered(|voxel| self.contains(voxel))
+    }
+
+    // Removes the voxels inside the box and returns how many went
+    pub fn delete(&self, voxels: &mut VoxelGrid) -> usize {
+        let before = voxels.len();
+        voxels.retain(|voxel| !self.contains(voxel));
+        before - voxels.len()
+    }
+
+    // Fills the empty cells of the box inside `shape`'s grid with palette color `color`
+    // and returns how many were added
+    pub fn fill(&self, voxels: &mut VoxelGrid, shape: &Shape, color: u8) -> usize {
+        let cells = self
+            .cells()
+            .into_iter()
+            .filter(|&cell| in_grid(cell, shape));
+        cells
+            .filter(|&cell| voxels.insert_colored(cell, color))
+            .count()
+    }
+
+    // The voxels inside the box, placed relative to its smallest corner
+    pub fn copy(&self, voxels: &VoxelGrid) -> Clipboard {
+        let Some((min, _)) = self.bounds else {
+            return Clipboard::default();
+        };
+        Clipboard {
+            voxels: translate_voxels(&self.selected_voxels(voxels), -min.0, -min.1, -min.2),
+            size: self.size(),
+        }
+    }
+
+    // Pastes a copy of the box `offset` cells away, selects the copy and returns how many
+    // voxels landed in the grid
+    pub fn duplicate(
+        &mut self,
+        voxels: &mut VoxelGrid,
+        shape: &Shape,
+        offset: VoxelCoord,
+    ) -> usize {
+        let Some((min, _)) = self.bounds else {
+            return 0;
+        };
+        let clipboard = self.copy(voxels);
+        let at = (min.0 + offset.0, min.1 + offset.1, min.2 + offset.2);
+        *self = clipboard.selection_at(at);
+        clipboard.paste(voxels, shape, at)
+    }
+
+    // Moves the voxels inside the box by `delta`, or the whole model when nothing is
+    // selected, and returns whether any voxel changed. Unless wrapping, the box goes along
+    // and is cut down to the part still inside the grid
+    pub fn nudge(
+        &mut self,
+        voxels: &mut VoxelGrid,
+        shape: &Shape,
+        delta: VoxelCoord,
+        mode: MoveMode,
+    ) -> bool {
+        let moving = match self.bounds {
+            Some(_) => self.clone(),
+            None => Self::all(shape),
+        };
+        let Some((min, max)) = moving.bounds else {
+            return false;
+        };
+        let step = |(x, y, z): VoxelCoord| (x + delta.0, y + delta.1, z + delta.2);
+        let selected = moving.selected_voxels(voxels);
+        let moved = match mode {
+            MoveMode::Wrap => {
+                let (w, h, d) = moving.size();
+                selected.map_coords(|cell| {
+                    let (x, y, z) = step(cell);
+                    (
+                        min.0 + (x - min.0).rem_euclid(w as i32),
+                        min.1 + (y - min.1).rem_euclid(h as i32),
+                        min.2 + (z - min.2).rem_euclid(d as i32),
+                    )
+                })
+            }
+            MoveMode::Clip | MoveMode::Push => selected.map_coords(step),
+        };
+        // Cells inside the box are free to land on, since everything there moves away
+        let blocked = |cell: &VoxelCoord| {
+            !in_grid(*cell, shape) || (voxels.contains(cell) && !moving.contains(cell))
+        };
+        if mode == MoveMode::Push && moved.iter().any(blocked) {
+            return false;
+        }
+
+        let changed = moved != selected;
+        moving.delete(voxels);
+        for (cell, color) in moved.colored().filter(|&(cell, _)| in_grid(cell, shape)) {
+            voxels.remove(&cell);
+            voxels.insert_colored(cell, color);
+        }
+        if self.bounds.is_some() && mode != MoveMode::Wrap {
+            self.bounds = clip_to_grid(step(min), step(max), shape);
+        }
+        changed
+    }
+
+    // Pulls the side of the box facing `normal` out by `distance` layers, repeating the
+    // selected voxels on that side, and grows the box to match. Returns how many voxels
+    // were added
+    pub fn extrude(
+        &mut self,
+        voxels: &mut VoxelGrid,
+        shape: &Shape,
+        normal: VoxelCoord,
+        distance: u32,
+    ) -> usize {
+        let Some((min, max)) = self.bounds else {
+            return 0;
+        };
+        let on_side = |value: i32, n: i32, min: i32, max: i32| match n.signum() {
+            1 => value == max,
+            -1 => value == min,
+            _ => true,
+        };
+        let faces: Vec<VoxelCoord> = self
+            .selected_voxels(voxels)
+            .iter()
+            .copied()
+            .filter(|&(x, y, z)| {
+                on_side(x, normal.0, min.0, max.0)
+                    && on_side(y, normal.1, min.1, max.1)
+                    && on_side(z, normal.2, min.2, max.2)
+            })
+            .collect();
+        let grown = extrusion(voxels, &faces, normal, distance);
+        let added = grown
+            .colored()
+            .filter(|&(cell, color)| in_grid(cell, shape) && voxels.insert_colored(cell, color))
+            .count();
+
+        let d = distance as i32;
+        let min = (
+            min.0 + normal.0.min(0) * d,
+            min.1 + normal.1.min(0) * d,
+            min.2 + normal.2.min(0) * d,
+        );
+        let max = (
+            max.0 + normal.0.max(0) * d,
+            max.1 + normal.1.max(0) * d,
+            max.2 + normal.2.max(0) * d,
+        );
+        self.bounds = clip_to_grid(min, max, shape);
+        added
+    }
+}
+
+// The part of the box `min..=max` inside `shape`'s grid, if any
+fn clip_to_grid(
+    min: VoxelCoord,
+    max: VoxelCoord,
+    shape: &Shape,
+) -> Option<(VoxelCoord, VoxelCoord)> {
+    let min = (min.0.max(0), min.1.max(0), min.2.max(0));
+    let max = (
+        max.0.min(shape.width as i32 - 1),
+        max.1.min(shape.height as i32 - 1),
+        max.2.min(shape.depth as i32 - 1),
+    );
+    let inside = min.0 <= max.0 && min.1 <= max.1 && min.2 <= max.2;
+    inside.then_some((min, max))
+}
+
+// Voxels copied out of a selection, relative to the box's smallest corner
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Clipboard {
+    pub voxels: VoxelGrid,
+    // Size of the box they were copied from, (0, 0, 0) when nothing was copied
+    pub size: (u32, u32, u32),
+}
+
+impl Clipboard {
+    pub fn is_empty(&self) -> bool {
+        self.size == (0, 0, 0)
+    }
+
+    // The box a paste at `at` covers
+    pub fn selection_at(&self, at: VoxelCoord) -> Selection {
+        let (w, h, d) = self.size;
+        let far = (
+            at.0 + w as i32 - 1,
+            at.1 + h as i32 - 1,
+            at.2 + d as i32 - 1,
+        );
+        Selection::from_corners(at, far)
+    }
+
+    // Puts the copied voxels into `shape`'s grid with the box's smallest corner at `at`,
+    // replacing the voxels they land on, and returns how many landed in the grid
+    pub fn paste(&self, voxels: &mut VoxelGrid, shape: &Shape, at: VoxelCoord) -> usize {
+        let pasted = translate_voxels(&self.voxels, at.0, at.1, at.2);
+        let mut count = 0;
+        for (cell, color) in pasted.colored().filter(|&(cell, _)| in_grid(cell, shape)) {
+            voxels.remove(&cell);
+            voxels.insert_colored(cell, color);
+            count += 1;
+        }
+        count
+    }
+}
+
+// Fills the empty cells 6-connected to `start` within `min..=max` with palette color `color`
+// and returns how many were added; none when `start` is occupied or outside the bounds
+pub fn fill_empty_region(
+    voxels: &mut VoxelGrid,
+    start: VoxelCoord,
+    (min, max): (VoxelCoord, VoxelCoord),
+    color: u8,
+) -> usize {
+    let region = connected_region(voxels, start, false, min, max);
+    let added = region.len();
+    voxels.extend(region.into_iter().map(|cell| (cell, color)));
+    added
+}
+
+// Gives palette color `color` to the voxels 6-connected to `start` that share its color and
+// returns how many changed
+pub fn recolor_region(voxels: &mut VoxelGrid, start: VoxelCoord, color: u8) -> usize {
+    let Some(from) = voxels.color(&start) else {
+        return 0;
+    };
+    if from == color {
+        return 0;
+    }
+    let mut region = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some((x, y, z)) = queue.pop_front() {
+        for &(dx, dy, dz) in &NEIGHBOR_OFFSETS {
+            let next = (x + dx, y + dy, z + dz);
+            if voxels.color(&next) == Some(from) && region.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    for cell in &region {
+        voxels.paint(cell, color);
+    }
+    region.len()
+}
+
+// Cells of a 3D Bresenham line from `a` to `b`, both included: one cell per step along the
+// axis with the largest distance, so the line is 6-connected on that axis only
+pub fn line_cells(a: VoxelCoord, b: VoxelCoord) -> Vec<VoxelCoord> {
+    let (from, to) = ([a.0, a.1, a.2], [b.0, b.1, b.2]);
+    let delta = [0, 1, 2].map(|axis| (to[axis] - from[axis]).abs());
+    let step = [0, 1, 2].map(|axis| (to[axis] - from[axis]).signum());
+    let main = if delta[0] >= delta[1] && delta[0] >= delta[2] {
+        0
+    } else if delta[1] >= delta[2] {
+        1
+    } else {
+        2
+    };
+
+    let mut cell = from;
+    // Error terms of the two minor axes
+    let mut error = [0, 1, 2].map(|axis| 2 * delta[axis] - delta[main]);
+    let mut cells = vec![a];
+    for _ in 0..delta[main] {
+        cell[main] += step[main];
+        for axis in (0..3).filter(|&axis| axis != main) {
+            if error[axis] >= 0 {
+                cell[axis] += step[axis];
+                error[axis] -= 2 * delta[main];
+            }
+            error[axis] += 2 * delta[axis];
+        }
+        cells.push((cell[0], cell[1], cell[2]));
+    }
+    cells
+}
+
+// Cells of the axis-aligned rectangle with corners `a` and `b`. It lies across the axis on
+// which the corners are closest, at `a`'s position on that axis
+pub fn plane_cells(a: VoxelCoord, b: VoxelCoord) -> Vec<VoxelCoord> {
+    let delta = [(b.0 - a.0).abs(), (b.1 - a.1).abs(), (b.2 - a.2).abs()];
+    let flat = if delta[1] <= delta[0] && delta[1] <= delta[2] {
+        1
+    } else if delta[0] <= delta[2] {
+        0
+    } else {
+        2
+    };
+    let b = match flat {
+        0 => (a.0, b.1, b.2),
+        1 => (b.0, a.1, b.2),
+        _ => (b.0, b.1, a.2),
+    };
+    Selection::from_corners(a, b).cells()
+}
+
+// The voxels whose face toward `normal` is exposed and lies in one flat patch with that face
+// of `start`, joined through shared edges. Empty when `start` has no exposed face there
+pub fn face_region(voxels: &VoxelGrid, start: VoxelCoord, normal: VoxelCoord) -> Vec<VoxelCoord> {
+    let step = |(x, y, z): VoxelCoord, (dx, dy, dz): VoxelCoord| (x + dx, y + dy, z + dz);
+    let exposed =
+        |cell: VoxelCoord| voxels.contains(&cell) && !voxels.contains(&step(cell, normal));
+    if !exposed(start) {
+        return Vec::new();
+    }
+    let mut region = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(cell) = queue.pop_front() {
+        // Neighbors within the plane of the face
+        let sideways = NEIGHBOR_OFFSETS
+            .iter()
+            .filter(|offset| offset.0 * normal.0 + offset.1 * normal.1 + offset.2 * normal.2 == 0);
+        for &offset in sideways {
+            let next = step(cell, offset);
+            if exposed(next) && region.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    let mut cells: Vec<VoxelCoord> = region.into_iter().collect();
+    cells.sort_unstable();
+    cells
+}
+
+// The cells of `distance` layers in front of the `faces` voxels along `normal`, each with the
+// color of the voxel it grows from
+pub fn extrusion(
+    voxels: &VoxelGrid,
+    faces: &[VoxelCoord],
+    normal: VoxelCoord,
+    distance: u32,
+) -> VoxelGrid {
+    let mut grown = VoxelGrid::new();
+    for &(x, y, z) in faces {
+        let color = voxels.color(&(x, y, z)).unwrap_or(UNPAINTED);
+        for layer in 1..=distance as i32 {
+            let cell = (
+                x + normal.0 * layer,
+                y + normal.1 * layer,
+                z + normal.2 * layer,
+            );
+            grown.insert_colored(cell, color);
+        }
+    }
+    grown
+}
+
+// Whether `voxel` lies inside `shape`'s grid
+pub fn in_grid(voxel: VoxelCoord, shape: &Shape) -> bool {
+    let (x, y, z) = voxel;
+    (0..shape.width as i32).contains(&x)
+        && (0..shape.height as i32).contains(&y)
+        && (0..shape.depth as i32).contains(&z)
+}
+
+// Cells a brush of `radius` covers around `center`: a radius of 1 is the center alone and
+// each step adds a layer. Sphere brushes take the cells whose centers are within
+// radius - 0.5 of `center`
+pub fn brush_cells(center: VoxelCoord, radius: u32, shape: BrushShape) -> Vec<VoxelCoord> {
+    let reach = radius.max(1) as i32 - 1;
+    let limit = (radius.max(1) as f32 - 0.5).powi(2);
+    let mut cells = Vec::new();
+    for dx in -reach..=reach {
+        for dy in -reach..=reach {
+            for dz in -reach..=reach {
+                let inside = match shape {
+                    BrushShape::Sphere => (dx * dx + dy * dy + dz * dz) as f32 <= limit,
+                    BrushShape::Cube => true,
+                };
+                if inside {
+                    cells.push((center.0 + dx, center.1 + dy, center.2 + dz));
+                }
+            }
+        }
+    }
+    cells
+}
+
+// First voxel of `voxels` the ray from `origin` along `direction` runs into, stepping
+// through the grid of `shape` cell by cell. Positions are in grid units with the center of
+// voxel (x, y, z) at (x, y, z). None when the ray misses both the voxels and the floor
+pub fn pick_voxel(
+    voxels: &VoxelGrid,
+    shape: &Shape,
+    origin: Vec3,
+    direction: Vec3,
+) -> Option<VoxelHit> {
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+    // Cell (x, y, z) spans x..x + 1 and so on from here
+    let start = (origin + Vec3::splat(0.5)).to_array();
+    let direction = direction.to_array();
+    let size = [shape.width as f32, shape.height as f32, shape.depth as f32];
+
+    // Where the ray enters and leaves the grid's box, and the axis it enters through
+    let (mut t_enter, mut t_exit, mut entry_axis) = (0.0f32, f32::INFINITY, None);
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            if !(0.0..=size[axis]).contains(&start[axis]) {
+                return None;
+            }
+            continue;
+        }
+        let t0 = -start[axis] / direction[axis];
+        let t1 = (size[axis] - start[axis]) / direction[axis];
+        if t0.min(t1) > t_enter {
+            t_enter = t0.min(t1);
+            entry_axis = Some(axis);
+        }
+        t_exit = t_exit.min(t0.max(t1));
+    }
+    if t_enter > t_exit {
+        return None;
+    }
+
+    let step = direction.map(|d| if d > 0.0 { 1 } else { -1 });
+    let mut cell = [0i32; 3];
+    let mut next = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        let position = start[axis] + direction[axis] * t_enter;
+        cell[axis] = (position.floor() as i32).clamp(0, size[axis] as i32 - 1);
+        if direction[axis] != 0.0 {
+            let boundary = cell[axis] + (step[axis] > 0) as i32;
+            next[axis] = (boundary as f32 - start[axis]) / direction[axis];
+        }
+    }
+    // Facing back along the ray on the axis it came in through; inside the grid already,
+    // the dominant axis of the ray stands in
+    let axis = entry_axis.unwrap_or_else(|| smallest_axis(direction.map(|d| -d.abs())));
+    let mut normal = [0; 3];
+    normal[axis] = -step[axis];
+
+    let as_coord = |c: [i32; 3]| (c[0], c[1], c[2]);
+    loop {
+        if voxels.contains(&as_coord(cell)) {
+            return Some(VoxelHit {
+                voxel: as_coord(cell),
+                normal: as_coord(normal),
+            });
+        }
+        let axis = smallest_axis(next);
+        cell[axis] += step[axis];
+        next[axis] += 1.0 / direction[axis].abs();
+        normal = [0; 3];
+        normal[axis] = -step[axis];
+        if !(0..size[axis] as i32).contains(&cell[axis]) {
+            // Leaving through the bottom lands on the floor
+            let floor = axis == 1 && cell[1] < 0;
+            return floor.then(|| VoxelHit {
+                voxel: as_coord(cell),
+                normal: as_coord(normal),
+            });
+        }
+    }
+}
+
+// Index of the smallest of three values, the first one on a tie
+fn smallest_axis(values: [f32; 3]) -> usize {
+    (0..3)
+        .min_by(|&a, &b| values[a].total_cmp(&values[b]))
+        .unwrap_or(0)
+}
@@ -0,0 +1,1770 @@
+use crate::expr;
+use crate::font::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::noise::{splitmix64, ValueNoise};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{hash_map, HashMap, HashSet, VecDeque};
+use std::f32::consts::TAU;
+use strum_macros::{Display, EnumIter};
+
+// --- Constants ---
+pub const PINK_COLOR_HEX: &str = "AC1754";
+pub const MIN_DIMENSION: u32 = 1;
+// Largest grid side anywhere (imports, files, command line); the UI sliders stop at a
+// user setting that starts at DEFAULT_MAX_DIMENSION
+pub const MAX_DIMENSION: u32 = 128;
+pub const DEFAULT_MAX_DIMENSION: u32 = 32;
+// Largest grid (width * height * depth) that is generated at all; bigger ones are refused
+pub const MAX_GRID_CELLS: u64 = (MAX_DIMENSION as u64).pow(3);
+const NOISE_BLOB_THRESHOLD: f32 = 0.45;
+pub const MIN_PRISM_SIDES: u32 = 3;
+pub const MAX_PRISM_SIDES: u32 = 12;
+pub const MAX_LETTER_SPACING: u32 = 8;
+pub const MAX_HELIX_TURNS: f32 = 16.0;
+pub const MAX_KNOT_WINDINGS: u32 = 12;
+// Fractals stop gaining detail well before this on a 128 grid
+pub const MAX_FRACTAL_ITERATIONS: u32 = 5;
+// Superellipsoid exponents: near 0 is boxy, 1 round, 2 pointed like an octahedron
+pub const MIN_ROUNDNESS: f32 = 0.05;
+pub const MAX_ROUNDNESS: f32 = 3.0;
+pub const MAX_TERRAIN_FREQUENCY: f32 = 16.0;
+pub const MIN_GEAR_TEETH: u32 = 3;
+pub const MAX_GEAR_TEETH: u32 = 48;
+
+// --- Enums ---
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter, Serialize, Deserialize,
+)]
+pub enum GeometricShape {
+    Cube,
+    Sphere,
+    Cylinder,
+    Cone,
+    SquarePyramid,
+    Tube,
+    Prism,
+    Formula,
+    NoiseBlob,
+    Text,
+    Stairs,
+    Ramp,
+    Torus,
+    Capsule,
+    Octahedron,
+    Tetrahedron,
+    Helix,
+    Arch,
+    TorusKnot,
+    MengerSponge,
+    Sierpinski,
+    Superellipsoid,
+    Terrain,
+    Gear,
+    Star,
+    Plus,
+}
+
+impl GeometricShape {
+    // Whether the shape reads `Shape::wall_thickness`
+    pub fn uses_wall_thickness(&self) -> bool {
+        matches!(
+            self,
+            GeometricShape::Sphere
+                | GeometricShape::Tube
+                | GeometricShape::Cube
+                | GeometricShape::Cylinder
+                | GeometricShape::Cone
+        )
+    }
+
+    // Whether the shape reads `Shape::seed`
+    pub fn uses_seed(&self) -> bool {
+        matches!(self, GeometricShape::NoiseBlob | GeometricShape::Terrain)
+    }
+
+    // Whether the shape reads `Shape::threshold`
+    pub fn uses_threshold(&self) -> bool {
+        matches!(self, GeometricShape::NoiseBlob)
+    }
+
+    // Whether the shape reads `Shape::frequency` and `Shape::amplitude`
+    pub fn uses_heightfield(&self) -> bool {
+        matches!(self, GeometricShape::Terrain)
+    }
+
+    // Whether the shape reads `Shape::sides`
+    pub fn uses_sides(&self) -> bool {
+        matches!(self, GeometricShape::Prism | GeometricShape::Star)
+    }
+
+    // Whether the shape reads `Shape::expression`
+    pub fn uses_expression(&self) -> bool {
+        matches!(self, GeometricShape::Formula)
+    }
+
+    // Whether the shape reads `Shape::text`
+    pub fn uses_text(&self) -> bool {
+        matches!(self, GeometricShape::Text)
+    }
+
+    // Whether the shape reads `Shape::tip_offset_x`, `Shape::tip_offset_z` and
+    // `Shape::top_radius`
+    pub fn uses_tip_offset(&self) -> bool {
+        matches!(self, GeometricShape::Cone)
+    }
+
+    // Whether the shape reads `Shape::climb`
+    pub fn uses_climb(&self) -> bool {
+        matches!(self, GeometricShape::Stairs | GeometricShape::Ramp)
+    }
+
+    // Whether the shape reads `Shape::steps`
+    pub fn uses_steps(&self) -> bool {
+        matches!(self, GeometricShape::Stairs)
+    }
+
+    // Whether the shape reads `Shape::coil_radius`
+    pub fn uses_coil(&self) -> bool {
+        matches!(self, GeometricShape::Helix | GeometricShape::TorusKnot)
+    }
+
+    // Whether the shape reads `Shape::turns`
+    pub fn uses_turns(&self) -> bool {
+        matches!(self, GeometricShape::Helix)
+    }
+
+    // Whether the shape reads `Shape::iterations`
+    pub fn uses_iterations(&self) -> bool {
+        matches!(self, GeometricShape::MengerSponge | GeometricShape::Sierpinski)
+    }
+
+    // Whether the shape reads `Shape::roundness_y` and `Shape::roundness_xz`
+    pub fn uses_roundness(&self) -> bool {
+        matches!(self, GeometricShape::Superellipsoid)
+    }
+
+    // Whether the shape reads `Shape::teeth`, `Shape::tooth_depth` and `Shape::bore_radius`
+    pub fn uses_teeth(&self) -> bool {
+        matches!(self, GeometricShape::Gear)
+    }
+
+    // Whether the shape reads `Shape::arm_thickness`
+    pub fn uses_arms(&self) -> bool {
+        matches!(self, GeometricShape::Plus)
+    }
+
+    // Whether the shape reads `Shape::knot_p` and `Shape::knot_q`
+    pub fn uses_knot(&self) -> bool {
+        matches!(self, GeometricShape::TorusKnot)
+    }
+}
+
+// Horizontal direction stairs and ramps rise towards
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Display, EnumIter, Serialize, Deserialize,
+)]
+pub enum ClimbDirection {
+    #[default]
+    #[strum(serialize = "+Z")]
+    PlusZ,
+    #[strum(serialize = "-Z")]
+    MinusZ,
+    #[strum(serialize = "+X")]
+    PlusX,
+    #[strum(serialize = "-X")]
+    MinusX,
+}
+
+impl ClimbDirection {
+    // Position of the cell at (x, z) along the climb, counted from the bottom step, and
+    // the number of cells in that direction
+    fn position(&self, x: u32, z: u32, width: u32, depth: u32) -> (u32, u32) {
+        match self {
+            ClimbDirection::PlusZ => (z, depth),
+            ClimbDirection::MinusZ => (depth - 1 - z, depth),
+            ClimbDirection::PlusX => (x, width),
+            ClimbDirection::MinusX => (width - 1 - x, width),
+        }
+    }
+
+    // Number of cells along the climb in a grid of this width and depth
+    pub fn run(&self, width: u32, depth: u32) -> u32 {
+        match self {
+            ClimbDirection::PlusZ | ClimbDirection::MinusZ => depth,
+            ClimbDirection::PlusX | ClimbDirection::MinusX => width,
+        }
+    }
+}
+
+// How two voxel sets are merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumIter)]
+pub enum BoolOp {
+    #[default]
+    Union,
+    Intersection,
+    Difference,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Display, EnumIter, Serialize, Deserialize,
+)]
+pub enum Axis {
+    X,
+    #[default]
+    Y,
+    Z,
+}
+
+// --- Data ---
+
+// Parameters describing the shape to generate. Fields missing when deserializing take
+// their default values
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shape {
+    pub kind: GeometricShape,
+    pub width: u32,
+    pub depth: u32,
+    pub height: u32,
+    // Direction the shape's main axis runs in: the height of cylinders and tubes, the
+    // apex of cones and pyramids. Y is upright
+    pub axis: Axis,
+    // Shell thickness in voxels for spheres, tubes, cubes, cylinders and cones; 0 keeps
+    // them solid (tubes are always at least 1). Ignored by the other shapes
+    pub wall_thickness: u32,
+    // Seed for procedural shapes; ignored by the others
+    pub seed: u64,
+    // Number of sides of the prism cross-section or points of the star (3-12); ignored by
+    // the other shapes
+    pub sides: u32,
+    // Density a noise blob voxel must exceed to be filled; higher gives a thinner blob
+    pub threshold: f32,
+    // String rendered by the Text shape and the empty columns between its characters;
+    // ignored by the others
+    pub text: String,
+    pub letter_spacing: u32,
+    // Formula shape: a voxel is filled where this is positive, with x, y and z running
+    // from -1 to 1 across the grid; ignored by the other shapes
+    pub expression: String,
+    // Cone apex shift along X and Z as a fraction of the base radius (-1 to 1), giving an
+    // oblique cone; ignored by the other shapes
+    pub tip_offset_x: f32,
+    pub tip_offset_z: f32,
+    // Cone top radius as a fraction of the base radius: 0 comes to a point, anything above
+    // cuts the cone off flat (a frustum) and 1 gives a cylinder; ignored by the other shapes
+    pub top_radius: f32,
+    // Direction stairs and ramps rise towards; ignored by the other shapes
+    pub climb: ClimbDirection,
+    // Number of stairs steps; 0 gives one step per layer (or per cell of the run when the
+    // run is shorter). Ignored by the other shapes
+    pub steps: u32,
+    // Helix: number of full turns over the height (the pitch is height / turns); ignored
+    // by the other shapes
+    pub turns: f32,
+    // Radius in voxels of the tube helices and torus knots are made of
+    pub coil_radius: f32,
+    // Torus knot windings around the ring's axis (p) and through its hole (q); ignored
+    // by the other shapes
+    pub knot_p: u32,
+    pub knot_q: u32,
+    // Subdivision steps of the fractal shapes; ignored by the other shapes
+    pub iterations: u32,
+    // Superellipsoid exponents for the vertical profile and the horizontal cross-section;
+    // ignored by the other shapes
+    pub roundness_y: f32,
+    pub roundness_xz: f32,
+    // Terrain: hills across the grid and how much of the height they may vary by (0 is a
+    // flat block, 1 uses the full height); ignored by the other shapes
+    pub frequency: f32,
+    pub amplitude: f32,
+    // Gear: number of teeth, how far they stick out and the radius of the hole through
+    // the middle, both in voxels; ignored by the other shapes
+    pub teeth: u32,
+    pub tooth_depth: u32,
+    pub bore_radius: u32,
+    // Thickness in voxels of the Plus shape's three arms; ignored by the other shapes
+    pub arm_thickness: u32,
+}
+
+impl Shape {
+    // Number of cells in the grid, the most voxels the shape can have
+    pub fn cell_count(&self) -> u64 {
+        self.width as u64 * self.height as u64 * self.depth as u64
+    }
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Self {
+            kind: GeometricShape::Cube,
+            width: 8,
+            depth: 8,
+            height: 8,
+            axis: Axis::Y,
+            wall_thickness: 0,
+            seed: 1,
+            sides: 6,
+            threshold: NOISE_BLOB_THRESHOLD,
+            text: "HELLO".into(),
+            letter_spacing: 1,
+            expression: "0.9 - (x*x + y*y + z*z)".into(),
+            tip_offset_x: 0.0,
+            tip_offset_z: 0.0,
+            top_radius: 0.0,
+            climb: ClimbDirection::PlusZ,
+            steps: 0,
+            turns: 3.0,
+            coil_radius: 1.0,
+            knot_p: 2,
+            knot_q: 3,
+            iterations: 3,
+            roundness_y: 0.5,
+            roundness_xz: 0.5,
+            frequency: 3.0,
+            amplitude: 0.7,
+            teeth: 12,
+            tooth_depth: 2,
+            bore_radius: 2,
+            arm_thickness: 2,
+        }
+    }
+}
+
+// Planes to mirror voxels across; any combination can be enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Symmetry {
+    pub x: bool,
+    pub y: bool,
+    pub z: bool,
+}
+
+impl Symmetry {
+    pub fn is_none(&self) -> bool {
+        !(self.x || self.y || self.z)
+    }
+}
+
+// An extra shape placed next to the main one, shifted by `offset` voxels
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SceneShape {
+    pub shape: Shape,
+    pub offset: (i32, i32, i32),
+}
+
+// Grid coordinates of a single voxel, each axis in 0..dimension
+pub type VoxelCoord = (i32, i32, i32);
+
+// Set of occupied cells: every cell is stored once and occupancy checks don't scan the
+// model. Each cell carries a PALETTE index, 0 (UNPAINTED) unless it was painted. Iteration
+// order is unspecified; `to_sorted_vec` gives a stable one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VoxelGrid {
+    cells: HashMap<VoxelCoord, u8>,
+}
+
+impl VoxelGrid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, voxel: &VoxelCoord) -> bool {
+        self.cells.contains_key(voxel)
+    }
+
+    // Fills the cell unpainted. Returns false if it was already occupied, which keeps its color
+    pub fn insert(&mut self, voxel: VoxelCoord) -> bool {
+        self.insert_colored(voxel, UNPAINTED)
+    }
+
+    // Fills the cell with palette color `color`. Returns false if it was already occupied,
+    // which keeps its color
+    pub fn insert_colored(&mut self, voxel: VoxelCoord, color: u8) -> bool {
+        match self.cells.entry(voxel) {
+            hash_map::Entry::Occupied(_) => false,
+            hash_map::Entry::Vacant(cell) => {
+                cell.insert(color);
+                true
+            }
+        }
+    }
+
+    // Returns false if the cell was empty
+    pub fn remove(&mut self, voxel: &VoxelCoord) -> bool {
+        self.cells.remove(voxel).is_some()
+    }
+
+    // Palette index of an occupied cell, None for an empty one
+    pub fn color(&self, voxel: &VoxelCoord) -> Option<u8> {
+        self.cells.get(voxel).copied()
+    }
+
+    // Gives an occupied cell palette color `color`. Returns false if the cell was empty
+    pub fn paint(&mut self, voxel: &VoxelCoord, color: u8) -> bool {
+        match self.cells.get_mut(voxel) {
+            Some(cell) => {
+                *cell = color;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn iter(&self) -> hash_map::Keys<'_, VoxelCoord, u8> {
+        self.cells.keys()
+    }
+
+    // The occupied cells with their palette index
+    pub fn colored(&self) -> impl Iterator<Item = (VoxelCoord, u8)> + '_ {
+        self.cells.iter().map(|(&voxel, &color)| (voxel, color))
+    }
+
+    // Whether any voxel has a palette color other than UNPAINTED
+    pub fn is_painted(&self) -> bool {
+        self.cells.values().any(|&color| color != UNPAINTED)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&VoxelCoord) -> bool) {
+        self.cells.retain(|voxel, _| keep(voxel));
+    }
+
+    // The cells `keep` accepts, with their colors
+    pub fn filtered(&self, keep: impl Fn(&VoxelCoord) -> bool) -> VoxelGrid {
+        let cells = self.cells.iter().filter(|(voxel, _)| keep(voxel));
+        Self {
+            cells: cells.map(|(&voxel, &color)| (voxel, color)).collect(),
+        }
+    }
+
+    // Every cell moved to `to(cell)`, keeping its color. Cells landing on the same spot
+    // collapse into one
+    pub fn map_coords(&self, to: impl Fn(VoxelCoord) -> VoxelCoord) -> VoxelGrid {
+        Self {
+            cells: self.cells.iter().map(|(&voxel, &color)| (to(voxel), color)).collect(),
+        }
+    }
+
+    // Smallest and largest occupied coordinate on each axis, or None when empty
+    pub fn bounding_box(&self) -> Option<(VoxelCoord, VoxelCoord)> {
+        bounding_box(self)
+    }
+
+    // Whether each neighbor of `voxel` is empty, in NEIGHBOR_OFFSETS order. Cells outside
+    // the grid count as empty
+    pub fn neighbors_empty(&self, voxel: VoxelCoord) -> [bool; 6] {
+        let (x, y, z) = voxel;
+        NEIGHBOR_OFFSETS.map(|(dx, dy, dz)| !self.contains(&(x + dx, y + dy, z + dz)))
+    }
+
+    // Whether `voxel` has at least one empty neighbor
+    pub fn is_exposed(&self, voxel: VoxelCoord) -> bool {
+        self.neighbors_empty(voxel).contains(&true)
+    }
+
+    // The voxels ordered by (x, y, z), for output that should not change between runs
+    pub fn to_sorted_vec(&self) -> Vec<VoxelCoord> {
+        let mut voxels: Vec<VoxelCoord> = self.cells.keys().copied().collect();
+        voxels.sort_unstable();
+        voxels
+    }
+}
+
+// Cells listed more than once keep their first color
+impl FromIterator<VoxelCoord> for VoxelGrid {
+    fn from_iter<I: IntoIterator<Item = VoxelCoord>>(iter: I) -> Self {
+        let mut grid = Self::new();
+        grid.extend(iter);
+        grid
+    }
+}
+
+impl FromIterator<(VoxelCoord, u8)> for VoxelGrid {
+    fn from_iter<I: IntoIterator<Item = (VoxelCoord, u8)>>(iter: I) -> Self {
+        let mut grid = Self::new();
+        grid.extend(iter);
+        grid
+    }
+}
+
+// Occupied cells keep their color
+impl Extend<VoxelCoord> for VoxelGrid {
+    fn extend<I: IntoIterator<Item = VoxelCoord>>(&mut self, iter: I) {
+        for voxel in iter {
+            self.insert(voxel);
+        }
+    }
+}
+
+impl Extend<(VoxelCoord, u8)> for VoxelGrid {
+    fn extend<I: IntoIterator<Item = (VoxelCoord, u8)>>(&mut self, iter: I) {
+        for (voxel, color) in iter {
+            self.insert_colored(voxel, color);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a VoxelGrid {
+    type Item = &'a VoxelCoord;
+    type IntoIter = hash_map::Keys<'a, VoxelCoord, u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells.keys()
+    }
+}
+
+impl IntoIterator for VoxelGrid {
+    type Item = VoxelCoord;
+    type IntoIter = hash_map::IntoKeys<VoxelCoord, u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells.into_keys()
+    }
+}
+
+// Duplicates in the list collapse into one voxel
+impl From<Vec<VoxelCoord>> for VoxelGrid {
+    fn from(voxels: Vec<VoxelCoord>) -> Self {
+        voxels.into_iter().collect()
+    }
+}
+
+impl From<VoxelGrid> for Vec<VoxelCoord> {
+    fn from(grid: VoxelGrid) -> Self {
+        grid.to_sorted_vec()
+    }
+}
+
+// The most recently generated shape and its voxels. As a resource this is the selected
+// object of the scene, which is where generation and all edits happen
+#[derive(Resource, Debug, Clone, Default)]
+pub struct VoxelData {
+    pub shape: Shape,
+    pub voxels: VoxelGrid,
+}
+
+impl VoxelData {
+    // World position of the center of `voxel` in this grid. The viewport and every exporter
+    // place voxels through this, so both agree exactly (see `voxel_world_position`).
+    pub fn centered_position(&self, voxel: VoxelCoord) -> Vec3 {
+        voxel_world_position(voxel, &self.shape)
+    }
+
+    // World position of the grid point at `corner`, the minimum corner of the voxel with
+    // the same coordinates. Mesh exporters write their vertices here.
+    pub fn corner_position(&self, corner: VoxelCoord) -> Vec3 {
+        self.centered_position(corner) - Vec3::splat(0.5)
+    }
+}
+
+// One object of the scene, placed `offset` voxels away from the first object
+#[derive(Debug, Clone)]
+pub struct SculptObject {
+    // Stable identifier; indices change when objects are deleted
+    pub id: u32,
+    pub name: String,
+    pub data: VoxelData,
+    pub offset: VoxelCoord,
+    pub visible: bool,
+}
+
+// Every object in the scene. The selected object is mirrored by the VoxelData resource
+#[derive(Resource, Debug, Clone)]
+pub struct SceneData {
+    pub objects: Vec<SculptObject>,
+    pub selected: usize,
+    next_id: u32,
+}
+
+impl Default for SceneData {
+    fn default() -> Self {
+        Self::new(VoxelData::default())
+    }
+}
+
+impl SceneData {
+    // A scene holding just `data`, selected
+    pub fn new(data: VoxelData) -> Self {
+        let mut scene = Self {
+            objects: Vec::new(),
+            selected: 0,
+            next_id: 0,
+        };
+        scene.add_object(data);
+        scene
+    }
+
+    // Appends a visible object named "Object <n>" at offset zero and returns its index
+    pub fn add_object(&mut self, data: VoxelData) -> usize {
+        self.next_id += 1;
+        self.objects.push(SculptObject {
+            id: self.next_id,
+            name: format!("Object {}", self.next_id),
+            data,
+            offset: (0, 0, 0),
+            visible: true,
+        });
+        self.objects.len() - 1
+    }
+
+    // Removes an object unless it is the last one; the selection stays on a valid object
+    pub fn remove_object(&mut self, index: usize) -> bool {
+        if self.objects.len() <= 1 || index >= self.objects.len() {
+            return false;
+        }
+        self.objects.remove(index);
+        if self.selected > index || self.selected >= self.objects.len() {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        true
+    }
+
+    pub fn selected_object(&self) -> &SculptObject {
+        &self.objects[self.selected]
+    }
+
+    // Object whose grid every object's voxels are placed in: the first one. Offsets are
+    // whole voxels, so all objects stay on one grid
+    pub fn origin(&self) -> &VoxelData {
+        &self.objects[0].data
+    }
+
+    // All visible objects at their offsets as one model, shifted into a fitting grid
+    pub fn merged(&self) -> VoxelData {
+        let voxels: VoxelGrid = self
+            .objects
+            .iter()
+            .filter(|object| object.visible)
+            .flat_map(|object| {
+                let (dx, dy, dz) = object.offset;
+                translate_voxels(&object.data.voxels, dx, dy, dz).cells
+            })
+            .collect();
+        let base = self.objects[0].data.shape.clone();
+        let Some((min, max)) = voxels.bounding_box() else {
+            return VoxelData { shape: base, voxels };
+        };
+        VoxelData {
+            shape: Shape {
+                width: (max.0 - min.0 + 1) as u32,
+                height: (max.1 - min.1 + 1) as u32,
+                depth: (max.2 - min.2 + 1) as u32,
+                ..base
+            },
+            voxels: normalize_voxels(&voxels),
+        }
+    }
+}
+
+// The voxel color as RGB bytes
+pub fn voxel_color_rgb() -> [u8; 3] {
+    let bytes = hex::decode(PINK_COLOR_HEX).expect("Invalid hex color");
+    [bytes[0], bytes[1], bytes[2]]
+}
+
+// Palette index of voxels that were never painted; they use the material's base color
+pub const UNPAINTED: u8 = 0;
+
+// Colors voxels can be painted with, by palette index. The UNPAINTED entry only stands in
+// for the base color, which the user can change
+pub const PALETTE: [[u8; 3]; 16] = [
+    [0xAC, 0x17, 0x54], // Base color
+    [245, 245, 245],    // White
+    [160, 160, 165],    // Gray
+    [70, 70, 75],       // Dark gray
+    [20, 20, 20],       // Black
+    [220, 40, 40],      // Red
+    [240, 130, 30],     // Orange
+    [245, 215, 50],     // Yellow
+    [140, 210, 60],     // Lime
+    [40, 140, 60],      // Green
+    [40, 170, 160],     // Teal
+    [80, 200, 240],     // Sky blue
+    [40, 80, 200],      // Blue
+    [120, 60, 180],     // Purple
+    [230, 110, 190],    // Pink
+    [120, 80, 45],      // Brown
+];
+
+// RGB color of palette index `color`, with `base` standing in for UNPAINTED and any index
+// past the end of the palette
+pub fn palette_color(color: u8, base: [u8; 3]) -> [u8; 3] {
+    match PALETTE.get(color as usize) {
+        Some(&rgb) if color != UNPAINTED => rgb,
+        _ => base,
+    }
+}
+
+// Palette index whose color is exactly `rgb`, or UNPAINTED if there is none
+pub fn palette_index(rgb: [u8; 3]) -> u8 {
+    (1..PALETTE.len())
+        .find(|&index| PALETTE[index] == rgb)
+        .map_or(UNPAINTED, |index| index as u8)
+}
+
+// Two colors blended from the bottom layer of the grid to the top one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeightGradient {
+    pub bottom: [u8; 3],
+    pub top: [u8; 3],
+}
+
+impl Default for HeightGradient {
+    fn default() -> Self {
+        Self {
+            bottom: [70, 50, 30], // Earth
+            top: [235, 235, 240], // Snow
+        }
+    }
+}
+
+impl HeightGradient {
+    // Color of layer `y` in a grid `height` voxels tall: `bottom` at y = 0, `top` at y = height - 1
+    pub fn color_at(&self, y: i32, height: u32) -> [u8; 3] {
+        let t = if height > 1 {
+            (y as f32 / (height - 1) as f32).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let mut color = [0; 3];
+        for (channel, (&bottom, &top)) in color.iter_mut().zip(self.bottom.iter().zip(&self.top)) {
+            *channel = (bottom as f32 + (top as f32 - bottom as f32) * t).round() as u8;
+        }
+        color
+    }
+}
+
+// The six face-adjacent neighbor offsets
+pub const NEIGHBOR_OFFSETS: [VoxelCoord; 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+// --- Generation ---
+
+// Returns the grid coordinates of every voxel inside the given shape
+pub fn generate_shape(shape: &Shape) -> VoxelGrid {
+    // Arches read the axis as the direction of their opening
+    if shape.axis != Axis::Y && shape.kind != GeometricShape::Arch {
+        // Generate upright in a grid whose height is the extent along the main axis, then
+        // swap that axis into place
+        let (width, height, depth) =
+            swap_axis(shape.width, shape.height, shape.depth, shape.axis);
+        let upright = Shape { axis: Axis::Y, width, height, depth, ..shape.clone() };
+        return generate_shape(&upright)
+            .into_iter()
+            .map(|(x, y, z)| swap_axis(x, y, z, shape.axis))
+            .collect();
+    }
+
+    match shape.kind {
+        GeometricShape::NoiseBlob => generate_noise_blob(shape, shape.seed),
+        GeometricShape::Terrain => generate_terrain(shape),
+        GeometricShape::Text => generate_text(shape),
+        GeometricShape::Helix => generate_helix(shape),
+        GeometricShape::Arch => generate_arch(shape),
+        GeometricShape::TorusKnot => generate_torus_knot(shape),
+        // Invalid formulas are reported by `generate_formula` and give no voxels here
+        GeometricShape::Formula => generate_formula(shape).unwrap_or_default(),
+        // These are closed shells of the solid, peeled from the inside
+        GeometricShape::Cube | GeometricShape::Cylinder | GeometricShape::Cone
+            if shape.wall_thickness > 0 =>
+        {
+            shell_voxels(&generate_solid(shape), shape.wall_thickness)
+        }
+        _ => generate_solid(shape),
+    }
+}
+
+// Swaps Y with `axis` (nothing for Y): turns upright coordinates or sizes into ones along
+// `axis` and back again
+fn swap_axis<T>(x: T, y: T, z: T, axis: Axis) -> (T, T, T) {
+    match axis {
+        Axis::X => (y, x, z),
+        Axis::Y => (x, y, z),
+        Axis::Z => (x, z, y),
+    }
+}
+
+// Number of steps a staircase is built with: `shape.steps` when set, otherwise one per
+// layer, and never more than there are layers or cells along the climb
+pub fn stair_steps(shape: &Shape) -> u32 {
+    let run = shape.climb.run(shape.width, shape.depth);
+    let most = shape.height.min(run).max(1);
+    if shape.steps == 0 {
+        most
+    } else {
+        shape.steps.min(most)
+    }
+}
+
+// Shapes described by a simple inside test on each voxel center
+fn generate_solid(shape: &Shape) -> VoxelGrid {
+    let mut voxels = VoxelGrid::new();
+
+    let w = shape.width as f32;
+    let d = shape.depth as f32;
+    let h = shape.height as f32;
+
+    let radius_x = w / 2.0;
+    let radius_y = h / 2.0; //sphere uses it as radius
+    let radius_z = d / 2.0;
+
+    // Inner radii of sphere shells and tubes. A wall at least as thick as the radius
+    // leaves no cavity, so the shape stays solid
+    let wall = match shape.kind {
+        GeometricShape::Tube => shape.wall_thickness.max(1) as f32,
+        _ => shape.wall_thickness as f32,
+    };
+    let inner_x = radius_x - wall;
+    let inner_y = radius_y - wall;
+    let inner_z = radius_z - wall;
+    let has_cavity = wall > 0.0 && inner_x > 0.0 && inner_z > 0.0;
+
+    let prism = Polygon::regular(shape.sides.clamp(MIN_PRISM_SIDES, MAX_PRISM_SIDES));
+    let triangle = Polygon::regular(3);
+
+    for y_idx in 0..shape.height {
+        for z_idx in 0..shape.depth {
+            for x_idx in 0..shape.width {
+                // Voxel center coordinates (relative to grid origin 0,0,0)
+                let vx = x_idx as f32 + 0.5;
+                let vy = y_idx as f32 + 0.5;
+                let vz = z_idx as f32 + 0.5;
+
+                let inside = match shape.kind {
+                    GeometricShape::Cube => true,
+                    GeometricShape::Sphere => {
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_y = if radius_y > 0.0 { (vy - h / 2.0) / radius_y } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        let in_outer = norm_x.powi(2) + norm_y.powi(2) + norm_z.powi(2) <= 1.0;
+
+                        // Spherical shell: drop voxels inside the inner ellipsoid
+                        let in_inner = has_cavity && inner_y > 0.0 && {
+                            let inner_norm_x = (vx - w / 2.0) / inner_x;
+                            let inner_norm_y = (vy - h / 2.0) / inner_y;
+                            let inner_norm_z = (vz - d / 2.0) / inner_z;
+                            inner_norm_x.powi(2) + inner_norm_y.powi(2) + inner_norm_z.powi(2) <= 1.0
+                        };
+
+                        in_outer && !in_inner
+                    }
+                    GeometricShape::Cylinder => {
+                        // Y-axis aligned cylinder
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        norm_x.powi(2) + norm_z.powi(2) <= 1.0
+                    }
+                    GeometricShape::Cone => {
+                        // Y-axis aligned cone, apex pointing up (+Y). The cross-section
+                        // shrinks linearly to the top radius, so the scale factor stays
+                        // between that and 1
+                        let top = shape.top_radius.clamp(0.0, 1.0);
+                        let scale_factor = (1.0 - (1.0 - top) * (vy / h)).max(0.0);
+
+                        let scaled_radius_x = radius_x * scale_factor;
+                        let scaled_radius_z = radius_z * scale_factor;
+
+                        // The cross-section's center moves linearly from the base center
+                        // towards the (possibly shifted) apex
+                        let center_x = w / 2.0 + shape.tip_offset_x * radius_x * (vy / h);
+                        let center_z = d / 2.0 + shape.tip_offset_z * radius_z * (vy / h);
+
+                        let norm_x = if scaled_radius_x > 0.01 { (vx - center_x) / scaled_radius_x } else { 0.0 };
+                        let norm_z = if scaled_radius_z > 0.01 { (vz - center_z) / scaled_radius_z } else { 0.0 };
+
+                        // Check within base ellipse at this height
+                        norm_x.powi(2) + norm_z.powi(2) <= 1.0
+                    }
+                    GeometricShape::Tube => {
+                        // Y-axis aligned cylinder with the inner ellipse removed on every layer
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        let in_outer = norm_x.powi(2) + norm_z.powi(2) <= 1.0;
+
+                        let in_inner = has_cavity && {
+                            let inner_norm_x = (vx - w / 2.0) / inner_x;
+                            let inner_norm_z = (vz - d / 2.0) / inner_z;
+                            inner_norm_x.powi(2) + inner_norm_z.powi(2) <= 1.0
+                        };
+
+                        in_outer && !in_inner
+                    }
+                    GeometricShape::Prism => prism.contains(vx / w, vz / d),
+                    GeometricShape::Capsule => {
+                        // Y-axis aligned cylinder with half-ellipsoid caps. The caps are as
+                        // tall as the narrower radius (at most half the height) and share
+                        // the body's elliptical footprint
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        let cap = radius_x.min(radius_z).min(radius_y);
+                        // Distance past the straight middle section, 0 inside it
+                        let past = vy - vy.clamp(cap, h - cap);
+                        let norm_y = if cap > 0.0 { past / cap } else { 0.0 };
+                        norm_x.powi(2) + norm_y.powi(2) + norm_z.powi(2) <= 1.0
+                    }
+                    GeometricShape::Tetrahedron => {
+                        // Triangular pyramid: the base triangle fills the width and depth
+                        // and shrinks towards the apex above its center
+                        let scale_factor = (1.0 - (vy / h)).max(0.0);
+                        triangle.contains_scaled(vx / w, vz / d, scale_factor)
+                    }
+                    GeometricShape::MengerSponge => {
+                        // Each step splits every cube into 27 and removes the center and the
+                        // middle of each face: the cells with at least two middle thirds
+                        let mut scale = 1.0;
+                        (0..shape.iterations.min(MAX_FRACTAL_ITERATIONS)).all(|_| {
+                            scale *= 3.0;
+                            let middle = |v: f32, size: f32| (v / size * scale) as u32 % 3 == 1;
+                            let middles = [middle(vx, w), middle(vy, h), middle(vz, d)];
+                            middles.iter().filter(|&&m| m).count() < 2
+                        })
+                    }
+                    GeometricShape::Sierpinski => {
+                        // Sierpinski tetrahedron in the corner of the grid: scaled to a
+                        // 2^n cube, a cell is kept when no two of its coordinates share a
+                        // set bit
+                        let cells = (1u32 << shape.iterations.min(MAX_FRACTAL_ITERATIONS)) as f32;
+                        let (x, y, z) = (
+                            (vx / w * cells) as u32,
+                            (vy / h * cells) as u32,
+                            (vz / d * cells) as u32,
+                        );
+                        x & y == 0 && y & z == 0 && x & z == 0
+                    }
+                    GeometricShape::Superellipsoid => {
+                        // (|x|^(2/e2) + |z|^(2/e2))^(e2/e1) + |y|^(2/e1) <= 1: exponents of
+                        // 1 give the sphere, smaller ones a rounded box, 2 the octahedron
+                        let e1 = shape.roundness_y.clamp(MIN_ROUNDNESS, MAX_ROUNDNESS);
+                        let e2 = shape.roundness_xz.clamp(MIN_ROUNDNESS, MAX_ROUNDNESS);
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_y = if radius_y > 0.0 { (vy - h / 2.0) / radius_y } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        let across = norm_x.abs().powf(2.0 / e2) + norm_z.abs().powf(2.0 / e2);
+                        across.powf(e2 / e1) + norm_y.abs().powf(2.0 / e1) <= 1.0
+                    }
+                    GeometricShape::Plus => {
+                        // Three bars through the center, one along each axis: a cell is in
+                        // a bar when it is in the central band of the other two axes
+                        let band = |index: u32, size: u32| {
+                            let thickness = shape.arm_thickness.clamp(1, size);
+                            let start = (size - thickness) / 2;
+                            (start..start + thickness).contains(&index)
+                        };
+                        let bands = [
+                            band(x_idx, shape.width),
+                            band(y_idx, shape.height),
+                            band(z_idx, shape.depth),
+                        ];
+                        bands.iter().filter(|&&in_band| in_band).count() >= 2
+                    }
+                    GeometricShape::Star => {
+                        // Y-axis aligned star prism with one point towards +X. Each point
+                        // is bounded by straight edges from the rim to inner corners at
+                        // half the radius, halfway to the neighboring points
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        let points = shape.sides.clamp(MIN_PRISM_SIDES, MAX_PRISM_SIDES) as f32;
+                        let half_step = TAU / points / 2.0;
+                        // Angle from the nearest point, folded onto 0..=half_step
+                        let angle = norm_z.atan2(norm_x).rem_euclid(2.0 * half_step);
+                        let angle = half_step - (angle - half_step).abs();
+                        let r = norm_x.hypot(norm_z);
+                        let (px, pz) = (r * angle.cos(), r * angle.sin());
+                        // Edge from the tip at (1, 0) to the inner corner; the center lies
+                        // on its left, so inside is where the cross product is positive
+                        let (sin, cos) = half_step.sin_cos();
+                        let (ex, ez) = (0.5 * cos - 1.0, 0.5 * sin);
+                        ex * pz - ez * (px - 1.0) >= 0.0
+                    }
+                    GeometricShape::Gear => {
+                        // Y-axis aligned disc with square teeth around the rim and a round
+                        // bore. Radii are fractions of the elliptical rim, with the depth and
+                        // bore measured along the narrower radius
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        let rim = norm_x.hypot(norm_z);
+                        let narrow = radius_x.min(radius_z).max(0.5);
+                        let root = 1.0 - shape.tooth_depth as f32 / narrow;
+                        let bore = shape.bore_radius as f32 / narrow;
+                        let teeth = shape.teeth.clamp(MIN_GEAR_TEETH, MAX_GEAR_TEETH) as f32;
+                        let turn = norm_z.atan2(norm_x) / TAU + 1.0;
+                        let on_tooth = (turn * teeth).fract() < 0.5;
+                        rim <= 1.0 && rim >= bore && (rim <= root || on_tooth)
+                    }
+                    GeometricShape::Octahedron => {
+                        // Diamond touching the middle of every face of the grid
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_y = if radius_y > 0.0 { (vy - h / 2.0) / radius_y } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        norm_x.abs() + norm_y.abs() + norm_z.abs() <= 1.0
+                    }
+                    GeometricShape::Torus => {
+                        // A ring filling the width and depth with a tube as thick as the
+                        // grid is high. The tube's radial half-width, as a fraction of the
+                        // ring's radius, is capped at a third so there is always a hole;
+                        // taller grids stretch the tube's cross-section instead
+                        let norm_x = if radius_x > 0.0 { (vx - w / 2.0) / radius_x } else { 0.0 };
+                        let norm_y = if radius_y > 0.0 { (vy - h / 2.0) / radius_y } else { 0.0 };
+                        let norm_z = if radius_z > 0.0 { (vz - d / 2.0) / radius_z } else { 0.0 };
+                        let tube = (radius_y / radius_x.min(radius_z)).min(1.0 / 3.0);
+                        let ring = (norm_x.powi(2) + norm_z.powi(2)).sqrt() - (1.0 - tube);
+                        (ring / tube).powi(2) + norm_y.powi(2) <= 1.0
+                    }
+                    GeometricShape::Stairs => {
+                        // Layer y belongs to step floor(y * steps / height) and keeps the
+                        // cells at or past floor(step * run / steps), so steps differ in
+                        // height and depth by at most one and the top layer is never empty
+                        let (pos, run) =
+                            shape.climb.position(x_idx, z_idx, shape.width, shape.depth);
+                        let steps = stair_steps(shape) as u64;
+                        let step = y_idx as u64 * steps / shape.height as u64;
+                        pos as u64 >= step * run as u64 / steps
+                    }
+                    GeometricShape::Ramp => {
+                        // pos / (run - 1) >= y / (height - 1): cell centers measured from
+                        // the first to the last one, so the bottom layer is a full slab
+                        // and the top layer keeps the last row
+                        let (pos, run) =
+                            shape.climb.position(x_idx, z_idx, shape.width, shape.depth);
+                        let (pos, run) = (pos as u64, run as u64);
+                        pos * (shape.height as u64 - 1) >= y_idx as u64 * (run - 1)
+                    }
+                    GeometricShape::NoiseBlob => unreachable!("generated by generate_noise_blob"),
+                    GeometricShape::Terrain => unreachable!("generated by generate_terrain"),
+                    GeometricShape::Text => unreachable!("generated by generate_text"),
+                    GeometricShape::Helix => unreachable!("generated by generate_helix"),
+                    GeometricShape::Arch => unreachable!("generated by generate_arch"),
+                    GeometricShape::TorusKnot => unreachable!("generated by generate_torus_knot"),
+                    GeometricShape::Formula => unreachable!("generated by generate_formula"),
+                    GeometricShape::SquarePyramid => {
+                        // Y-axis aligned pyramid, apex pointing up (+Y)
+                        let scale_factor = (1.0 - (vy / h)).max(0.0); // Ensure scale factor is not negative
+
+                        let max_dist_x = radius_x * scale_factor;
+                        let max_dist_z = radius_z * scale_factor;
+
+                        // Check within bounding box at this height
+                        (vx - w / 2.0).abs() <= max_dist_x && (vz - d / 2.0).abs() <= max_dist_z
+                    }
+                };
+
+                if inside {
+                    voxels.insert((x_idx as i32, y_idx as i32, z_idx as i32));
+                }
+            }
+        }
+    }
+
+    voxels
+}
+
+// Fills the voxels where `shape.expression` is positive, evaluated at each voxel center with
+// x, y and z scaled to -1..1 across the grid. Fails with the parse error for invalid formulas
+pub fn generate_formula(shape: &Shape) -> Result<VoxelGrid, String> {
+    let expr = expr::parse(&shape.expression)?;
+    let normalized = |index: u32, size: u32| (index as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+
+    let mut voxels = VoxelGrid::new();
+    for y_idx in 0..shape.height {
+        for z_idx in 0..shape.depth {
+            for x_idx in 0..shape.width {
+                let x = normalized(x_idx, shape.width);
+                let y = normalized(y_idx, shape.height);
+                let z = normalized(z_idx, shape.depth);
+                if expr.eval(x, y, z) > 0.0 {
+                    voxels.insert((x_idx as i32, y_idx as i32, z_idx as i32));
+                }
+            }
+        }
+    }
+    Ok(voxels)
+}
+
+// Regular polygon with one flat side facing +X, stretched so it fills the unit square
+struct Polygon {
+    // Outward edge normals; a point is inside when it is within `apothem` of the center
+    // along every one of them
+    normals: Vec<(f32, f32)>,
+    apothem: f32,
+    // Corner of the polygon's bounding box and its size
+    min: (f32, f32),
+    size: (f32, f32),
+}
+
+impl Polygon {
+    fn regular(sides: u32) -> Self {
+        let step = TAU / sides as f32;
+        let normals: Vec<(f32, f32)> = (0..sides)
+            .map(|i| (i as f32 * step).cos())
+            .zip((0..sides).map(|i| (i as f32 * step).sin()))
+            .collect();
+        // Vertices on the unit circle sit halfway between the edge normals
+        let vertices: Vec<(f32, f32)> = (0..sides)
+            .map(|i| {
+                let angle = (i as f32 + 0.5) * step;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        let min_x = vertices.iter().map(|v| v.0).fold(f32::MAX, f32::min);
+        let max_x = vertices.iter().map(|v| v.0).fold(f32::MIN, f32::max);
+        let min_z = vertices.iter().map(|v| v.1).fold(f32::MAX, f32::min);
+        let max_z = vertices.iter().map(|v| v.1).fold(f32::MIN, f32::max);
+        Self {
+            normals,
+            apothem: (step / 2.0).cos(),
+            min: (min_x, min_z),
+            size: (max_x - min_x, max_z - min_z),
+        }
+    }
+
+    // Whether the point (u, v) of the unit square lies inside the stretched polygon
+    fn contains(&self, u: f32, v: f32) -> bool {
+        self.contains_scaled(u, v, 1.0)
+    }
+
+    // Same for the polygon shrunk by `scale` towards its center
+    fn contains_scaled(&self, u: f32, v: f32, scale: f32) -> bool {
+        let x = self.min.0 + u * self.size.0;
+        let z = self.min.1 + v * self.size.1;
+        self.normals
+            .iter()
+            .all(|&(nx, nz)| x * nx + z * nz <= self.apothem * scale + 1e-4)
+    }
+}
+
+// Irregular rock-like blob: fractal noise on top of an ellipsoid falloff, so the blob
+// stays roughly centered. Voxels denser than `shape.threshold` are filled. Never empty,
+// the same seed always gives the same voxels
+pub fn generate_noise_blob(shape: &Shape, seed: u64) -> VoxelGrid {
+    let noise = ValueNoise::new(seed);
+    let frequency = (shape.width as f32 / 4.0).max(1.0);
+
+    let w = shape.width as f32;
+    let d = shape.depth as f32;
+    let h = shape.height as f32;
+
+    let mut voxels = VoxelGrid::new();
+    let mut densest: Option<(f32, VoxelCoord)> = None;
+    for y_idx in 0..shape.height {
+        for z_idx in 0..shape.depth {
+            for x_idx in 0..shape.width {
+                let vx = x_idx as f32 + 0.5;
+                let vy = y_idx as f32 + 0.5;
+                let vz = z_idx as f32 + 0.5;
+
+                // Squared distance from the center, 1.0 on the bounding ellipsoid
+                let norm_x = (vx - w / 2.0) / (w / 2.0);
+                let norm_y = (vy - h / 2.0) / (h / 2.0);
+                let norm_z = (vz - d / 2.0) / (d / 2.0);
+                let falloff = 1.0 - (norm_x.powi(2) + norm_y.powi(2) + norm_z.powi(2));
+
+                let value = noise.fractal(vx / frequency, vy / frequency, vz / frequency, 3);
+                let density = 0.6 * value + 0.4 * falloff;
+
+                let voxel = (x_idx as i32, y_idx as i32, z_idx as i32);
+                if density > shape.threshold {
+                    voxels.insert(voxel);
+                }
+                if densest.is_none_or(|(best, _)| density > best) {
+                    densest = Some((density, voxel));
+                }
+            }
+        }
+    }
+
+    // Unlucky seeds can fall below the threshold everywhere; keep the densest voxel then
+    if voxels.is_empty() {
+        voxels.extend(densest.map(|(_, voxel)| voxel));
+    }
+    voxels
+}
+
+// Fills every column up to a seeded fractal noise heightfield. `shape.frequency` is about the
+// number of hills across the grid and `shape.amplitude` the fraction of the height they span
+// from the top down; every column keeps at least the bottom layer
+pub fn generate_terrain(shape: &Shape) -> VoxelGrid {
+    let noise = ValueNoise::new(shape.seed);
+    let frequency = shape.frequency.clamp(0.0, MAX_TERRAIN_FREQUENCY);
+    let amplitude = shape.amplitude.clamp(0.0, 1.0);
+    let h = shape.height as f32;
+
+    let mut voxels = VoxelGrid::new();
+    for z_idx in 0..shape.depth {
+        for x_idx in 0..shape.width {
+            let u = (x_idx as f32 + 0.5) / shape.width as f32 * frequency;
+            let v = (z_idx as f32 + 0.5) / shape.depth as f32 * frequency;
+            // A plane between lattice layers, so the heights don't snap to the lattice
+            let value = noise.fractal(u, 0.5, v, 4);
+            let surface = (h * (1.0 - amplitude + amplitude * value)).round() as u32;
+            for y_idx in 0..surface.clamp(1, shape.height) {
+                voxels.insert((x_idx as i32, y_idx as i32, z_idx as i32));
+            }
+        }
+    }
+    voxels
+}
+
+// Adds the mirror image of every voxel across the enabled center planes of the shape's grid,
+// e.g. x -> (width - 1) - x. With an even width the two middle columns swap; with an odd
+// width the center column mirrors onto itself. Voxels that land on the same cell are only
+// kept once.
+pub fn apply_symmetry<'a>(
+    voxels: impl IntoIterator<Item = &'a VoxelCoord>,
+    shape: &Shape,
+    symmetry: Symmetry,
+) -> VoxelGrid {
+    if symmetry.is_none() {
+        return voxels.into_iter().copied().collect();
+    }
+
+    let max_x = shape.width as i32 - 1;
+    let max_y = shape.height as i32 - 1;
+    let max_z = shape.depth as i32 - 1;
+
+    let mut result = VoxelGrid::new();
+    for &(x, y, z) in voxels {
+        // Every combination of the enabled mirrors, including the voxel itself
+        for mirror_x in [false, symmetry.x] {
+            for mirror_y in [false, symmetry.y] {
+                for mirror_z in [false, symmetry.z] {
+                    let voxel = (
+                        if mirror_x { max_x - x } else { x },
+                        if mirror_y { max_y - y } else { y },
+                        if mirror_z { max_z - z } else { z },
+                    );
+                    result.insert(voxel);
+                }
+            }
+        }
+    }
+    result
+}
+
+// A block filling the grid with a round-topped opening cut through it. The opening runs
+// along X for the X axis and through the depth otherwise, so the arch always stands on
+// the ground. The pillars are a quarter of the width and the top a quarter of the height
+// (at least one voxel each); the half-ellipse at the top is as tall as the opening is wide
+// where the height allows it
+pub fn generate_arch(shape: &Shape) -> VoxelGrid {
+    // The opening's width runs across `span` cells
+    let along_x = shape.axis == Axis::X;
+    let (span, length) = if along_x {
+        (shape.depth, shape.width)
+    } else {
+        (shape.width, shape.depth)
+    };
+    let (s, h) = (span as f32, shape.height as f32);
+    let pillar = (span / 4).max(1) as f32;
+    let top = (shape.height / 4).max(1) as f32;
+    let radius = s / 2.0 - pillar;
+    let opening_height = h - top;
+    let rise = radius.min(opening_height);
+    let spring = opening_height - rise;
+
+    let mut voxels = VoxelGrid::new();
+    for y_idx in 0..shape.height {
+        for across in 0..span {
+            let vx = across as f32 + 0.5 - s / 2.0;
+            let vy = y_idx as f32 + 0.5;
+            let in_curve =
+                rise > 0.0 && (vx / radius).powi(2) + ((vy - spring) / rise).powi(2) <= 1.0;
+            let in_opening = radius > 0.0 && vx.abs() <= radius && (vy <= spring || in_curve);
+            if in_opening {
+                continue;
+            }
+            for along in 0..length {
+                let (x, z) = if along_x { (along, across) } else { (across, along) };
+                voxels.insert((x as i32, y_idx as i32, z as i32));
+            }
+        }
+    }
+    voxels
+}
+
+// A tube of `shape.coil_radius` coiled `shape.turns` times around the Y axis, like a spring.
+// The coil fills the width and depth and climbs from the bottom to the top of the grid
+pub fn generate_helix(shape: &Shape) -> VoxelGrid {
+    let (w, h, d) = (shape.width as f32, shape.height as f32, shape.depth as f32);
+    let tube = shape.coil_radius.max(0.5);
+    let turns = shape.turns.clamp(0.0, MAX_HELIX_TURNS);
+    // The center line stays a tube radius inside the grid where the grid allows it
+    let inset = |size: f32| (size / 2.0 - tube).max(0.0);
+    let (radius_x, radius_z) = (inset(w), inset(d));
+    let (bottom, top) = (tube.min(h / 2.0), (h - tube).max(h / 2.0));
+
+    let length = turns * TAU * radius_x.max(radius_z) + (top - bottom);
+    sweep_tube(shape, tube, length, |t| {
+        let angle = t * turns * TAU;
+        Vec3::new(
+            w / 2.0 + radius_x * angle.cos(),
+            bottom + t * (top - bottom),
+            d / 2.0 + radius_z * angle.sin(),
+        )
+    })
+}
+
+// A (p, q) torus knot: a tube of `shape.coil_radius` winding p times around the ring's
+// axis and q times through its hole. The ring lies flat and fills the grid; p and q with
+// a common factor give several linked loops instead of one knot
+pub fn generate_torus_knot(shape: &Shape) -> VoxelGrid {
+    let (w, h, d) = (shape.width as f32, shape.height as f32, shape.depth as f32);
+    let tube = shape.coil_radius.max(0.5);
+    let winding = |n: u32| n.clamp(1, MAX_KNOT_WINDINGS) as f32;
+    let (p, q) = (winding(shape.knot_p), winding(shape.knot_q));
+    // The curve spans -3..3 across the ring and -1..1 vertically
+    let inset = |size: f32| (size / 2.0 - tube).max(0.0);
+    let scale = Vec3::new(inset(w) / 3.0, inset(h), inset(d) / 3.0);
+
+    let length = TAU * (p * 2.0 * scale.x.max(scale.z) + q * (scale.x.max(scale.z) + scale.y));
+    sweep_tube(shape, tube, length, |t| {
+        let angle = t * TAU;
+        let ring = 2.0 + (q * angle).cos();
+        let (sin, cos) = (p * angle).sin_cos();
+        let point = Vec3::new(ring * cos, (q * angle).sin(), ring * sin);
+        Vec3::new(w, h, d) / 2.0 + point * scale
+    })
+}
+
+// Stamps a ball of `radius` at about four points per voxel along a curve of roughly
+// `length` voxels. `point` maps 0..=1 to grid coordinates; voxels outside the grid are
+// left out
+fn sweep_tube(shape: &Shape, radius: f32, length: f32, point: impl Fn(f32) -> Vec3) -> VoxelGrid {
+    let samples = (length * 4.0).ceil().max(1.0) as u32;
+    let reach = radius.ceil() as i32;
+
+    let mut voxels = VoxelGrid::new();
+    for i in 0..=samples {
+        let center = point(i as f32 / samples as f32);
+        let cell = center.floor().as_ivec3();
+        for dy in -reach..=reach {
+            for dz in -reach..=reach {
+                for dx in -reach..=reach {
+                    let (x, y, z) = (cell.x + dx, cell.y + dy, cell.z + dz);
+                    let inside_grid = (0..shape.width as i32).contains(&x)
+                        && (0..shape.height as i32).contains(&y)
+                        && (0..shape.depth as i32).contains(&z);
+                    let voxel_center = Vec3::new(x as f32, y as f32, z as f32) + 0.5;
+                    if inside_grid && voxel_center.distance_squared(center) <= radius * radius {
+                        voxels.insert((x, y, z));
+                    }
+                }
+            }
+        }
+    }
+    voxels
+}
+
+// Rasterizes `shape.text` with the 5x7 font: characters run along +X with
+// `shape.letter_spacing` empty columns between them and are extruded through the whole
+// depth. The line is centered in the grid; whatever doesn't fit the width or height is cut
+// off. Characters the font lacks leave a gap.
+pub fn generate_text(shape: &Shape) -> VoxelGrid {
+    let chars: Vec<char> = shape.text.chars().collect();
+    if chars.is_empty() {
+        return VoxelGrid::new();
+    }
+
+    let advance = GLYPH_WIDTH + shape.letter_spacing.min(MAX_LETTER_SPACING) as usize;
+    let line_width = (chars.len() * advance - (advance - GLYPH_WIDTH)) as i32;
+    let start_x = (shape.width as i32 - line_width) / 2;
+    let start_y = (shape.height as i32 - GLYPH_HEIGHT as i32) / 2;
+
+    let mut voxels = VoxelGrid::new();
+    for (index, c) in chars.into_iter().enumerate() {
+        let Some(rows) = glyph(c) else {
+            continue;
+        };
+        let glyph_x = start_x + (index * advance) as i32;
+        for (row, bits) in rows.iter().enumerate() {
+            // Row 0 is the top of the glyph
+            let y = start_y + (GLYPH_HEIGHT - 1 - row) as i32;
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                let x = glyph_x + column as i32;
+                if !(0..shape.width as i32).contains(&x) || !(0..shape.height as i32).contains(&y) {
+                    continue;
+                }
+                for z in 0..shape.depth as i32 {
+                    voxels.insert((x, y, z));
+                }
+            }
+        }
+    }
+    voxels
+}
+
+// Keeps only the surface voxels, i.e. those with at least one empty neighbor
+pub fn hollow_voxels(voxels: &VoxelGrid) -> VoxelGrid {
+    voxels.filtered(|&voxel| voxels.is_exposed(voxel))
+}
+
+// Keeps the voxels at most `thickness` steps from an empty neighbor, removing the inside
+// layer by layer; a thickness of 1 is the same as `hollow_voxels`
+pub fn shell_voxels(voxels: &VoxelGrid, thickness: u32) -> VoxelGrid {
+    let mut inside = voxels.clone();
+    for _ in 0..thickness {
+        if inside.is_empty() {
+            break;
+        }
+        let peeled = hollow_voxels(&inside);
+        inside.retain(|voxel| !peeled.contains(voxel));
+    }
+    voxels.filtered(|voxel| !inside.contains(voxel))
+}
+
+// Cells 6-connected to `start` that are occupied (`filled`) or empty (`!filled`), staying
+// within `min..=max` on every axis. Empty when `start` itself doesn't match or is outside
+pub fn connected_region(
+    voxels: &VoxelGrid,
+    start: VoxelCoord,
+    filled: bool,
+    min: VoxelCoord,
+    max: VoxelCoord,
+) -> HashSet<VoxelCoord> {
+    let matches = |cell: VoxelCoord| {
+        let (x, y, z) = cell;
+        (min.0..=max.0).contains(&x)
+            && (min.1..=max.1).contains(&y)
+            && (min.2..=max.2).contains(&z)
+            && voxels.contains(&cell) == filled
+    };
+    let mut region = HashSet::new();
+    if !matches(start) {
+        return region;
+    }
+
+    region.insert(start);
+    let mut queue = VecDeque::from([start]);
+    while let Some((x, y, z)) = queue.pop_front() {
+        for &(dx, dy, dz) in &NEIGHBOR_OFFSETS {
+            let next = (x + dx, y + dy, z + dz);
+            if matches(next) && region.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    region
+}
+
+// Empty cells 6-connected to `start` within the grid 0..bounds on each axis. Empty when
+// `start` is occupied or outside the grid
+pub fn flood_fill_empty(
+    voxels: &VoxelGrid,
+    start: VoxelCoord,
+    bounds: (i32, i32, i32),
+) -> HashSet<VoxelCoord> {
+    let max = (bounds.0 - 1, bounds.1 - 1, bounds.2 - 1);
+    connected_region(voxels, start, false, (0, 0, 0), max)
+}
+
+// Voxels bordering the air pocket that contains `start`, i.e. the inner wall of the pocket.
+// None when `start` isn't empty or its air reaches the edge of the grid, so it is open to
+// the outside rather than enclosed
+pub fn pocket_walls(
+    voxels: &VoxelGrid,
+    start: VoxelCoord,
+    bounds: (i32, i32, i32),
+) -> Option<HashSet<VoxelCoord>> {
+    let pocket = flood_fill_empty(voxels, start, bounds);
+    let on_edge = |&(x, y, z): &VoxelCoord| {
+        x == 0 || y == 0 || z == 0 || x == bounds.0 - 1 || y == bounds.1 - 1 || z == bounds.2 - 1
+    };
+    if pocket.is_empty() || pocket.iter().any(on_edge) {
+        return None;
+    }
+
+    let walls = pocket
+        .iter()
+        .flat_map(|&(x, y, z)| {
+            NEIGHBOR_OFFSETS.iter().map(move |&(dx, dy, dz)| (x + dx, y + dy, z + dz))
+        })
+        .filter(|neighbor| voxels.contains(neighbor))
+        .collect();
+    Some(walls)
+}
+
+// Erodes the surface: each voxel with an empty neighbor is removed with probability
+// `amount` (0.0-1.0). Interior voxels are never touched, and anything no longer connected
+// to the bottom layer is dropped so no floating crumbs are left behind. The outcome depends
+// only on the voxels, `amount` and `seed`.
+pub fn apply_noise(voxels: &mut VoxelGrid, amount: f32, seed: u64) {
+    let amount = amount.clamp(0.0, 1.0);
+    if amount <= 0.0 || voxels.is_empty() {
+        return;
+    }
+
+    let occupied = voxels.clone();
+    voxels.retain(|&(x, y, z)| {
+        if !occupied.is_exposed((x, y, z)) {
+            return true;
+        }
+        // Seed per coordinate so the result doesn't depend on voxel order
+        let mut state = seed
+            ^ (x as u64).wrapping_mul(0x9E37_79B9)
+            ^ (y as u64).wrapping_mul(0x85EB_CA6B) << 21
+            ^ (z as u64).wrapping_mul(0xC2B2_AE35) << 42;
+        let roll = (splitmix64(&mut state) >> 40) as f32 / (1u64 << 24) as f32;
+        roll >= amount
+    });
+
+    // Flood fill from the bottom layer and drop whatever it can't reach
+    let Some(bottom) = voxels.iter().map(|v| v.1).min() else {
+        return;
+    };
+    let mut connected: HashSet<VoxelCoord> = HashSet::new();
+    let mut stack: Vec<VoxelCoord> = voxels.iter().copied().filter(|v| v.1 == bottom).collect();
+    while let Some(voxel) = stack.pop() {
+        if !connected.insert(voxel) {
+            continue;
+        }
+        let (x, y, z) = voxel;
+        for &(dx, dy, dz) in &NEIGHBOR_OFFSETS {
+            let neighbor = (x + dx, y + dy, z + dz);
+            if voxels.contains(&neighbor) && !connected.contains(&neighbor) {
+                stack.push(neighbor);
+            }
+        }
+    }
+    voxels.retain(|voxel| connected.contains(voxel));
+}
+
+// --- Boolean operations ---
+
+// Combines two voxel sets. Difference removes `b` from `a`. Voxels of `a` keep their color,
+// also where `b` overlaps them
+pub fn combine_shapes(a: &VoxelGrid, b: &VoxelGrid, op: BoolOp) -> VoxelGrid {
+    match op {
+        BoolOp::Union => a.colored().chain(b.colored()).collect(),
+        BoolOp::Intersection => a.filtered(|v| b.contains(v)),
+        BoolOp::Difference => a.filtered(|v| !b.contains(v)),
+    }
+}
+
+// Combines the current model with a freshly generated second shape. Both are centered
+// in a grid large enough for either, then the second shape is moved `offset` cells. The
+// second shape is mirrored across the enabled symmetry planes of that grid first, so adding
+// or cutting it keeps a symmetric model symmetric. A union grows the grid to fit an offset
+// shape and shifts everything if it reaches below zero. The grown grid isn't capped, so
+// callers check it against MAX_DIMENSION and MAX_GRID_CELLS.
+pub fn combine_with_shape(
+    current: &VoxelData,
+    second: &Shape,
+    offset: VoxelCoord,
+    op: BoolOp,
+    symmetry: Symmetry,
+) -> VoxelData {
+    let first = &current.shape;
+    let grid = Shape {
+        width: first.width.max(second.width),
+        height: first.height.max(second.height),
+        depth: first.depth.max(second.depth),
+        ..first.clone()
+    };
+
+    let centered = |voxels: &VoxelGrid, shape: &Shape| -> VoxelGrid {
+        let dx = (grid.width - shape.width) as i32 / 2;
+        let dy = (grid.height - shape.height) as i32 / 2;
+        let dz = (grid.depth - shape.depth) as i32 / 2;
+        translate_voxels(voxels, dx, dy, dz)
+    };
+
+    let a = centered(&current.voxels, first);
+    let b = centered(&generate_shape(second), second);
+    let b = translate_voxels(&b, offset.0, offset.1, offset.2);
+    let b = apply_symmetry(&b, &grid, symmetry);
+
+    let mut min = (0, 0, 0);
+    let mut max = (grid.width as i32 - 1, grid.height as i32 - 1, grid.depth as i32 - 1);
+    let added = b.bounding_box().filter(|_| op == BoolOp::Union);
+    if let Some((b_min, b_max)) = added {
+        min = (min.0.min(b_min.0), min.1.min(b_min.1), min.2.min(b_min.2));
+        max = (max.0.max(b_max.0), max.1.max(b_max.1), max.2.max(b_max.2));
+    }
+    let combined = combine_shapes(&a, &b, op);
+    VoxelData {
+        voxels: translate_voxels(&combined, -min.0, -min.1, -min.2),
+        shape: Shape {
+            width: (max.0 - min.0 + 1) as u32,
+            height: (max.1 - min.1 + 1) as u32,
+            depth: (max.2 - min.2 + 1) as u32,
+            ..grid
+        },
+    }
+}
+
+// Merges the main shape with extra shapes at their offsets (a plain union, each cell once).
+// The grid grows to fit and everything is shifted if an offset pushes voxels below zero.
+pub fn compose_scene(main: &Shape, extra: &[SceneShape]) -> VoxelData {
+    let mut voxels = generate_shape(main);
+    if extra.is_empty() {
+        return VoxelData { shape: main.clone(), voxels };
+    }
+
+    for scene_shape in extra {
+        let (dx, dy, dz) = scene_shape.offset;
+        voxels.extend(translate_voxels(&generate_shape(&scene_shape.shape), dx, dy, dz));
+    }
+
+    let Some((min, max)) = voxels.bounding_box() else {
+        return VoxelData { shape: main.clone(), voxels };
+    };
+    let shift = (min.0.min(0), min.1.min(0), min.2.min(0));
+    let voxels = translate_voxels(&voxels, -shift.0, -shift.1, -shift.2);
+    VoxelData {
+        shape: Shape {
+            width: main.width.max((max.0 - shift.0 + 1) as u32),
+            height: main.height.max((max.1 - shift.1 + 1) as u32),
+            depth: main.depth.max((max.2 - shift.2 + 1) as u32),
+            ..main.clone()
+        },
+        voxels,
+    }
+}
+
+// --- Transforms ---
+
+// Moves every voxel by the given offset
+pub fn translate_voxels(voxels: &VoxelGrid, dx: i32, dy: i32, dz: i32) -> VoxelGrid {
+    voxels.map_coords(|(x, y, z)| (x + dx, y + dy, z + dz))
+}
+
+// Shifts the voxels so the smallest coordinate on each axis is 0
+pub fn normalize_voxels(voxels: &VoxelGrid) -> VoxelGrid {
+    match voxels.bounding_box() {
+        Some((min, _)) => translate_voxels(voxels, -min.0, -min.1, -min.2),
+        None => VoxelGrid::new(),
+    }
+}
+
+// Rotates the voxels a quarter turn counter-clockwise around `axis` (right-handed),
+// then normalizes them. Four rotations around the same axis give back the original set
+pub fn rotate_voxels_90(voxels: &VoxelGrid, axis: Axis) -> VoxelGrid {
+    let rotated = voxels.map_coords(|(x, y, z)| match axis {
+        Axis::X => (x, -z, y),
+        Axis::Y => (z, y, -x),
+        Axis::Z => (-y, x, z),
+    });
+    normalize_voxels(&rotated)
+}
+
+// Rotates a whole model inside its grid, swapping the grid dimensions that trade places.
+// The model keeps its place relative to the grid instead of being pushed to the corner
+pub fn rotate_model(voxel_data: &VoxelData, axis: Axis) -> VoxelData {
+    let mut shape = voxel_data.shape.clone();
+    let Some((min, max)) = voxel_data.voxels.bounding_box() else {
+        return voxel_data.clone();
+    };
+
+    let (w, h, d) = (shape.width as i32, shape.height as i32, shape.depth as i32);
+    // Where the rotated bounding box starts in the rotated grid
+    let offset = match axis {
+        Axis::X => (min.0, d - 1 - max.2, min.1),
+        Axis::Y => (min.2, min.1, w - 1 - max.0),
+        Axis::Z => (h - 1 - max.1, min.0, min.2),
+    };
+    match axis {
+        Axis::X => std::mem::swap(&mut shape.height, &mut shape.depth),
+        Axis::Y => std::mem::swap(&mut shape.width, &mut shape.depth),
+        Axis::Z => std::mem::swap(&mut shape.width, &mut shape.height),
+    }
+
+    let rotated = rotate_voxels_90(&voxel_data.voxels, axis);
+    VoxelData {
+        shape,
+        voxels: translate_voxels(&rotated, offset.0, offset.1, offset.2),
+    }
+}
+
+// Changes the grid size without regenerating: voxels keep their coordinates and any that
+// fall outside the new bounds are dropped
+pub fn resize_voxels(voxel_data: &VoxelData, width: u32, height: u32, depth: u32) -> VoxelData {
+    let (width, height, depth) = (width as i32, height as i32, depth as i32);
+    VoxelData {
+        shape: Shape {
+            width: width as u32,
+            height: height as u32,
+            depth: depth as u32,
+            ..voxel_data.shape.clone()
+        },
+        voxels: voxel_data.voxels.filtered(|&(x, y, z)| {
+            (0..width).contains(&x) && (0..height).contains(&y) && (0..depth).contains(&z)
+        }),
+    }
+}
+
+// Splits every voxel into a `factor`^3 block, multiplying the grid size by `factor`. Sides
+// that would grow past MAX_DIMENSION are capped and the blocks beyond them cut off.
+pub fn subdivide_voxels(voxel_data: &VoxelData, factor: u32) -> VoxelData {
+    let factor = factor.max(1);
+    let shape = Shape {
+        width: (voxel_data.shape.width * factor).min(MAX_DIMENSION),
+        height: (voxel_data.shape.height * factor).min(MAX_DIMENSION),
+        depth: (voxel_data.shape.depth * factor).min(MAX_DIMENSION),
+        ..voxel_data.shape.clone()
+    };
+    let f = factor as i32;
+    let (width, height, depth) = (shape.width as i32, shape.height as i32, shape.depth as i32);
+    let mut voxels = VoxelGrid::new();
+    for ((x, y, z), color) in voxel_data.voxels.colored() {
+        for sx in (x * f..x * f + f).filter(|sx| (0..width).contains(sx)) {
+            for sy in (y * f..y * f + f).filter(|sy| (0..height).contains(sy)) {
+                for sz in (z * f..z * f + f).filter(|sz| (0..depth).contains(sz)) {
+                    voxels.insert_colored((sx, sy, sz), color);
+                }
+            }
+        }
+    }
+    VoxelData { shape, voxels }
+}
+
+// --- Placement ---
+
+// World position of the center of `voxel` in a grid of `shape`'s size. Index (size - 1) / 2
+// lands on 0 on every axis, so a grid is centered whether its sides are odd or even and the
+// voxel of a 1x1x1 grid sits exactly at the origin. Voxel corners are half a voxel away.
+pub fn voxel_world_position(voxel: VoxelCoord, shape: &Shape) -> Vec3 {
+    let (x, y, z) = voxel;
+    let size = Vec3::new(shape.width as f32, shape.height as f32, shape.depth as f32);
+    Vec3::new(x as f32, y as f32, z as f32) - (size - 1.0) / 2.0
+}
+
+// --- Analysis ---
+
+// Smallest and largest occupied coordinate on each axis, or None for an empty model
+pub fn bounding_box<'a>(
+    voxels: impl IntoIterator<Item = &'a VoxelCoord>,
+) -> Option<(VoxelCoord, VoxelCoord)> {
+    let mut voxels = voxels.into_iter();
+    let first = voxels.next()?;
+    let mut min = *first;
+    let mut max = *first;
+    for &(x, y, z) in voxels {
+        min = (min.0.min(x), min.1.min(y), min.2.min(z));
+        max = (max.0.max(x), max.1.max(y), max.2.max(z));
+    }
+    Some((min, max))
+}
+
+// Number of voxel faces not covered by a neighboring voxel, i.e. the faces an exporter has to write
+pub fn count_exposed_faces(voxel_data: &VoxelData) -> usize {
+    let voxels = &voxel_data.voxels;
+    voxels
+        .iter()
+        .map(|&voxel| voxels.neighbors_empty(voxel).iter().filter(|&&empty| empty).count())
+        .sum()
+}
@@ -0,0 +1,1653 @@
+use crate::export::{
+    export_obj_to_writer, export_scene_to_obj, export_to_ply, load_vxl, parse_voxel_list, save_vxl,
+    scene_to_vxl_layers, vxl_layers_to_scene,
+};
+use crate::history::History;
+use crate::import::import_file;
+use crate::keymap::{Action, KeyMap, KEYBINDINGS_PATH};
+use crate::morph::MorphAnimation;
+use crate::noise;
+use crate::presets::{Preset, PresetLibrary};
+use crate::sculpt::{
+    BrushShape, Clipboard, MoveMode, Selection, MAX_BRUSH_RADIUS, MAX_EXTRUDE_DISTANCE,
+};
+use crate::shapes::{
+    apply_symmetry, combine_with_shape, connected_region, palette_color, pocket_walls,
+    resize_voxels, rotate_model, stair_steps, subdivide_voxels, Axis, BoolOp, ClimbDirection,
+    GeometricShape, SceneData, SceneShape, Shape, VoxelCoord, VoxelData, MAX_DIMENSION,
+    MAX_FRACTAL_ITERATIONS, MAX_GEAR_TEETH, MAX_GRID_CELLS, MAX_HELIX_TURNS, MAX_KNOT_WINDINGS,
+    MAX_LETTER_SPACING, MAX_PRISM_SIDES, MAX_ROUNDNESS, MAX_TERRAIN_FREQUENCY, MIN_DIMENSION,
+    MIN_GEAR_TEETH, MIN_PRISM_SIDES, MIN_ROUNDNESS, NEIGHBOR_OFFSETS, PALETTE, UNPAINTED,
+};
+use crate::{
+    CameraMode, CameraProjection, CameraView, ExportJob, ImportScale, MaterialPreset, ResetCamera,
+    ScreenshotResult, ScreenshotState, SculptTool, SliceTool, UserInput, ViewPreset, VoxelDirty,
+    VoxelStats, EMPTY_SHAPE_ERROR, EXPORT_PATH, LARGE_GRID_WARNING_CELLS, MAX_IMPORT_RESOLUTION,
+    MIN_IMPORT_RESOLUTION, PLY_EXPORT_PATH, SELECTION_MOVES,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use strum::IntoEnumIterator;
+
+// Rough output size per exposed face: four "v x y z" lines plus one "f a b c d" line
+const OBJ_BYTES_PER_FACE: usize = 4 * 28 + 24;
+// Binary STL: 80 byte header + triangle count, then 50 bytes per triangle (two per face)
+const STL_HEADER_BYTES: usize = 84;
+const STL_BYTES_PER_FACE: usize = 2 * 50;
+// Side length of the square area the slice grid is drawn into
+const SLICE_VIEW_SIZE: f32 = 200.0;
+const SLICE_EMPTY_COLOR: egui::Color32 = egui::Color32::from_gray(40);
+const MIN_SLIDER_MAX: u32 = 8;
+// Button labels for the SELECTION_MOVES directions, in the same order
+const DIRECTION_LABELS: [&str; 6] = ["-X", "+X", "-Y", "+Y", "-Z", "+Z"];
+
+#[allow(clippy::too_many_arguments)]
+pub fn ui_system(
+    mut contexts: EguiContexts,
+    mut user_input: ResMut<UserInput>,
+    mut voxel_data: ResMut<VoxelData>,
+    stats: Res<VoxelStats>,
+    mut screenshot: ResMut<ScreenshotState>,
+    mut reset_camera: EventWriter<ResetCamera>,
+    mut scene: ResMut<SceneData>,
+    mut morph: ResMut<MorphAnimation>,
+    keymap: Res<KeyMap>,
+    mut presets: ResMut<PresetLibrary>,
+    mut export_job: ResMut<ExportJob>,
+    mut camera_view: ResMut<CameraView>,
+    mut selection: ResMut<Selection>,
+    mut clipboard: ResMut<Clipboard>,
+    mut history: ResMut<History>,
+    mut dirty: ResMut<VoxelDirty>,
+) {
+    if screenshot.ui_hidden {
+        return;
+    }
+
+    egui::Window::new("Sculptor Controls").show(contexts.ctx_mut(), |ui| {
+        let mut changed = false;
+
+        if let Some(message) = user_input.error_message.clone() {
+            ui.colored_label(egui::Color32::RED, message);
+            if ui.button("Dismiss").clicked() {
+                user_input.error_message = None;
+            }
+            ui.separator();
+        }
+        if let Some(message) = user_input.status_message.clone() {
+            ui.horizontal(|ui| {
+                ui.label(message);
+                if ui.small_button("Dismiss").clicked() {
+                    user_input.status_message = None;
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            let id = scene.selected_object().id;
+            for (action, label, redo, enabled) in [
+                (Action::Undo, "Undo", false, history.can_undo(id)),
+                (Action::Redo, "Redo", true, history.can_redo(id)),
+            ] {
+                let button = ui.add_enabled(enabled, egui::Button::new(label));
+                if button.on_hover_text(keymap.describe(action)).clicked() {
+                    step_history(
+                        redo,
+                        &mut history,
+                        &scene,
+                        &mut user_input,
+                        &mut voxel_data,
+                        &mut dirty,
+                    );
+                }
+            }
+        });
+
+        egui::CollapsingHeader::new("Objects").default_open(true).show(ui, |ui| {
+            object_list(ui, &mut user_input, &mut voxel_data, &mut scene, &mut dirty);
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.heading("Dimensions");
+            let mut locked = user_input.aspect_lock.is_some();
+            if ui.checkbox(&mut locked, "Lock aspect ratio").changed() {
+                let shape = &user_input.shape;
+                user_input.aspect_lock = locked.then_some([shape.width, shape.depth, shape.height]);
+            }
+        });
+
+        let max = user_input.max_dimension;
+        let old = [user_input.shape.width, user_input.shape.depth, user_input.shape.height];
+        let mut dimensions = old;
+        for (value, label) in dimensions.iter_mut().zip(["Width", "Depth", "Height"]) {
+            ui.add(egui::Slider::new(value, MIN_DIMENSION..=max).text(label));
+        }
+        let moved = (0..3).find(|&axis| dimensions[axis] != old[axis]);
+        if let (Some(base), Some(axis)) = (user_input.aspect_lock, moved) {
+            dimensions = scale_locked(base, axis, dimensions[axis], max);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Slider max:");
+            let mut current_max = max;
+            ui.add(egui::DragValue::new(&mut current_max).clamp_range(MIN_SLIDER_MAX..=MAX_DIMENSION));
+            if current_max != max {
+                user_input.max_dimension = current_max;
+                dimensions = dimensions.map(|side| side.min(current_max));
+            }
+        });
+
+        if dimensions != old {
+            [user_input.shape.width, user_input.shape.depth, user_input.shape.height] = dimensions;
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Confirm above:");
+            let confirm_cells = egui::DragValue::new(&mut user_input.confirm_cells)
+                .clamp_range(1..=MAX_GRID_CELLS)
+                .suffix(" cells");
+            ui.add(confirm_cells)
+                .on_hover_text("Larger grids wait for \"Generate\" instead of updating right away");
+        });
+
+        let cells = user_input.shape.cell_count();
+        let cells_label = format!("Up to {} voxels", cells);
+        if cells > MAX_GRID_CELLS {
+            ui.colored_label(egui::Color32::RED, format!("{} (limit {})", cells_label, MAX_GRID_CELLS));
+        } else if cells > user_input.confirm_cells {
+            ui.colored_label(egui::Color32::YELLOW, cells_label)
+                .on_hover_text("Large grids take a while to generate and render");
+        } else {
+            ui.label(cells_label);
+        }
+        let allowed = cells <= MAX_GRID_CELLS;
+        if allowed && cells > LARGE_GRID_WARNING_CELLS {
+            ui.colored_label(egui::Color32::YELLOW, "Large grid: generating will be slow")
+                .on_hover_text("Large models are drawn as merged layer meshes");
+        }
+        let generate = egui::Button::new("Generate");
+        if user_input.large_grid_pending && ui.add_enabled(allowed, generate).clicked() {
+            user_input.large_grid_pending = false;
+            user_input.error_message = None;
+            user_input.needs_regeneration = true;
+        }
+
+        ui.separator();
+        ui.heading("Shape");
+
+        // Shape Dropdown (Combo Box)
+        let selected_shape_label = user_input.shape.kind.to_string();
+        egui::ComboBox::from_label("Select Shape")
+            .selected_text(selected_shape_label)
+            .show_ui(ui, |ui| {
+                for shape in GeometricShape::iter() {
+                    if ui
+                        .selectable_value(&mut user_input.shape.kind, shape, shape.to_string())
+                        .clicked()
+                    {
+                        changed = true;
+                    }
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.label("Primary Axis:");
+            for axis in Axis::iter() {
+                if ui.radio_value(&mut user_input.shape.axis, axis, axis.to_string()).clicked() {
+                    changed = true;
+                }
+            }
+        });
+
+        if user_input.shape.kind.uses_seed() {
+            ui.horizontal(|ui| {
+                let mut current_seed = user_input.shape.seed;
+                ui.label("Seed:");
+                ui.add(egui::DragValue::new(&mut current_seed));
+                if ui.button("Randomize Seed").clicked() {
+                    current_seed = time_seed();
+                }
+                if current_seed != user_input.shape.seed {
+                    user_input.shape.seed = current_seed;
+                    changed = true;
+                }
+            });
+        }
+
+        if user_input.shape.kind.uses_threshold() {
+            let mut current_threshold = user_input.shape.threshold;
+            ui.add(egui::Slider::new(&mut current_threshold, 0.2..=0.8).text("Threshold"));
+            if current_threshold != user_input.shape.threshold {
+                user_input.shape.threshold = current_threshold;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_heightfield() {
+            let mut field = [user_input.shape.frequency, user_input.shape.amplitude];
+            ui.add(egui::Slider::new(&mut field[0], 0.5..=MAX_TERRAIN_FREQUENCY).text("Frequency"))
+                .on_hover_text("About how many hills fit across the grid");
+            ui.add(egui::Slider::new(&mut field[1], 0.0..=1.0).text("Amplitude"))
+                .on_hover_text("How much of the height the hills span; 0 is flat");
+            if field != [user_input.shape.frequency, user_input.shape.amplitude] {
+                [user_input.shape.frequency, user_input.shape.amplitude] = field;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_sides() {
+            let mut current_sides = user_input.shape.sides;
+            let star = user_input.shape.kind == GeometricShape::Star;
+            let slider = egui::Slider::new(&mut current_sides, MIN_PRISM_SIDES..=MAX_PRISM_SIDES);
+            ui.add(slider.text(if star { "Points" } else { "Sides" }));
+            if current_sides != user_input.shape.sides {
+                user_input.shape.sides = current_sides;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_tip_offset() {
+            let mut tip = [user_input.shape.tip_offset_x, user_input.shape.tip_offset_z];
+            for (value, label) in tip.iter_mut().zip(["Tip Offset X", "Tip Offset Z"]) {
+                ui.add(egui::Slider::new(value, -1.0..=1.0).text(label))
+                    .on_hover_text("Shifts the apex by this fraction of the base radius");
+            }
+            if tip != [user_input.shape.tip_offset_x, user_input.shape.tip_offset_z] {
+                [user_input.shape.tip_offset_x, user_input.shape.tip_offset_z] = tip;
+                changed = true;
+            }
+            let mut top_radius = user_input.shape.top_radius;
+            ui.add(egui::Slider::new(&mut top_radius, 0.0..=1.0).text("Top Radius"))
+                .on_hover_text("Fraction of the base radius; above 0 the cone is cut off flat");
+            if top_radius != user_input.shape.top_radius {
+                user_input.shape.top_radius = top_radius;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_climb() {
+            ui.horizontal(|ui| {
+                ui.label("Climb Towards:");
+                for climb in ClimbDirection::iter() {
+                    let label = climb.to_string();
+                    if ui.radio_value(&mut user_input.shape.climb, climb, label).clicked() {
+                        changed = true;
+                    }
+                }
+            });
+        }
+
+        if user_input.shape.kind.uses_steps() {
+            let mut current_steps = user_input.shape.steps;
+            ui.add(egui::Slider::new(&mut current_steps, 0..=max).text("Steps"))
+                .on_hover_text("0 gives one step per layer");
+            if current_steps != user_input.shape.steps {
+                user_input.shape.steps = current_steps;
+                changed = true;
+            }
+            let shape = &user_input.shape;
+            let steps = stair_steps(shape);
+            let run = shape.climb.run(shape.width, shape.depth);
+            ui.label(format!(
+                "{} steps, about {:.1} deep and {:.1} high",
+                steps,
+                run as f32 / steps as f32,
+                shape.height as f32 / steps as f32
+            ));
+        }
+
+        if user_input.shape.kind.uses_turns() {
+            let mut turns = user_input.shape.turns;
+            ui.add(egui::Slider::new(&mut turns, 0.5..=MAX_HELIX_TURNS).text("Turns"))
+                .on_hover_text("Full turns over the height; the pitch is Height / Turns");
+            if turns != user_input.shape.turns {
+                user_input.shape.turns = turns;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_iterations() {
+            let mut iterations = user_input.shape.iterations;
+            let slider = egui::Slider::new(&mut iterations, 0..=MAX_FRACTAL_ITERATIONS);
+            ui.add(slider.text("Iterations"))
+                .on_hover_text("Detail shows best on grids sized 3^n (Menger) or 2^n (Sierpinski)");
+            if iterations != user_input.shape.iterations {
+                user_input.shape.iterations = iterations;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_roundness() {
+            let mut roundness = [user_input.shape.roundness_y, user_input.shape.roundness_xz];
+            let labels = ["Vertical Roundness", "Horizontal Roundness"];
+            for (value, label) in roundness.iter_mut().zip(labels) {
+                ui.add(egui::Slider::new(value, MIN_ROUNDNESS..=MAX_ROUNDNESS).text(label))
+                    .on_hover_text("Near 0 is boxy, 1 is round and 2 is pointed");
+            }
+            if roundness != [user_input.shape.roundness_y, user_input.shape.roundness_xz] {
+                [user_input.shape.roundness_y, user_input.shape.roundness_xz] = roundness;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_teeth() {
+            let shape = &user_input.shape;
+            let mut gear = [shape.teeth, shape.tooth_depth, shape.bore_radius];
+            let narrow = shape.width.min(shape.depth) / 2;
+            ui.add(egui::Slider::new(&mut gear[0], MIN_GEAR_TEETH..=MAX_GEAR_TEETH).text("Teeth"));
+            ui.add(egui::Slider::new(&mut gear[1], 0..=narrow).text("Tooth Depth"));
+            ui.add(egui::Slider::new(&mut gear[2], 0..=narrow).text("Bore Radius"))
+                .on_hover_text("Radius of the hole for the axle; 0 leaves the gear solid");
+            let shape = &mut user_input.shape;
+            if gear != [shape.teeth, shape.tooth_depth, shape.bore_radius] {
+                [shape.teeth, shape.tooth_depth, shape.bore_radius] = gear;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_arms() {
+            let mut thickness = user_input.shape.arm_thickness;
+            ui.add(egui::Slider::new(&mut thickness, 1..=max).text("Arm Thickness"));
+            if thickness != user_input.shape.arm_thickness {
+                user_input.shape.arm_thickness = thickness;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_knot() {
+            let mut knot = [user_input.shape.knot_p, user_input.shape.knot_q];
+            ui.add(egui::Slider::new(&mut knot[0], 1..=MAX_KNOT_WINDINGS).text("P"))
+                .on_hover_text("Windings around the ring's axis");
+            ui.add(egui::Slider::new(&mut knot[1], 1..=MAX_KNOT_WINDINGS).text("Q"))
+                .on_hover_text("Windings through the ring's hole");
+            if knot != [user_input.shape.knot_p, user_input.shape.knot_q] {
+                [user_input.shape.knot_p, user_input.shape.knot_q] = knot;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_coil() {
+            let mut radius = user_input.shape.coil_radius;
+            ui.add(egui::Slider::new(&mut radius, 0.5..=8.0).text("Coil Radius"))
+                .on_hover_text("Radius of the coiled tube in voxels");
+            if radius != user_input.shape.coil_radius {
+                user_input.shape.coil_radius = radius;
+                changed = true;
+            }
+        }
+
+        if user_input.shape.kind.uses_expression() {
+            ui.horizontal(|ui| {
+                ui.label("Formula:");
+                if ui.text_edit_singleline(&mut user_input.shape.expression)
+                    .on_hover_text("Filled where positive; x, y, z run from -1 to 1. \
+                                    Supports + - * / ( ) sin cos sqrt abs and one > or <")
+                    .changed()
+                {
+                    changed = true;
+                }
+            });
+        }
+
+        if user_input.shape.kind.uses_text() {
+            ui.horizontal(|ui| {
+                ui.label("Text:");
+                if ui.text_edit_singleline(&mut user_input.shape.text).changed() {
+                    changed = true;
+                }
+            });
+            let mut spacing = user_input.shape.letter_spacing;
+            ui.add(egui::Slider::new(&mut spacing, 0..=MAX_LETTER_SPACING).text("Letter Spacing"))
+                .on_hover_text("Empty columns between characters; Depth sets the extrusion");
+            if spacing != user_input.shape.letter_spacing {
+                user_input.shape.letter_spacing = spacing;
+                changed = true;
+            }
+        }
+
+        // Wall thickness only applies to shapes that can be hollow inside
+        if user_input.shape.kind.uses_wall_thickness() {
+            let min_wall = if user_input.shape.kind == GeometricShape::Tube { 1 } else { 0 };
+            let mut current_wall = user_input.shape.wall_thickness.max(min_wall);
+            ui.add(egui::Slider::new(&mut current_wall, min_wall..=max / 2).text("Wall Thickness"))
+                .on_hover_text("Shell thickness in voxels; 0 keeps the shape solid");
+            if current_wall != user_input.shape.wall_thickness {
+                user_input.shape.wall_thickness = current_wall;
+                changed = true;
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Symmetry:")
+                .on_hover_text("Mirrors the generated shape and anything combined into it");
+            let symmetry = &mut user_input.symmetry;
+            changed |= ui.checkbox(&mut symmetry.x, "X").changed();
+            changed |= ui.checkbox(&mut symmetry.y, "Y").changed();
+            changed |= ui.checkbox(&mut symmetry.z, "Z").changed();
+        });
+
+        if ui.checkbox(&mut user_input.hollow, "Hollow").changed() {
+            changed = true;
+        }
+
+        if ui
+            .add(egui::Slider::new(&mut user_input.erosion, 0.0..=1.0).text("Erosion"))
+            .on_hover_text("Randomly removes surface voxels for a rough, rocky look")
+            .changed()
+        {
+            changed = true;
+        }
+        if user_input.erosion > 0.0 {
+            ui.horizontal(|ui| {
+                ui.label("Erosion Seed:");
+                if ui.add(egui::DragValue::new(&mut user_input.erosion_seed)).changed() {
+                    changed = true;
+                }
+                if ui.button("Reroll").clicked() {
+                    user_input.erosion_seed = time_seed();
+                    changed = true;
+                }
+            });
+        }
+
+        ui.collapsing("Presets", |ui| {
+            changed |= preset_controls(ui, &mut user_input, &mut presets);
+        });
+
+        ui.collapsing("Scene Shapes", |ui| {
+            ui.label("Extra shapes added to the main one at an offset");
+            let mut removed = None;
+            for (index, scene_shape) in user_input.scene_shapes.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("kind")
+                            .selected_text(scene_shape.shape.kind.to_string())
+                            .show_ui(ui, |ui| {
+                                for shape in GeometricShape::iter() {
+                                    changed |= ui
+                                        .selectable_value(&mut scene_shape.shape.kind, shape, shape.to_string())
+                                        .changed();
+                                }
+                            });
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                    let shape = &mut scene_shape.shape;
+                    for (value, label) in [
+                        (&mut shape.width, "Width"),
+                        (&mut shape.depth, "Depth"),
+                        (&mut shape.height, "Height"),
+                    ] {
+                        changed |= ui
+                            .add(egui::Slider::new(value, MIN_DIMENSION..=max).text(label))
+                            .changed();
+                    }
+                    let offset = &mut scene_shape.offset;
+                    for (value, label) in [
+                        (&mut offset.0, "Offset X"),
+                        (&mut offset.1, "Offset Y"),
+                        (&mut offset.2, "Offset Z"),
+                    ] {
+                        changed |= ui.add(egui::Slider::new(value, -32..=32).text(label)).changed();
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                user_input.scene_shapes.remove(index);
+                changed = true;
+            }
+            if ui.button("Add Shape").clicked() {
+                user_input.scene_shapes.push(SceneShape::default());
+                changed = true;
+            }
+        });
+
+        if changed {
+            request_regeneration(&mut user_input);
+        }
+
+        ui.collapsing("Combine", |ui| {
+            let second = &mut user_input.second_shape;
+            egui::ComboBox::from_label("Second Shape")
+                .selected_text(second.kind.to_string())
+                .show_ui(ui, |ui| {
+                    for shape in GeometricShape::iter() {
+                        ui.selectable_value(&mut second.kind, shape, shape.to_string());
+                    }
+                });
+            ui.add(egui::Slider::new(&mut second.width, MIN_DIMENSION..=max).text("Width"));
+            ui.add(egui::Slider::new(&mut second.depth, MIN_DIMENSION..=max).text("Depth"));
+            ui.add(egui::Slider::new(&mut second.height, MIN_DIMENSION..=max).text("Height"));
+            let offset = &mut user_input.second_offset;
+            for (value, label) in [
+                (&mut offset.0, "Offset X"),
+                (&mut offset.1, "Offset Y"),
+                (&mut offset.2, "Offset Z"),
+            ] {
+                ui.add(egui::Slider::new(value, -32..=32).text(label))
+                    .on_hover_text("Cells from the grid center; a union grows the grid to fit");
+            }
+
+            egui::ComboBox::from_label("Operation")
+                .selected_text(user_input.bool_op.to_string())
+                .show_ui(ui, |ui| {
+                    for op in BoolOp::iter() {
+                        ui.selectable_value(&mut user_input.bool_op, op, op.to_string());
+                    }
+                });
+
+            if ui.button("Combine").clicked() {
+                let combined = combine_with_shape(
+                    &voxel_data,
+                    &user_input.second_shape,
+                    user_input.second_offset,
+                    user_input.bool_op,
+                    user_input.symmetry,
+                );
+                let cells = combined.shape.cell_count();
+                let shape = &combined.shape;
+                let largest = shape.width.max(shape.height).max(shape.depth);
+                if combined.voxels.is_empty() {
+                    user_input.error_message = Some(EMPTY_SHAPE_ERROR.into());
+                } else if largest > MAX_DIMENSION {
+                    user_input.error_message = Some(format!(
+                        "The combined grid would be {}x{}x{}, more than the {} voxel limit per axis",
+                        shape.width, shape.height, shape.depth, MAX_DIMENSION
+                    ));
+                } else if cells > MAX_GRID_CELLS {
+                    user_input.error_message = Some(format!(
+                        "The combined grid would have {} cells, over the limit of {}",
+                        cells, MAX_GRID_CELLS
+                    ));
+                } else {
+                    *voxel_data = combined;
+                    dirty.0 = true;
+                }
+            }
+        });
+
+        ui.collapsing("Resize Canvas", |ui| {
+            ui.label("Changes the grid size but keeps the voxels; anything outside is cut off.");
+            let (width, height, depth) = &mut user_input.canvas_size;
+            ui.add(egui::Slider::new(width, MIN_DIMENSION..=max).text("Width"));
+            ui.add(egui::Slider::new(depth, MIN_DIMENSION..=max).text("Depth"));
+            ui.add(egui::Slider::new(height, MIN_DIMENSION..=max).text("Height"));
+            if ui.button("Resize Canvas").clicked() {
+                let (width, height, depth) = user_input.canvas_size;
+                let resized = resize_voxels(&voxel_data, width, height, depth);
+                *voxel_data = resized;
+                dirty.0 = true;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Rotate 90°:");
+            for axis in Axis::iter() {
+                if ui.button(axis.to_string()).clicked() {
+                    let rotated = rotate_model(&voxel_data, axis);
+                    *voxel_data = rotated;
+                    dirty.0 = true;
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Subdivide:")
+                .on_hover_text("Splits every voxel into smaller ones for finer editing");
+            for factor in [2, 4] {
+                if ui.button(format!("×{}", factor)).clicked() {
+                    let shape = &voxel_data.shape;
+                    let largest = shape.width.max(shape.height).max(shape.depth);
+                    if largest * factor > MAX_DIMENSION {
+                        user_input.error_message = Some(format!(
+                            "×{} is capped at {} voxels per side; the rest is cut off",
+                            factor, MAX_DIMENSION
+                        ));
+                    }
+                    let subdivided = subdivide_voxels(&voxel_data, factor);
+                    *voxel_data = subdivided;
+                    dirty.0 = true;
+                }
+            }
+        });
+
+        ui.collapsing("Morph", |ui| {
+            morph_controls(ui, &mut morph, &voxel_data);
+        });
+
+        ui.horizontal(|ui| {
+            // Exports run in the background; one at a time
+            let idle = !export_job.is_running();
+            if ui.add_enabled(idle, egui::Button::new("Export OBJ")).clicked() {
+                export_scene(&user_input, &scene, &mut export_job);
+            }
+
+            if ui.add_enabled(idle, egui::Button::new("Export PLY")).clicked() {
+                let (data, binary) = (voxel_data.clone(), user_input.ply_binary);
+                let base_color = user_input.lighting.base_color;
+                export_job.start(PLY_EXPORT_PATH, vec![PLY_EXPORT_PATH.into()], move || {
+                    export_to_ply(&data, PLY_EXPORT_PATH, base_color, binary)
+                });
+            }
+
+            #[cfg(feature = "gltf-export")]
+            if ui.add_enabled(idle, egui::Button::new("Export as GLTF")).clicked() {
+                let path = crate::GLTF_EXPORT_PATH;
+                let files = vec![path.into(), Path::new(path).with_extension("bin")];
+                let (data, optimize) = (voxel_data.clone(), user_input.optimize_mesh);
+                let base_color = user_input.lighting.base_color;
+                export_job.start(path, files, move || {
+                    crate::export::export_to_gltf(&data, path, base_color, optimize)
+                });
+            }
+
+            #[cfg(feature = "gltf-export")]
+            if ui.add_enabled(idle, egui::Button::new("Export as GLB")).clicked() {
+                let path = crate::GLB_EXPORT_PATH;
+                let (data, optimize) = (voxel_data.clone(), user_input.optimize_mesh);
+                let base_color = user_input.lighting.base_color;
+                export_job.start(path, vec![path.into()], move || {
+                    crate::export::export_to_glb(&data, path, base_color, optimize)
+                });
+            }
+
+            ui.checkbox(&mut user_input.merge_on_export, "Merge objects")
+                .on_hover_text("Export all visible objects as one mesh instead of one OBJ object each");
+            ui.checkbox(&mut user_input.optimize_mesh, "Optimize mesh")
+                .on_hover_text("Merge flat areas into large quads for far fewer faces");
+            ui.checkbox(&mut user_input.ply_binary, "Binary PLY");
+
+            if let Some(path) = export_job.path() {
+                ui.spinner();
+                if export_job.is_cancelling() {
+                    ui.label(format!("Cancelling export to {}...", path));
+                } else {
+                    ui.label(format!("Exporting {}...", path));
+                    if ui.button("Cancel").clicked() {
+                        export_job.cancel();
+                    }
+                }
+            }
+
+            if ui.button("Copy OBJ to clipboard").clicked() {
+                let mut buffer = Vec::new();
+                match export_obj_to_writer(&voxel_data, &mut buffer, user_input.optimize_mesh) {
+                    Ok(()) => {
+                        let text = String::from_utf8_lossy(&buffer).into_owned();
+                        ui.ctx().output_mut(|o| o.copied_text = text);
+                    }
+                    Err(e) => user_input.error_message = Some(format!("Copy failed: {}", e)),
+                }
+            }
+        });
+
+        ui.collapsing("Paste voxel list", |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut user_input.paste_text)
+                    .hint_text("One \"x y z\" per line")
+                    .desired_rows(4),
+            );
+            if ui.button("Replace voxels").clicked() {
+                match parse_voxel_list(&user_input.paste_text, &voxel_data.shape) {
+                    Ok(pasted) => {
+                        *voxel_data = pasted;
+                        dirty.0 = true;
+                    }
+                    Err(e) => user_input.error_message = Some(e),
+                }
+            }
+        });
+
+        ui.collapsing("Scene File (.vxl)", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                ui.text_edit_singleline(&mut user_input.vxl_path);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Save .vxl").clicked() {
+                    let layers = scene_to_vxl_layers(&scene, |y, height, paint| {
+                        voxel_color(&user_input, y, height, paint)
+                    });
+                    match save_vxl(&layers, user_input.vxl_path.trim()) {
+                        Ok(()) => {
+                            let path = user_input.vxl_path.trim();
+                            user_input.status_message = Some(format!("Saved scene to {}", path));
+                        }
+                        Err(e) => {
+                            user_input.error_message =
+                                Some(format!("Saving {} failed: {}", user_input.vxl_path.trim(), e));
+                        }
+                    }
+                }
+                if ui.button("Load .vxl").clicked() {
+                    let loaded = load_vxl(user_input.vxl_path.trim())
+                        .map_err(|e| format!("Loading {} failed: {}", user_input.vxl_path.trim(), e))
+                        .and_then(|layers| {
+                            vxl_layers_to_scene(&layers, &user_input.shape)
+                                .ok_or_else(|| "The file contains no voxels".to_string())
+                        });
+                    match loaded {
+                        Ok(loaded) => {
+                            // Object ids start over, so the old history would apply to the
+                            // wrong objects
+                            history.clear();
+                            *scene = loaded;
+                            load_selected_object(&scene, &mut user_input, &mut voxel_data);
+                            dirty.0 = true;
+                        }
+                        Err(e) => user_input.error_message = Some(e),
+                    }
+                }
+            });
+        });
+
+        ui.collapsing("Import", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                ui.add(egui::TextEdit::singleline(&mut user_input.import_path).hint_text("model.vox or model.obj"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("OBJ size:");
+                for (scale, label) in [
+                    (ImportScale::MeshUnits, "1 voxel per unit"),
+                    (ImportScale::CurrentSize, "Current dimensions"),
+                    (ImportScale::Resolution, "Resolution"),
+                ] {
+                    ui.selectable_value(&mut user_input.import_scale, scale, label);
+                }
+            });
+            ui.add_enabled(
+                user_input.import_scale == ImportScale::Resolution,
+                egui::Slider::new(
+                    &mut user_input.import_resolution,
+                    MIN_IMPORT_RESOLUTION..=MAX_IMPORT_RESOLUTION,
+                )
+                .text("Longest side"),
+            );
+            if ui.button("Import").clicked() {
+                let shape = &user_input.shape;
+                let resolution = user_input.import_resolution;
+                let fit = match user_input.import_scale {
+                    ImportScale::MeshUnits => None,
+                    ImportScale::CurrentSize => Some((shape.width, shape.height, shape.depth)),
+                    ImportScale::Resolution => Some((resolution, resolution, resolution)),
+                };
+                match import_file(user_input.import_path.trim(), shape, fit) {
+                    Ok(imported) => {
+                        // Keep the sliders in sync without triggering a regeneration
+                        user_input.shape.width = imported.shape.width;
+                        user_input.shape.height = imported.shape.height;
+                        user_input.shape.depth = imported.shape.depth;
+                        *voxel_data = imported;
+                        dirty.0 = true;
+                    }
+                    Err(e) => user_input.error_message = Some(e),
+                }
+            }
+        });
+
+        ui.separator();
+        ui.heading("Sculpt");
+        ui.horizontal(|ui| {
+            ui.label("Click tool:");
+            for tool in SculptTool::iter() {
+                ui.selectable_value(&mut user_input.sculpt_tool, tool, tool.to_string());
+            }
+        })
+        .response
+        .on_hover_text(
+            "Left-click the model to edit it, Shift-click to erase with Add and Alt-click to \
+             pick a voxel's color",
+        );
+        ui.horizontal(|ui| {
+            ui.label("Mirror edits:")
+                .on_hover_text("Repeat every sculpt edit across the grid's center planes");
+            let mirror = &mut user_input.mirror_edits;
+            ui.checkbox(&mut mirror.x, "X");
+            ui.checkbox(&mut mirror.y, "Y");
+            ui.checkbox(&mut mirror.z, "Z");
+        });
+        if user_input.sculpt_tool == SculptTool::Brush {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut user_input.brush_erase, false, "Add");
+                ui.selectable_value(&mut user_input.brush_erase, true, "Remove");
+                ui.separator();
+                for shape in BrushShape::iter() {
+                    ui.selectable_value(&mut user_input.brush_shape, shape, shape.to_string());
+                }
+            });
+            let radius = egui::Slider::new(&mut user_input.brush_radius, 1..=MAX_BRUSH_RADIUS);
+            ui.add(radius.text("Radius"))
+                .on_hover_text("1 is a single voxel; drag across the model to paint strokes");
+        }
+        if user_input.sculpt_tool == SculptTool::Fill {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut user_input.fill_recolor, false, "Empty space")
+                    .on_hover_text("Fill the connected empty cells, within the selection if any");
+                ui.selectable_value(&mut user_input.fill_recolor, true, "Recolor")
+                    .on_hover_text("Paint the connected voxels of the clicked voxel's color");
+            });
+        }
+        if user_input.sculpt_tool == SculptTool::Extrude {
+            let distance =
+                egui::Slider::new(&mut user_input.extrude_distance, 1..=MAX_EXTRUDE_DISTANCE);
+            ui.add(distance.text("Extrude distance"))
+                .on_hover_text("Layers of voxels a click adds in front of the clicked faces");
+        }
+
+        ui.horizontal(|ui| {
+            match selection.bounds {
+                Some(_) => {
+                    let (w, h, d) = selection.size();
+                    let count = voxel_data.voxels.iter().filter(|v| selection.contains(v)).count();
+                    ui.label(format!("Selection: {}×{}×{}, {} voxels", w, h, d, count));
+                }
+                None => {
+                    ui.label("No selection");
+                }
+            }
+            if ui.button("Select all").clicked() {
+                *selection = Selection::all(&voxel_data.shape);
+            }
+        });
+        ui.add_enabled_ui(selection.bounds.is_some(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Delete").on_hover_text("Remove the selected voxels").clicked() {
+                    selection.delete(&mut voxel_data.voxels);
+                    dirty.0 = true;
+                }
+                if ui.button("Fill").on_hover_text("Fill the box with the paint color").clicked() {
+                    let shape = voxel_data.shape.clone();
+                    selection.fill(&mut voxel_data.voxels, &shape, user_input.paint_color);
+                    dirty.0 = true;
+                }
+                if ui.button("Clear selection").clicked() {
+                    selection.clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Copy").on_hover_text(keymap.describe(Action::Copy)).clicked() {
+                    *clipboard = selection.copy(&voxel_data.voxels);
+                }
+                if ui.button("Cut").on_hover_text(keymap.describe(Action::Cut)).clicked() {
+                    *clipboard = selection.copy(&voxel_data.voxels);
+                    selection.delete(&mut voxel_data.voxels);
+                    dirty.0 = true;
+                }
+                let hover = format!(
+                    "Paste a copy by the offset below and select it ({})",
+                    keymap.describe(Action::Duplicate)
+                );
+                if ui.button("Duplicate").on_hover_text(hover).clicked() {
+                    let VoxelData { shape, voxels } = &mut *voxel_data;
+                    selection.duplicate(voxels, shape, user_input.duplicate_offset);
+                    dirty.0 = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Extrude side:").on_hover_text(
+                    "Pull a side of the selection outward, repeating the voxels on it",
+                );
+                for ((_, normal), label) in SELECTION_MOVES.into_iter().zip(DIRECTION_LABELS) {
+                    if ui.button(label).clicked() {
+                        let VoxelData { shape, voxels } = &mut *voxel_data;
+                        let distance = user_input.extrude_distance;
+                        dirty.0 |= selection.extrude(voxels, shape, normal, distance) > 0;
+                    }
+                }
+                let distance = egui::DragValue::new(&mut user_input.extrude_distance)
+                    .clamp_range(1..=MAX_EXTRUDE_DISTANCE)
+                    .suffix(" layers");
+                ui.add(distance);
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Duplicate offset:");
+            let offset = &mut user_input.duplicate_offset;
+            ui.add(egui::DragValue::new(&mut offset.0).prefix("X "));
+            ui.add(egui::DragValue::new(&mut offset.1).prefix("Y "));
+            ui.add(egui::DragValue::new(&mut offset.2).prefix("Z "));
+        });
+        ui.add_enabled_ui(!clipboard.is_empty(), |ui| {
+            ui.horizontal(|ui| {
+                let hover = format!(
+                    "Pick the Paste tool and click where the copy's corner goes ({})",
+                    keymap.describe(Action::Paste)
+                );
+                if ui.button("Paste").on_hover_text(hover).clicked() {
+                    user_input.sculpt_tool = SculptTool::Paste;
+                }
+                let ((w, h, d), count) = (clipboard.size, clipboard.voxels.len());
+                ui.label(format!("Clipboard: {}×{}×{}, {} voxels", w, h, d, count));
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Move:")
+                .on_hover_text("Move the selection, or the whole model without one, by a voxel");
+            for ((action, delta), label) in SELECTION_MOVES.into_iter().zip(DIRECTION_LABELS) {
+                if ui.button(label).on_hover_text(keymap.describe(action)).clicked() {
+                    let VoxelData { shape, voxels } = &mut *voxel_data;
+                    dirty.0 |= selection.nudge(voxels, shape, delta, user_input.move_mode);
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            for mode in MoveMode::iter() {
+                let hover = match mode {
+                    MoveMode::Clip => "Drop voxels moved out of the grid and replace the ones hit",
+                    MoveMode::Wrap => "Voxels leaving one side of the box come back on the other",
+                    MoveMode::Push => "Only move into empty cells inside the grid",
+                };
+                ui.selectable_value(&mut user_input.move_mode, mode, mode.to_string())
+                    .on_hover_text(hover);
+            }
+        });
+
+        ui.separator();
+        ui.heading("View");
+
+        ui.add(egui::Slider::new(&mut user_input.voxel_size, 0.1..=1.0).text("Voxel Gap"))
+            .on_hover_text("Size of each rendered cube; smaller values leave gaps between voxels");
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut user_input.wireframe, "Wireframe");
+            ui.checkbox(&mut user_input.show_edges, "Show voxel edges")
+                .on_hover_text("Outline every voxel in black");
+        });
+
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgb(&mut user_input.background_color);
+            ui.label("Background");
+        });
+
+        ui.collapsing("Lighting & Material", |ui| {
+            ui.add(
+                egui::Slider::new(&mut user_input.ambient_brightness, 0.0..=3.0)
+                    .text("Ambient Light"),
+            );
+            let lighting = &mut user_input.lighting;
+            ui.add(egui::Slider::new(&mut lighting.light_yaw, -180.0..=180.0).text("Light Yaw (°)"));
+            ui.add(egui::Slider::new(&mut lighting.light_pitch, -90.0..=90.0).text("Light Pitch (°)"));
+            ui.add(
+                egui::Slider::new(&mut lighting.illuminance, 0.0..=100_000.0)
+                    .logarithmic(true)
+                    .text("Illuminance (lux)"),
+            );
+            ui.checkbox(&mut lighting.shadows, "Shadows");
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgb(&mut lighting.base_color);
+                ui.label("Voxel Color");
+            });
+            ui.add(egui::Slider::new(&mut lighting.roughness, 0.0..=1.0).text("Roughness"));
+            ui.add(egui::Slider::new(&mut lighting.metallic, 0.0..=1.0).text("Metallic"));
+            ui.add(egui::Slider::new(&mut lighting.opacity, 0.05..=1.0).text("Opacity"));
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgb(&mut lighting.emissive);
+                ui.label("Emissive");
+            });
+            ui.add(
+                egui::Slider::new(&mut lighting.emissive_strength, 0.0..=10_000.0)
+                    .logarithmic(true)
+                    .text("Emissive Strength"),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Presets:");
+                for preset in MaterialPreset::iter() {
+                    if ui.button(preset.to_string()).clicked() {
+                        preset.apply(lighting);
+                    }
+                }
+            });
+
+            ui.checkbox(&mut user_input.height_gradient, "Height Gradient")
+                .on_hover_text("Tint voxels by layer; OBJ exports get a matching .mtl file");
+            ui.add_enabled_ui(user_input.height_gradient, |ui| {
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgb(&mut user_input.gradient.bottom);
+                    ui.label("Bottom");
+                    ui.color_edit_button_srgb(&mut user_input.gradient.top);
+                    ui.label("Top");
+                });
+            });
+        });
+
+        ui.collapsing("Palette", |ui| {
+            let base = user_input.lighting.base_color;
+            palette_picker(ui, &mut user_input.paint_color, base);
+            ui.label("Voxels added in the Slice View get this color; painted voxels keep it \
+                      over the voxel color and the height gradient.");
+            ui.horizontal(|ui| {
+                let paint = user_input.paint_color;
+                if ui.button("Paint model").clicked() {
+                    voxel_data.voxels =
+                        voxel_data.voxels.iter().map(|&voxel| (voxel, paint)).collect();
+                    dirty.0 = true;
+                }
+                if ui.button("Clear paint").clicked() {
+                    voxel_data.voxels =
+                        voxel_data.voxels.iter().map(|&voxel| (voxel, UNPAINTED)).collect();
+                    dirty.0 = true;
+                }
+            });
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Camera:");
+            for mode in CameraMode::iter() {
+                ui.selectable_value(&mut user_input.camera_mode, mode, mode.to_string());
+            }
+        })
+        .response
+        .on_hover_text("Fly: WASD to move, Q/E down/up, Shift faster, right-drag to look around");
+
+        ui.horizontal(|ui| {
+            ui.label("Projection:");
+            let mut projection = camera_view.projection;
+            for option in CameraProjection::iter() {
+                ui.selectable_value(&mut projection, option, option.to_string());
+            }
+            // Only touch the resource on a real change; update_projection watches it
+            if projection != camera_view.projection {
+                camera_view.projection = projection;
+            }
+        })
+        .response
+        .on_hover_text("Orthographic: no perspective distortion, zooming scales the view");
+        ui.add_enabled_ui(user_input.camera_mode == CameraMode::Orbit, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("View:");
+                for preset in ViewPreset::iter() {
+                    if ui.button(preset.to_string()).clicked() {
+                        camera_view.requested_preset = Some(preset);
+                    }
+                }
+            });
+        });
+
+        ui.checkbox(&mut user_input.turntable, "Turntable");
+        ui.add_enabled(
+            user_input.turntable,
+            egui::Slider::new(&mut user_input.turntable_speed, 1.0..=180.0).text("Speed (°/s)"),
+        );
+        ui.checkbox(&mut user_input.show_slice_view, "Slice View");
+
+        // The range follows the model, which may have been replaced since the last frame
+        let max_layer = voxel_data.shape.height as i32 - 1;
+        user_input.layer_clip_y = user_input.layer_clip_y.clamp(0, max_layer);
+        user_input.layer_clip_min = user_input.layer_clip_min.clamp(0, user_input.layer_clip_y);
+        ui.checkbox(&mut user_input.layer_clip, "Layer Clipping")
+            .on_hover_text("Show only a range of layers to look inside the model; exports are not affected");
+        ui.add_enabled_ui(user_input.layer_clip, |ui| {
+            ui.add(
+                egui::Slider::new(&mut user_input.layer_clip_y, 0..=max_layer)
+                    .text("Show layers up to Y"),
+            );
+            ui.checkbox(&mut user_input.single_layer, "Show single layer only");
+            let max_min = user_input.layer_clip_y;
+            ui.add_enabled(
+                !user_input.single_layer,
+                egui::Slider::new(&mut user_input.layer_clip_min, 0..=max_min)
+                    .text("Show layers from Y"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Screenshot to:");
+            ui.text_edit_singleline(&mut user_input.screenshot_path)
+                .on_hover_text("A .png file, or a folder for timestamped files");
+        });
+        ui.checkbox(&mut user_input.hide_ui_in_screenshot, "Hide UI in screenshot");
+        ui.horizontal(|ui| {
+            if ui.button("Screenshot (F12)").clicked() {
+                screenshot.requested = true;
+            }
+            if ui.button("Reset View (R)").clicked() {
+                reset_camera.send(ResetCamera);
+            }
+        });
+
+        ui.separator();
+        ui.heading("Stats");
+
+        ui.label(format!("Voxels: {}", stats.voxel_count));
+        // Only the cells themselves, a coordinate and a palette index each; the hash map
+        // around them adds more
+        ui.label(format!(
+            "Voxel memory: at least {}",
+            format_bytes(stats.voxel_count * std::mem::size_of::<(VoxelCoord, u8)>())
+        ));
+        match stats.bounds {
+            Some((min, max)) => {
+                ui.label(format!("X: {} to {}", min.0, max.0));
+                ui.label(format!("Y: {} to {}", min.1, max.1));
+                ui.label(format!("Z: {} to {}", min.2, max.2));
+            }
+            None => {
+                ui.label("Bounding box: —");
+            }
+        }
+        ui.label(format!("Exposed faces: {}", stats.exposed_faces));
+        // Exports write one quad per exposed face, or the merged quads with "Optimize mesh"
+        let quads = if user_input.optimize_mesh {
+            stats.greedy_quads
+        } else {
+            stats.exposed_faces
+        };
+        ui.label(format!("Exported triangles: {}", quads * 2));
+        ui.label(format!("Estimated OBJ size: {}", format_bytes(quads * OBJ_BYTES_PER_FACE)));
+        ui.label(format!(
+            "Estimated STL size: {}",
+            format_bytes(STL_HEADER_BYTES + quads * STL_BYTES_PER_FACE)
+        ));
+
+        ui.separator();
+        ui.collapsing("Controls", |ui| {
+            ui.label("Left drag: rotate view");
+            ui.label("Right drag: pan view");
+            ui.label("Scroll: zoom");
+            ui.label("Fly mode: WASD move, Q/E down/up, Shift faster, right drag look");
+        });
+        ui.collapsing("Shortcuts", |ui| {
+            egui::Grid::new("shortcuts").num_columns(2).striped(true).show(ui, |ui| {
+                for action in Action::iter() {
+                    ui.label(action.label());
+                    ui.label(keymap.describe(action));
+                    ui.end_row();
+                }
+            });
+            ui.label(format!("Change these in {}", KEYBINDINGS_PATH));
+        });
+    });
+
+    if slice_view(contexts.ctx_mut(), &mut user_input, &mut voxel_data) {
+        dirty.0 = true;
+    }
+}
+
+// Top-down view of a single Y layer; clicking a cell adds or removes that voxel. Returns
+// whether the voxels were edited
+fn slice_view(
+    ctx: &egui::Context,
+    user_input: &mut UserInput,
+    voxel_data: &mut ResMut<VoxelData>,
+) -> bool {
+    let mut edited = false;
+    let mut open = user_input.show_slice_view;
+    egui::Window::new("Slice View")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let shape = &voxel_data.shape;
+            let max_y = shape.height as i32 - 1;
+            user_input.slice_y = user_input.slice_y.clamp(0, max_y);
+            ui.add(egui::Slider::new(&mut user_input.slice_y, 0..=max_y).text("Y"));
+            let slice_y = user_input.slice_y;
+            ui.horizontal(|ui| {
+                ui.label("Click:");
+                ui.selectable_value(&mut user_input.slice_tool, SliceTool::Toggle, "Toggle voxel");
+                ui.selectable_value(
+                    &mut user_input.slice_tool,
+                    SliceTool::ClearPocket,
+                    "Clear pocket",
+                )
+                .on_hover_text("Remove the walls around an enclosed air pocket");
+                ui.selectable_value(&mut user_input.slice_tool, SliceTool::FloodFill, "Fill region")
+                    .on_hover_text("Fill the connected empty cells inside the model's extents");
+                ui.selectable_value(
+                    &mut user_input.slice_tool,
+                    SliceTool::EraseRegion,
+                    "Erase region",
+                )
+                .on_hover_text("Remove every voxel connected to the clicked one");
+                ui.selectable_value(&mut user_input.slice_tool, SliceTool::Paint, "Paint")
+                    .on_hover_text("Give the clicked voxel the paint color");
+            });
+            let base = user_input.lighting.base_color;
+            palette_picker(ui, &mut user_input.paint_color, base);
+            let paint = user_input.paint_color;
+
+            let (width, depth) = (shape.width as i32, shape.depth as i32);
+            let cell_size = SLICE_VIEW_SIZE / width.max(depth) as f32;
+            let (response, painter) = ui.allocate_painter(
+                egui::vec2(cell_size * width as f32, cell_size * depth as f32),
+                egui::Sense::click(),
+            );
+            let origin = response.rect.min;
+
+            // Occupied (x, z) cells on this layer with their palette colors
+            let layer: HashMap<(i32, i32), u8> = voxel_data
+                .voxels
+                .colored()
+                .filter(|(voxel, _)| voxel.1 == slice_y)
+                .map(|((x, _, z), color)| ((x, z), color))
+                .collect();
+
+            for z in 0..depth {
+                for x in 0..width {
+                    let min = origin + egui::vec2(x as f32 * cell_size, z as f32 * cell_size);
+                    let cell = egui::Rect::from_min_size(min, egui::vec2(cell_size, cell_size)).shrink(0.5);
+                    let color = match layer.get(&(x, z)) {
+                        Some(&color) => {
+                            let [r, g, b] = palette_color(color, base);
+                            egui::Color32::from_rgb(r, g, b)
+                        }
+                        None => SLICE_EMPTY_COLOR,
+                    };
+                    painter.rect_filled(cell, 0.0, color);
+                }
+            }
+
+            if response.clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let offset = (pos - origin) / cell_size;
+                    let (x, z) = (offset.x.floor() as i32, offset.y.floor() as i32);
+                    let inside = (0..width).contains(&x) && (0..depth).contains(&z);
+                    let tool = user_input.slice_tool;
+                    if inside && tool == SliceTool::ClearPocket {
+                        match clear_pocket(voxel_data, (x, slice_y, z)) {
+                            Ok(removed) => {
+                                user_input.status_message =
+                                    Some(format!("Removed {} pocket wall voxels", removed));
+                                edited = true;
+                            }
+                            Err(e) => user_input.error_message = Some(e),
+                        }
+                    } else if inside && tool == SliceTool::FloodFill {
+                        match fill_region(voxel_data, (x, slice_y, z), paint) {
+                            Ok(added) => {
+                                user_input.status_message =
+                                    Some(format!("Filled {} voxels", added));
+                                edited = true;
+                            }
+                            Err(e) => user_input.error_message = Some(e),
+                        }
+                    } else if inside && tool == SliceTool::EraseRegion {
+                        match erase_region(voxel_data, (x, slice_y, z)) {
+                            Ok(removed) => {
+                                user_input.status_message =
+                                    Some(format!("Erased {} voxels", removed));
+                                edited = true;
+                            }
+                            Err(e) => user_input.error_message = Some(e),
+                        }
+                    } else if inside {
+                        // The clicked cell and its mirror images, each once (a cell on a
+                        // mirror plane is its own image)
+                        let symmetry = user_input.symmetry;
+                        let cells = apply_symmetry(&[(x, slice_y, z)], shape, symmetry);
+                        if tool == SliceTool::Paint {
+                            for cell in &cells {
+                                edited |= voxel_data.voxels.paint(cell, paint);
+                            }
+                        } else if layer.contains_key(&(x, z)) {
+                            voxel_data.voxels.retain(|voxel| !cells.contains(voxel));
+                            edited = true;
+                        } else {
+                            voxel_data.voxels.extend(cells.iter().map(|&cell| (cell, paint)));
+                            edited = true;
+                        }
+                    }
+                }
+            }
+        });
+    user_input.show_slice_view = open;
+    edited
+}
+
+// Takes the selected object back a step in its history, or forward again with `redo`
+pub fn step_history(
+    redo: bool,
+    history: &mut History,
+    scene: &SceneData,
+    user_input: &mut UserInput,
+    voxel_data: &mut VoxelData,
+    dirty: &mut VoxelDirty,
+) {
+    let id = scene.selected_object().id;
+    let restored = if redo { history.redo(id) } else { history.undo(id) };
+    if let Some(data) = restored {
+        user_input.shape = data.shape.clone();
+        *voxel_data = data;
+        dirty.0 = true;
+    }
+}
+
+// Regenerates after the shape inputs changed. Large grids only regenerate once confirmed with
+// "Generate", so dragging a slider or pressing a shape key can't stall the app
+pub fn request_regeneration(user_input: &mut UserInput) {
+    let cells = user_input.shape.cell_count();
+    if cells > user_input.confirm_cells {
+        user_input.large_grid_pending = true;
+        user_input.error_message = Some(if cells > MAX_GRID_CELLS {
+            format!("{} cells is over the limit of {}; reduce the dimensions", cells, MAX_GRID_CELLS)
+        } else {
+            format!("This grid can hold {} voxels; click Generate to build it", cells)
+        });
+    } else {
+        user_input.large_grid_pending = false;
+        user_input.needs_regeneration = true;
+    }
+}
+
+// Starts writing the visible objects to EXPORT_PATH with the export options from the UI
+pub fn export_scene(user_input: &UserInput, scene: &SceneData, export_job: &mut ExportJob) {
+    let gradient = user_input.height_gradient.then_some(user_input.gradient);
+    let (merge, optimize) = (user_input.merge_on_export, user_input.optimize_mesh);
+    let base_color = user_input.lighting.base_color;
+    let mut files = vec![PathBuf::from(EXPORT_PATH)];
+    // Painted voxels get their colors from the .mtl file too
+    let painted = scene
+        .objects
+        .iter()
+        .any(|object| object.visible && object.data.voxels.is_painted());
+    if gradient.is_some() || painted {
+        files.push(Path::new(EXPORT_PATH).with_extension("mtl"));
+    }
+    let scene = scene.clone();
+    export_job.start(EXPORT_PATH, files, move || {
+        export_scene_to_obj(&scene, EXPORT_PATH, base_color, gradient.as_ref(), merge, optimize)
+    });
+}
+
+// Removes the inner wall of the enclosed air pocket at `cell`, or next to it when `cell` is
+// occupied, and returns how many voxels went
+fn clear_pocket(voxel_data: &mut VoxelData, cell: VoxelCoord) -> Result<usize, String> {
+    let occupied = &voxel_data.voxels;
+    let shape = &voxel_data.shape;
+    let bounds = (shape.width as i32, shape.height as i32, shape.depth as i32);
+
+    let (x, y, z) = cell;
+    let starts: Vec<VoxelCoord> = if occupied.contains(&cell) {
+        NEIGHBOR_OFFSETS.iter().map(|&(dx, dy, dz)| (x + dx, y + dy, z + dz)).collect()
+    } else {
+        vec![cell]
+    };
+    let walls = starts
+        .into_iter()
+        .find_map(|start| pocket_walls(occupied, start, bounds))
+        .ok_or("No enclosed air pocket there; the space is open to the outside")?;
+
+    voxel_data.voxels.retain(|voxel| !walls.contains(voxel));
+    Ok(walls.len())
+}
+
+// Fills the empty cells connected to `cell` with palette color `color`, limited to the bounding
+// box of the current voxels (and so to the grid) so an open region can't flood the whole grid.
+// Returns how many were added
+fn fill_region(voxel_data: &mut VoxelData, cell: VoxelCoord, color: u8) -> Result<usize, String> {
+    let occupied = &voxel_data.voxels;
+    if occupied.contains(&cell) {
+        return Err("Fill region needs an empty cell; that one is already filled".to_string());
+    }
+    let (min, max) = occupied.bounding_box().ok_or("There are no voxels to bound the fill")?;
+    let region = connected_region(occupied, cell, false, min, max);
+    if region.is_empty() {
+        return Err("That cell is outside the model's extents".to_string());
+    }
+
+    let added = region.len();
+    voxel_data.voxels.extend(region.into_iter().map(|voxel| (voxel, color)));
+    Ok(added)
+}
+
+// Removes the cluster of voxels 6-connected to `cell` and returns how many went
+fn erase_region(voxel_data: &mut VoxelData, cell: VoxelCoord) -> Result<usize, String> {
+    let occupied = &voxel_data.voxels;
+    if !occupied.contains(&cell) {
+        return Err("Erase region needs a filled cell; that one is empty".to_string());
+    }
+    let shape = &voxel_data.shape;
+    let max = (shape.width as i32 - 1, shape.height as i32 - 1, shape.depth as i32 - 1);
+    let region = connected_region(occupied, cell, true, (0, 0, 0), max);
+
+    voxel_data.voxels.retain(|voxel| !region.contains(voxel));
+    Ok(region.len())
+}
+
+// Small notice at the bottom of the window about the last screenshot; doesn't block input
+pub fn screenshot_toast_system(
+    mut contexts: EguiContexts,
+    screenshot: Res<ScreenshotState>,
+    result: Option<Res<ScreenshotResult>>,
+) {
+    let Some(result) = result else {
+        return;
+    };
+    if screenshot.ui_hidden {
+        return;
+    }
+    let color = if result.success { egui::Color32::LIGHT_GREEN } else { egui::Color32::RED };
+    egui::Area::new(egui::Id::new("screenshot_toast"))
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -20.0))
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.colored_label(color, &result.message);
+            });
+        });
+}
+
+// Loading a preset replaces the shape settings and returns true so the shape is regenerated.
+// The name field doubles as the name to save under and the preset to delete.
+fn preset_controls(
+    ui: &mut egui::Ui,
+    user_input: &mut UserInput,
+    presets: &mut ResMut<PresetLibrary>,
+) -> bool {
+    let mut loaded = None;
+    egui::ComboBox::from_label("Load Preset")
+        .selected_text("Choose...")
+        .show_ui(ui, |ui| {
+            for preset in &presets.presets {
+                if ui.selectable_label(false, &preset.name).clicked() {
+                    loaded = Some(preset.clone());
+                }
+            }
+        });
+    if let Some(preset) = &loaded {
+        user_input.shape = preset.shape.clone();
+        user_input.hollow = preset.hollow;
+        user_input.preset_name = preset.name.clone();
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("Name:");
+        ui.text_edit_singleline(&mut user_input.preset_name);
+    });
+    let name = user_input.preset_name.trim().to_string();
+    ui.horizontal(|ui| {
+        if ui.add_enabled(!name.is_empty(), egui::Button::new("Save Preset")).clicked() {
+            let preset = Preset {
+                name: name.clone(),
+                shape: user_input.shape.clone(),
+                hollow: user_input.hollow,
+            };
+            if let Err(e) = presets.save(preset) {
+                let path = presets.path().display();
+                user_input.error_message = Some(format!("Cannot save {}: {}", path, e));
+            }
+        }
+        let exists = presets.get(&name).is_some();
+        if ui.add_enabled(exists, egui::Button::new("Delete Preset")).clicked() {
+            if let Err(e) = presets.remove(&name) {
+                let path = presets.path().display();
+                user_input.error_message = Some(format!("Cannot save {}: {}", path, e));
+            }
+        }
+    });
+    loaded.is_some()
+}
+
+// Keyframes are snapshots of the model being edited; playback shows the morph in its place
+fn morph_controls(ui: &mut egui::Ui, morph: &mut ResMut<MorphAnimation>, voxel_data: &VoxelData) {
+    ui.horizontal(|ui| {
+        if ui.button("Set Keyframe A").clicked() {
+            morph.from = voxel_data.voxels.clone();
+        }
+        if ui.button("Set Keyframe B").clicked() {
+            morph.to = voxel_data.voxels.clone();
+        }
+    });
+    ui.label(format!("A: {} voxels, B: {} voxels", morph.from.len(), morph.to.len()));
+
+    let mut speed = morph.speed;
+    if ui.add(egui::Slider::new(&mut speed, 0.1..=2.0).text("Speed")).changed() {
+        morph.speed = speed;
+    }
+    ui.horizontal(|ui| {
+        let ready = !morph.from.is_empty() && !morph.to.is_empty();
+        if ui.add_enabled(ready, egui::Button::new("▶ Play Morph")).clicked() {
+            morph.play();
+        }
+        if ui.add_enabled(morph.is_active(), egui::Button::new("■ Stop")).clicked() {
+            morph.stop();
+        }
+    });
+    if morph.is_active() {
+        ui.add(egui::ProgressBar::new(morph.t));
+    }
+}
+
+// Scales the locked proportions so `axis` gets `value`. The factor is taken from the
+// proportions stored when the lock was turned on, not from the previous frame, so dragging
+// back and forth never drifts; each side is clamped to the allowed range on its own.
+fn scale_locked(base: [u32; 3], axis: usize, value: u32, max: u32) -> [u32; 3] {
+    let factor = value as f32 / base[axis] as f32;
+    let mut scaled =
+        base.map(|side| ((side as f32 * factor).round() as u32).clamp(MIN_DIMENSION, max));
+    scaled[axis] = value;
+    scaled
+}
+
+// Row of swatches, one per PALETTE color; clicking one selects it. The first one shows
+// `base`, the color unpainted voxels get
+fn palette_picker(ui: &mut egui::Ui, selected: &mut u8, base: [u8; 3]) {
+    ui.horizontal_wrapped(|ui| {
+        for index in 0..PALETTE.len() as u8 {
+            let [r, g, b] = palette_color(index, base);
+            let stroke = if *selected == index {
+                egui::Stroke::new(2.0, ui.visuals().strong_text_color())
+            } else {
+                egui::Stroke::NONE
+            };
+            let swatch = egui::Button::new("")
+                .fill(egui::Color32::from_rgb(r, g, b))
+                .stroke(stroke)
+                .min_size(egui::vec2(18.0, 18.0));
+            let hover = if index == UNPAINTED { "Unpainted (voxel color)" } else { "Paint color" };
+            if ui.add(swatch).on_hover_text(hover).clicked() {
+                *selected = index;
+            }
+        }
+    });
+}
+
+// Color a voxel is rendered with, from its layer, its object's grid height and its
+// palette color
+fn voxel_color(user_input: &UserInput, y: i32, height: u32, paint: u8) -> [u8; 3] {
+    if paint != UNPAINTED {
+        palette_color(paint, user_input.lighting.base_color)
+    } else if user_input.height_gradient {
+        user_input.gradient.color_at(y, height)
+    } else {
+        user_input.lighting.base_color
+    }
+}
+
+// Scene objects: select, show/hide, add and delete; the selected one can be renamed and moved
+fn object_list(
+    ui: &mut egui::Ui,
+    user_input: &mut UserInput,
+    voxel_data: &mut ResMut<VoxelData>,
+    scene: &mut ResMut<SceneData>,
+    dirty: &mut VoxelDirty,
+) {
+    let mut clicked = None;
+    let selected = scene.selected;
+    for (index, object) in scene.objects.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut object.visible, "").on_hover_text("Visible").changed() {
+                dirty.0 = true;
+            }
+            if ui.selectable_label(index == selected, &object.name).clicked() {
+                clicked = Some(index);
+            }
+        });
+    }
+    if let Some(index) = clicked.filter(|&index| index != selected) {
+        // Keep the edits of the object we leave
+        scene.objects[selected].data = (**voxel_data).clone();
+        scene.selected = index;
+        load_selected_object(scene, user_input, voxel_data);
+        dirty.0 = true;
+    }
+
+    let selected = scene.selected;
+    let object = &mut scene.objects[selected];
+    ui.horizontal(|ui| {
+        ui.label("Name:");
+        ui.text_edit_singleline(&mut object.name);
+    });
+    let offset = &mut object.offset;
+    for (value, label) in [
+        (&mut offset.0, "Offset X"),
+        (&mut offset.1, "Offset Y"),
+        (&mut offset.2, "Offset Z"),
+    ] {
+        if ui.add(egui::Slider::new(value, -32..=32).text(label)).changed() {
+            dirty.0 = true;
+        }
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add Object").clicked() {
+            let selected = scene.selected;
+            scene.objects[selected].data = (**voxel_data).clone();
+            scene.selected = scene.add_object(VoxelData::default());
+            // New objects start as a default cube
+            user_input.shape = Shape::default();
+            user_input.needs_regeneration = true;
+        }
+        let can_delete = scene.objects.len() > 1;
+        if ui.add_enabled(can_delete, egui::Button::new("Delete")).clicked() {
+            let selected = scene.selected;
+            scene.remove_object(selected);
+            load_selected_object(scene, user_input, voxel_data);
+            dirty.0 = true;
+        }
+    });
+}
+
+// Makes the selected scene object the one being edited, without regenerating it
+fn load_selected_object(scene: &SceneData, user_input: &mut UserInput, voxel_data: &mut VoxelData) {
+    let data = scene.selected_object().data.clone();
+    user_input.shape = data.shape.clone();
+    *voxel_data = data;
+}
+
+// Fresh seed from the clock, so no random number crate is needed
+fn time_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let mut state = nanos;
+    noise::splitmix64(&mut state)
+}
+
+// Human readable file size
+fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+use voxel_sculptor::history::History;
+use voxel_sculptor::morph::MorphAnimation;
+use voxel_sculptor::shapes::{
+    apply_noise, apply_symmetry, combine_with_shape, compose_scene, connected_region,
+    hollow_voxels, pocket_walls, resize_voxels, rotate_model, subdivide_voxels, Axis, BoolOp,
+    HeightGradient, Symmetry, UNPAINTED,
+};
+use voxel_sculptor::{generate_shape, GeometricShape, SceneShape, Shape, VoxelData, VoxelGrid};
+
+fn cube(size: u32) -> Shape {
+    Shape {
+        kind: GeometricShape::Cube,
+        width: size,
+        height: size,
+        depth: size,
+        ..Shape::default()
+    }
+}
+
+fn grid_size(shape: &Shape) -> (u32, u32, u32) {
+    (shape.width, shape.height, shape.depth)
+}
+
+#[test]
+fn erosion_is_deterministic() {
+    let voxels = generate_shape(&cube(10));
+    let eroded = |seed| {
+        let mut voxels = voxels.clone();
+        apply_noise(&mut voxels, 0.5, seed);
+        voxels
+    };
+    let first = eroded(7);
+    assert_eq!(eroded(7), first);
+    assert!(first.len() < voxels.len());
+    // Only the surface is eroded
+    assert!(first.contains(&(5, 5, 5)));
+}
+
+#[test]
+fn gradient_hits_both_endpoints() {
+    let gradient = HeightGradient::default();
+    assert_eq!(gradient.color_at(0, 10), gradient.bottom);
+    assert_eq!(gradient.color_at(9, 10), gradient.top);
+    assert_eq!(gradient.color_at(0, 1), gradient.bottom);
+}
+
+#[test]
+fn resize_drops_voxels_outside_the_new_grid() {
+    let data = VoxelData {
+        voxels: generate_shape(&cube(4)),
+        shape: cube(4),
+    };
+    let resized = resize_voxels(&data, 2, 4, 3);
+    assert_eq!(grid_size(&resized.shape), (2, 4, 3));
+    assert_eq!(resized.voxels.len(), 2 * 4 * 3);
+
+    let grown = resize_voxels(&data, 6, 6, 6);
+    assert_eq!(grown.voxels, data.voxels);
+}
+
+#[test]
+fn compose_scene_unions_offset_shapes() {
+    let extra = [SceneShape {
+        shape: cube(3),
+        offset: (3, 0, 0),
+    }];
+    let scene = compose_scene(&cube(3), &extra);
+    assert_eq!(scene.voxels.len(), 54);
+    assert_eq!(grid_size(&scene.shape), (6, 3, 3));
+}
+
+#[test]
+fn symmetry_mirrors_even_and_odd_widths() {
+    let voxels = VoxelGrid::from(vec![(0, 0, 0)]);
+    let symmetry = Symmetry {
+        x: true,
+        ..Symmetry::default()
+    };
+
+    let even = apply_symmetry(&voxels, &cube(4), symmetry);
+    assert_eq!(even.to_sorted_vec(), vec![(0, 0, 0), (3, 0, 0)]);
+
+    // The center column of an odd width mirrors onto itself
+    let center = VoxelGrid::from(vec![(2, 0, 0)]);
+    assert_eq!(apply_symmetry(&center, &cube(5), symmetry).len(), 1);
+    let odd = apply_symmetry(&voxels, &cube(5), symmetry);
+    assert_eq!(odd.to_sorted_vec(), vec![(0, 0, 0), (4, 0, 0)]);
+}
+
+#[test]
+fn connected_region_fills_and_erases() {
+    // Two separate bars on the bottom layer of a 5x1x3 grid
+    let voxels = VoxelGrid::from(vec![(0, 0, 0), (1, 0, 0), (3, 0, 0), (4, 0, 0)]);
+    let (min, max) = ((0, 0, 0), (4, 0, 2));
+
+    let erased = connected_region(&voxels, (0, 0, 0), true, min, max);
+    assert_eq!(erased, HashSet::from([(0, 0, 0), (1, 0, 0)]));
+
+    // The empty cells all connect through rows z = 1 and z = 2
+    let filled = connected_region(&voxels, (2, 0, 0), false, min, max);
+    assert_eq!(filled.len(), 15 - 4);
+
+    assert!(connected_region(&voxels, (0, 0, 0), false, min, max).is_empty());
+    assert!(connected_region(&voxels, (9, 0, 0), true, min, max).is_empty());
+}
+
+#[test]
+fn pocket_walls_find_enclosed_air() {
+    let hollow = hollow_voxels(&generate_shape(&cube(5)));
+    let bounds = (5, 5, 5);
+    let walls = pocket_walls(&hollow, (2, 2, 2), bounds).expect("the cube is closed");
+    assert_eq!(walls.len(), 54);
+
+    let mut opened = hollow.clone();
+    opened.remove(&(2, 4, 2));
+    assert!(pocket_walls(&opened, (2, 2, 2), bounds).is_none());
+}
+
+#[test]
+fn morph_step_scales_voxels_in_and_out() {
+    let mut morph = MorphAnimation {
+        from: VoxelGrid::from(vec![(0, 0, 0), (1, 0, 0)]),
+        to: VoxelGrid::from(vec![(1, 0, 0), (2, 0, 0)]),
+        ..MorphAnimation::default()
+    };
+    morph.play();
+    morph.step(1.0);
+    let t = morph.t;
+    assert!(t > 0.0 && t < 1.0);
+    assert!(morph.is_active());
+
+    let frame = morph.frame();
+    assert_eq!(frame.len(), 3);
+    let scale_of = |coord| frame.iter().find(|v| v.coord == coord).unwrap().scale;
+    assert_eq!(scale_of((0, 0, 0)), 1.0 - t);
+    assert_eq!(scale_of((1, 0, 0)), 1.0);
+    assert_eq!(scale_of((2, 0, 0)), t);
+}
+
+#[test]
+fn subdivide_splits_each_voxel_into_a_block() {
+    let single = VoxelData {
+        voxels: VoxelGrid::from(vec![(1, 0, 0)]),
+        shape: Shape {
+            width: 2,
+            height: 1,
+            depth: 1,
+            ..Shape::default()
+        },
+    };
+    let subdivided = subdivide_voxels(&single, 3);
+    assert_eq!(grid_size(&subdivided.shape), (6, 3, 3));
+    assert_eq!(subdivided.voxels.len(), 27);
+    assert_eq!(
+        subdivided.voxels.bounding_box(),
+        Some(((3, 0, 0), (5, 2, 2)))
+    );
+
+    // Sides are capped at the maximum dimension and the blocks past it dropped
+    let bar = Shape {
+        width: 40,
+        height: 1,
+        depth: 1,
+        ..Shape::default()
+    };
+    let large = VoxelData {
+        voxels: generate_shape(&bar),
+        shape: bar,
+    };
+    let capped = subdivide_voxels(&large, 4);
+    assert_eq!(grid_size(&capped.shape), (128, 4, 4));
+    assert_eq!(capped.voxels.len(), 128 * 4 * 4);
+}
+
+#[test]
+fn painted_voxels_keep_their_color_through_edits() {
+    let mut data = VoxelData {
+        voxels: generate_shape(&cube(2)),
+        shape: cube(2),
+    };
+    assert!(!data.voxels.is_painted());
+    assert!(data.voxels.paint(&(0, 0, 0), 3));
+    assert!(!data.voxels.paint(&(5, 5, 5), 3));
+    // Inserting over an occupied cell keeps its color
+    assert!(!data.voxels.insert((0, 0, 0)));
+    assert_eq!(data.voxels.color(&(0, 0, 0)), Some(3));
+
+    // A quarter turn around Y takes (0, 0, 0) to (0, 0, 1) in the 2x2x2 grid
+    let rotated = rotate_model(&data, Axis::Y);
+    assert_eq!(rotated.voxels.color(&(0, 0, 1)), Some(3));
+    assert_eq!(rotated.voxels.color(&(0, 0, 0)), Some(UNPAINTED));
+
+    let subdivided = subdivide_voxels(&data, 2);
+    let painted = subdivided
+        .voxels
+        .iter()
+        .filter(|voxel| subdivided.voxels.color(voxel) == Some(3));
+    assert_eq!(painted.count(), 8);
+
+    let hollow = hollow_voxels(&data.voxels);
+    assert_eq!(hollow.color(&(0, 0, 0)), Some(3));
+}
+
+#[test]
+fn history_undoes_and_redoes_per_object() {
+    let mut history = History::default();
+    let mut data = VoxelData {
+        voxels: generate_shape(&cube(2)),
+        shape: cube(2),
+    };
+    let original = data.clone();
+    history.record(1, &data);
+    assert!(!history.can_undo(1));
+
+    data.voxels.remove(&(0, 0, 0));
+    data.voxels.paint(&(1, 1, 1), 4);
+    history.record(1, &data);
+    let edited = data.clone();
+    data = resize_voxels(&data, 3, 3, 3);
+    data.voxels.insert((2, 2, 2));
+    history.record(1, &data);
+    // Recording an unchanged state adds no step
+    history.record(1, &data);
+
+    let undone = history.undo(1).unwrap();
+    assert_eq!(undone.voxels, edited.voxels);
+    assert_eq!(undone.shape, edited.shape);
+    let undone = history.undo(1).unwrap();
+    assert_eq!(undone.voxels, original.voxels);
+    assert!(history.undo(1).is_none());
+    assert_eq!(history.redo(1).unwrap().voxels, edited.voxels);
+
+    // A new edit drops what could be redone
+    history.record(1, &original);
+    assert!(!history.can_redo(1));
+
+    // Switching objects is not an edit, and each object keeps its own steps
+    history.record(2, &data);
+    assert!(!history.can_undo(2));
+    assert!(history.undo(1).is_none());
+    history.record(1, &original);
+    assert!(history.can_undo(1));
+}
+
+#[test]
+fn combine_places_the_second_shape_at_an_offset() {
+    let current = VoxelData {
+        voxels: generate_shape(&cube(4)),
+        shape: cube(4),
+    };
+    let union = combine_with_shape(
+        &current,
+        &cube(2),
+        (0, 0, 0),
+        BoolOp::Union,
+        Symmetry::default(),
+    );
+    assert_eq!(union.voxels, current.voxels);
+
+    // Moved past the -X side, the grid grows and the model shifts to stay at x >= 0
+    let union = combine_with_shape(
+        &current,
+        &cube(2),
+        (-3, 0, 0),
+        BoolOp::Union,
+        Symmetry::default(),
+    );
+    assert_eq!(grid_size(&union.shape), (6, 4, 4));
+    assert_eq!(union.voxels.len(), 64 + 8);
+    assert_eq!(union.voxels.bounding_box(), Some(((0, 0, 0), (5, 3, 3))));
+    assert!(!union.voxels.contains(&(0, 0, 0)));
+    assert!(union.voxels.contains(&(0, 1, 1)));
+
+    // Other operations keep the grid
+    let cut = combine_with_shape(
+        &current,
+        &cube(2),
+        (2, 0, 0),
+        BoolOp::Difference,
+        Symmetry::default(),
+    );
+    assert_eq!(grid_size(&cut.shape), (4, 4, 4));
+    assert_eq!(cut.voxels.len(), 64 - 4);
+}
@@ -0,0 +1,331 @@
+use voxel_sculptor::export::{
+    export_obj_to_writer, greedy_mesh, read_vxl, scene_to_vxl_layers, vxl_layers_to_scene,
+    write_ply, write_vxl,
+};
+use voxel_sculptor::shapes::{count_exposed_faces, palette_color, UNPAINTED};
+use voxel_sculptor::{generate_shape, GeometricShape, SceneData, Shape, VoxelData};
+
+fn model(kind: GeometricShape, width: u32, height: u32, depth: u32) -> VoxelData {
+    let shape = Shape {
+        kind,
+        width,
+        height,
+        depth,
+        ..Shape::default()
+    };
+    VoxelData {
+        voxels: generate_shape(&shape),
+        shape,
+    }
+}
+
+fn cube(size: u32) -> VoxelData {
+    model(GeometricShape::Cube, size, size, size)
+}
+
+fn obj_text(data: &VoxelData, optimize: bool) -> String {
+    let mut buffer = Vec::new();
+    export_obj_to_writer(data, &mut buffer, optimize).unwrap();
+    String::from_utf8(buffer).unwrap()
+}
+
+// The "v" and "vn" lines of an OBJ file and the "v/vt/vn" triples of its faces
+struct Obj {
+    vertices: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    faces: Vec<Vec<[usize; 3]>>,
+}
+
+fn parse_obj(text: &str) -> Obj {
+    let mut obj = Obj {
+        vertices: Vec::new(),
+        normals: Vec::new(),
+        faces: Vec::new(),
+    };
+    let vector = |parts: Vec<&str>| [0, 1, 2].map(|i| parts[i].parse::<f32>().unwrap());
+    for line in text.lines() {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("v") => obj.vertices.push(vector(parts.collect())),
+            Some("vn") => obj.normals.push(vector(parts.collect())),
+            Some("f") => obj.faces.push(
+                parts
+                    .map(|corner| {
+                        let indices: Vec<usize> = corner
+                            .split('/')
+                            .map(|index| index.parse().unwrap())
+                            .collect();
+                        assert_eq!(indices.len(), 3, "face corner '{}'", corner);
+                        [indices[0], indices[1], indices[2]]
+                    })
+                    .collect(),
+            ),
+            _ => {}
+        }
+    }
+    obj
+}
+
+#[test]
+fn obj_is_centered_on_the_origin() {
+    for (size, half) in [(1, 0.5), (2, 1.0), (5, 2.5)] {
+        let obj = parse_obj(&obj_text(&cube(size), false));
+        for axis in 0..3 {
+            let values = obj.vertices.iter().map(|vertex| vertex[axis]);
+            let min = values.clone().fold(f32::MAX, f32::min);
+            let max = values.fold(f32::MIN, f32::max);
+            assert_eq!((min, max), (-half, half), "{}^3 axis {}", size, axis);
+        }
+    }
+}
+
+#[test]
+fn obj_center_of_mass_is_the_origin() {
+    let obj = parse_obj(&obj_text(&cube(4), false));
+    let mut sum = [0.0f32; 3];
+    for vertex in &obj.vertices {
+        for axis in 0..3 {
+            sum[axis] += vertex[axis];
+        }
+    }
+    assert!(sum.iter().all(|value| value.abs() < 1e-4), "{:?}", sum);
+}
+
+#[test]
+fn obj_faces_reference_outward_normals() {
+    let data = model(GeometricShape::Sphere, 6, 6, 6);
+    let obj = parse_obj(&obj_text(&data, false));
+    assert_eq!(obj.faces.len(), count_exposed_faces(&data));
+
+    for face in &obj.faces {
+        assert_eq!(face.len(), 4);
+        let corner = |i: usize| obj.vertices[face[i][0] - 1];
+        let normal = obj.normals[face[0][2] - 1];
+        assert!(face.iter().all(|&[_, _, n]| n == face[0][2]));
+
+        // The winding agrees with the normal...
+        let (a, b, c) = (corner(0), corner(1), corner(2));
+        let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let cross = [
+            ab[1] * ac[2] - ab[2] * ac[1],
+            ab[2] * ac[0] - ab[0] * ac[2],
+            ab[0] * ac[1] - ab[1] * ac[0],
+        ];
+        let dot = |p: [f32; 3], q: [f32; 3]| p[0] * q[0] + p[1] * q[1] + p[2] * q[2];
+        assert!(dot(cross, normal) > 0.0);
+
+        // ...and points away from the sphere's center
+        let center = [0, 1, 2].map(|axis| (0..4).map(|i| corner(i)[axis]).sum::<f32>() / 4.0);
+        assert!(dot(center, normal) > 0.0);
+    }
+}
+
+#[test]
+fn greedy_quads_cover_exactly_the_exposed_faces() {
+    for data in [
+        model(GeometricShape::Sphere, 9, 9, 9),
+        model(GeometricShape::Cone, 8, 6, 7),
+        model(GeometricShape::Tube, 10, 4, 10),
+    ] {
+        let area: i32 = greedy_mesh(&data, false)
+            .iter()
+            .map(|quad| quad.size.0 * quad.size.1 * quad.size.2)
+            .sum();
+        assert_eq!(
+            area as usize,
+            count_exposed_faces(&data),
+            "{}",
+            data.shape.kind
+        );
+    }
+
+    assert_eq!(greedy_mesh(&cube(32), false).len(), 6);
+}
+
+// A 2x2x2 cube with its X = 1 half painted red
+fn two_color_cube() -> VoxelData {
+    let mut data = cube(2);
+    for voxel in data.voxels.to_sorted_vec() {
+        if voxel.0 == 1 {
+            data.voxels.paint(&voxel, 5);
+        }
+    }
+    data
+}
+
+#[test]
+fn greedy_quads_stop_at_color_boundaries() {
+    let data = two_color_cube();
+    let quads = greedy_mesh(&data, false);
+    // Each X face is one color; the four other sides split into a quad per color
+    assert_eq!(quads.len(), 2 + 4 * 2);
+    for quad in quads {
+        let (x, y, z) = quad.voxel;
+        let (sx, sy, sz) = quad.size;
+        for dx in 0..sx {
+            for dy in 0..sy {
+                for dz in 0..sz {
+                    let voxel = (x + dx, y + dy, z + dz);
+                    assert_eq!(data.voxels.color(&voxel), Some(quad.color), "{:?}", quad);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn ply_ascii_layout() {
+    let mut buffer = Vec::new();
+    write_ply(
+        &model(GeometricShape::Cube, 2, 1, 1),
+        &mut buffer,
+        [1, 2, 3],
+        false,
+    )
+    .unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+
+    let header_end = lines.iter().position(|&line| line == "end_header").unwrap();
+    assert_eq!(lines[0], "ply");
+    assert_eq!(lines[1], "format ascii 1.0");
+    assert!(lines.contains(&"element vertex 12"));
+    assert!(lines.contains(&"element face 10"));
+    assert!(lines.contains(&"property list uchar int vertex_indices"));
+    assert_eq!(lines.len(), header_end + 1 + 12 + 10);
+
+    let faces = &lines[header_end + 1 + 12..];
+    assert_eq!(faces[0], "4 0 1 2 3");
+    assert_eq!(faces[9], "4 6 5 9 8");
+}
+
+#[test]
+fn ply_single_voxel() {
+    let mut buffer = Vec::new();
+    write_ply(&cube(1), &mut buffer, [1, 2, 3], false).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    assert!(text.contains("element vertex 8\n"));
+    assert!(text.contains("element face 6\n"));
+}
+
+#[test]
+fn ply_writes_the_painted_colors() {
+    let mut buffer = Vec::new();
+    write_ply(&two_color_cube(), &mut buffer, [1, 2, 3], false).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    let header_end = lines.iter().position(|&line| line == "end_header").unwrap();
+
+    // The 26 corners of the surface, with the 8 on the color boundary once per color
+    assert!(lines.contains(&"element vertex 34"));
+    let vertices = &lines[header_end + 1..header_end + 1 + 34];
+    let with_color = |rgb: &str| vertices.iter().filter(|line| line.ends_with(rgb)).count();
+    assert_eq!(with_color(" 220 40 40"), 17);
+    assert_eq!(with_color(" 1 2 3"), 17);
+}
+
+#[test]
+fn vxl_round_trip() {
+    let mut scene = SceneData::new(cube(3));
+    let index = scene.add_object(model(GeometricShape::Sphere, 5, 5, 5));
+    scene.objects[index].offset = (4, 1, -2);
+    scene.objects[index].visible = false;
+
+    let layers = scene_to_vxl_layers(&scene, |_, _, _| [10, 20, 30]);
+    let mut bytes = Vec::new();
+    write_vxl(&layers, &mut bytes).unwrap();
+    let read = read_vxl(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read, layers);
+
+    let loaded = vxl_layers_to_scene(&read, &Shape::default()).unwrap();
+    assert_eq!(loaded.objects.len(), 2);
+    for (loaded, original) in loaded.objects.iter().zip(&scene.objects) {
+        assert_eq!(loaded.name, original.name);
+        assert_eq!(loaded.visible, original.visible);
+        assert_eq!(loaded.data.voxels.len(), original.data.voxels.len());
+    }
+}
+
+#[test]
+fn vxl_keeps_palette_colors() {
+    let mut data = cube(2);
+    data.voxels.paint(&(1, 1, 1), 5);
+    let scene = SceneData::new(data);
+    let layers = scene_to_vxl_layers(&scene, |_, _, paint| palette_color(paint, [1, 2, 3]));
+    assert_eq!(layers[0].voxels.len(), 8);
+    assert!(layers[0].voxels.contains(&((1, 1, 1), [220, 40, 40, 255])));
+    assert!(layers[0].voxels.contains(&((0, 0, 0), [1, 2, 3, 255])));
+
+    let loaded = vxl_layers_to_scene(&layers, &Shape::default()).unwrap();
+    let voxels = &loaded.objects[0].data.voxels;
+    assert_eq!(voxels.color(&(1, 1, 1)), Some(5));
+    // Colors outside the palette load unpainted
+    assert_eq!(voxels.color(&(0, 0, 0)), Some(UNPAINTED));
+}
+
+#[test]
+fn vxl_rejects_a_wrong_magic_number() {
+    let mut bytes = Vec::new();
+    write_vxl(
+        &scene_to_vxl_layers(&SceneData::new(cube(2)), |_, _, _| [0; 3]),
+        &mut bytes,
+    )
+    .unwrap();
+    bytes[0] = b'X';
+    let error = read_vxl(&mut bytes.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+    // Cut off in the middle of a layer
+    let mut bytes = Vec::new();
+    write_vxl(
+        &scene_to_vxl_layers(&SceneData::new(cube(2)), |_, _, _| [0; 3]),
+        &mut bytes,
+    )
+    .unwrap();
+    bytes.truncate(bytes.len() - 3);
+    let error = read_vxl(&mut bytes.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+
+    // A name length no real file has is rejected before anything is allocated for it
+    let mut bytes = b"VXLR\x01".to_vec();
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    let error = read_vxl(&mut bytes.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "gltf-export")]
+#[test]
+fn glb_layout_matches_the_mesh() {
+    use voxel_sculptor::export::write_glb;
+
+    let data = model(GeometricShape::Cube, 2, 1, 1);
+    let mut bytes = Vec::new();
+    write_glb(&data, &mut bytes, [255, 0, 0], false).unwrap();
+    let word =
+        |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+
+    assert_eq!(&bytes[..4], b"glTF");
+    assert_eq!(word(4), 2);
+    assert_eq!(word(8), bytes.len());
+
+    let json_length = word(12);
+    assert_eq!(word(16), 0x4E4F534A);
+    let bin_start = 20 + json_length;
+    assert_eq!(word(bin_start + 4), 0x004E4942);
+    assert_eq!(bin_start + 8 + word(bin_start), bytes.len());
+    assert_eq!(json_length % 4, 0);
+
+    let document: serde_json::Value = serde_json::from_slice(&bytes[20..bin_start]).unwrap();
+    let faces = count_exposed_faces(&data);
+    let accessors = &document["accessors"];
+    assert_eq!(accessors[0]["count"], faces * 4);
+    assert_eq!(accessors[1]["count"], faces * 4);
+    assert_eq!(accessors[2]["count"], faces * 6);
+    assert!(document["buffers"][0].get("uri").is_none());
+    let color = &document["materials"][0]["pbrMetallicRoughness"]["baseColorFactor"];
+    assert_eq!(color, &serde_json::json!([1.0, 0.0, 0.0, 1.0]));
+
+    // glTF can't hold an empty mesh
+    assert!(write_glb(&VoxelData::default(), &mut Vec::new(), [255, 0, 0], false).is_err());
+}
@@ -0,0 +1,86 @@
+use voxel_sculptor::export::export_obj_to_writer;
+use voxel_sculptor::import::voxelize_obj;
+use voxel_sculptor::shapes::normalize_voxels;
+use voxel_sculptor::{generate_shape, GeometricShape, Shape, VoxelData};
+
+fn model(kind: GeometricShape, size: u32) -> VoxelData {
+    let shape = Shape {
+        kind,
+        width: size,
+        height: size,
+        depth: size,
+        ..Shape::default()
+    };
+    VoxelData {
+        voxels: generate_shape(&shape),
+        shape,
+    }
+}
+
+#[test]
+fn exported_obj_imports_as_the_same_voxels() {
+    for kind in [
+        GeometricShape::Cube,
+        GeometricShape::Sphere,
+        GeometricShape::Cone,
+    ] {
+        let data = model(kind, 7);
+        // Unoptimized, so every edge is shared by two faces and the mesh counts as closed;
+        // greedy quads meet in T-junctions
+        let mut buffer = Vec::new();
+        export_obj_to_writer(&data, &mut buffer, false).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        let imported = voxelize_obj(&text, &Shape::default(), None).unwrap();
+        // The import is fitted to the voxels' bounding box
+        assert_eq!(imported.voxels, normalize_voxels(&data.voxels), "{}", kind);
+    }
+}
+
+#[test]
+fn faces_with_texture_and_normal_indices() {
+    // A unit cube written with "v/vt/vn", "v//vn" and negative indices
+    let text = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vt 0 0
+vn 0 0 -1
+f 1/1/1 4/1/1 3/1/1 2/1/1
+f 5//1 6//1 7//1 8//1
+f 1 2 6 5
+f 4 8 7 3
+f -8 -4 -1 -5
+f 2 3 7 6
+";
+    let data = voxelize_obj(text, &Shape::default(), Some((4, 4, 4))).unwrap();
+    assert_eq!(
+        (data.shape.width, data.shape.height, data.shape.depth),
+        (4, 4, 4)
+    );
+    assert_eq!(data.voxels.len(), 64);
+
+    assert!(voxelize_obj("v 0 0 0\nf 1/1/1 2/2/2 3/3/3\n", &Shape::default(), None).is_err());
+}
+
+#[test]
+fn open_quad_voxelizes_its_surface() {
+    let text = "\
+v 0 0 0
+v 4 0 0
+v 4 0 4
+v 0 0 4
+f 1 2 3 4
+";
+    let data = voxelize_obj(text, &Shape::default(), None).unwrap();
+    assert_eq!(
+        (data.shape.width, data.shape.height, data.shape.depth),
+        (4, 1, 4)
+    );
+    assert_eq!(data.voxels.len(), 16);
+}
@@ -0,0 +1,282 @@
+use bevy::math::Vec3;
+use voxel_sculptor::sculpt::{
+    brush_cells, extrusion, face_region, fill_empty_region, line_cells, pick_voxel, plane_cells,
+    recolor_region, BrushShape, MoveMode, Selection, VoxelHit,
+};
+use voxel_sculptor::shapes::hollow_voxels;
+use voxel_sculptor::{generate_shape, GeometricShape, Shape, VoxelGrid};
+
+fn grid(size: u32) -> Shape {
+    Shape {
+        kind: GeometricShape::Cube,
+        width: size,
+        height: size,
+        depth: size,
+        ..Shape::default()
+    }
+}
+
+#[test]
+fn ray_hits_the_nearest_face() {
+    let shape = grid(4);
+    let voxels = generate_shape(&shape);
+
+    // Straight down onto the top layer
+    let hit = pick_voxel(&voxels, &shape, Vec3::new(1.0, 10.0, 2.0), Vec3::NEG_Y).unwrap();
+    assert_eq!(
+        hit,
+        VoxelHit {
+            voxel: (1, 3, 2),
+            normal: (0, 1, 0),
+        }
+    );
+    assert_eq!(hit.adjacent(), (1, 4, 2));
+
+    // From the -X side, slightly downwards
+    let hit = pick_voxel(
+        &voxels,
+        &shape,
+        Vec3::new(-5.0, 2.4, 1.0),
+        Vec3::new(1.0, -0.05, 0.0),
+    );
+    assert_eq!(hit.unwrap().voxel, (0, 2, 1));
+    assert_eq!(hit.unwrap().normal, (-1, 0, 0));
+
+    // Passing beside the grid
+    assert!(pick_voxel(&voxels, &shape, Vec3::new(6.0, 10.0, 0.0), Vec3::NEG_Y).is_none());
+}
+
+#[test]
+fn ray_passes_empty_cells_and_lands_on_the_floor() {
+    let shape = grid(5);
+    let voxels = VoxelGrid::from(vec![(4, 0, 4)]);
+
+    // Diagonally across the grid to the far corner voxel
+    let hit = pick_voxel(
+        &voxels,
+        &shape,
+        Vec3::new(-1.0, 0.0, -1.0),
+        Vec3::new(1.0, 0.0, 1.0),
+    );
+    assert_eq!(hit.unwrap().voxel, (4, 0, 4));
+
+    let hit = pick_voxel(
+        &voxels,
+        &shape,
+        Vec3::new(2.0, 8.0, 1.0),
+        Vec3::new(0.0, -1.0, 0.1),
+    );
+    let hit = hit.unwrap();
+    assert!(hit.on_floor());
+    assert_eq!(hit.adjacent(), (2, 0, 2));
+
+    // Looking up from below the grid never lands on the floor
+    assert!(pick_voxel(&voxels, &shape, Vec3::new(2.0, -3.0, 2.0), Vec3::Y).is_none());
+}
+
+#[test]
+fn brush_cells_by_radius_and_shape() {
+    let sphere = |radius| brush_cells((0, 0, 0), radius, BrushShape::Sphere);
+    let cube = |radius| brush_cells((0, 0, 0), radius, BrushShape::Cube);
+    assert_eq!(sphere(1), vec![(0, 0, 0)]);
+    assert_eq!(cube(1), vec![(0, 0, 0)]);
+    // The 3x3x3 block without its 8 corners
+    assert_eq!(sphere(2).len(), 19);
+    assert_eq!(cube(3).len(), 125);
+
+    let big = brush_cells((10, 10, 10), 4, BrushShape::Sphere);
+    // Cells whose centers are within 3.5 voxels
+    assert!(big.contains(&(13, 11, 10)));
+    assert!(big.contains(&(12, 12, 12)));
+    assert!(!big.contains(&(13, 12, 10)));
+}
+
+#[test]
+fn selection_box_from_two_clicks() {
+    let mut selection = Selection::default();
+    selection.click((3, 0, 1));
+    assert_eq!(selection.anchor, Some((3, 0, 1)));
+    assert_eq!(selection.size(), (1, 1, 1));
+    selection.click((1, 2, 1));
+    assert_eq!(selection.anchor, None);
+    assert_eq!(selection.bounds, Some(((1, 0, 1), (3, 2, 1))));
+    assert_eq!(selection.cells().len(), 9);
+    // A third click starts over
+    selection.click((0, 0, 0));
+    assert_eq!(selection.size(), (1, 1, 1));
+}
+
+#[test]
+fn selection_deletes_and_fills_its_box() {
+    let shape = grid(4);
+    let mut voxels = generate_shape(&shape);
+    let selection = Selection::from_corners((0, 3, 0), (3, 3, 3));
+    assert_eq!(selection.selected_voxels(&voxels).len(), 16);
+    assert_eq!(selection.delete(&mut voxels), 16);
+    assert_eq!(voxels.len(), 48);
+
+    // Filling is clipped to the grid
+    let wide = Selection::from_corners((2, 3, 2), (5, 3, 5));
+    assert_eq!(wide.fill(&mut voxels, &shape, 7), 4);
+    assert_eq!(voxels.color(&(3, 3, 3)), Some(7));
+    assert_eq!(Selection::all(&shape).fill(&mut voxels, &shape, 0), 12);
+}
+
+#[test]
+fn fill_tool_fills_empty_space_and_recolors() {
+    // A hollow 5^3 box: the air inside is enclosed
+    let shape = grid(5);
+    let mut voxels = hollow_voxels(&generate_shape(&shape));
+    let inside = ((1, 1, 1), (3, 3, 3));
+    assert_eq!(fill_empty_region(&mut voxels, (2, 2, 2), inside, 4), 27);
+    assert_eq!(voxels.len(), 125);
+    assert_eq!(fill_empty_region(&mut voxels, (2, 2, 2), inside, 4), 0);
+
+    // Recoloring stops at voxels of another color
+    assert_eq!(recolor_region(&mut voxels, (2, 2, 2), 9), 27);
+    assert_eq!(recolor_region(&mut voxels, (0, 0, 0), 2), 98);
+    assert_eq!(voxels.color(&(2, 2, 2)), Some(9));
+    assert_eq!(recolor_region(&mut voxels, (9, 9, 9), 2), 0);
+}
+
+#[test]
+fn lines_step_once_per_cell_of_the_longest_axis() {
+    assert_eq!(line_cells((2, 2, 2), (2, 2, 2)), vec![(2, 2, 2)]);
+    assert_eq!(
+        line_cells((0, 0, 0), (3, 0, 0)),
+        vec![(0, 0, 0), (1, 0, 0), (2, 0, 0), (3, 0, 0)]
+    );
+    assert_eq!(
+        line_cells((3, 3, 3), (0, 0, 0)),
+        vec![(3, 3, 3), (2, 2, 2), (1, 1, 1), (0, 0, 0)]
+    );
+
+    let line = line_cells((0, 0, 0), (6, 3, -2));
+    assert_eq!(line.len(), 7);
+    assert_eq!(line.last(), Some(&(6, 3, -2)));
+    // Neighboring cells are at most one step apart on every axis
+    for pair in line.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        assert_eq!(b.0 - a.0, 1);
+        assert!((b.1 - a.1).abs() <= 1 && (b.2 - a.2).abs() <= 1);
+    }
+}
+
+#[test]
+fn planes_lie_across_the_shortest_axis() {
+    let wall = plane_cells((0, 0, 2), (3, 2, 2));
+    assert_eq!(wall.len(), 12);
+    assert!(wall.iter().all(|cell| cell.2 == 2));
+
+    // Corners that differ on every axis: the plane stays at the first corner's Y
+    let floor = plane_cells((0, 1, 0), (4, 2, 3));
+    assert_eq!(floor.len(), 20);
+    assert!(floor.iter().all(|cell| cell.1 == 1));
+}
+
+#[test]
+fn nudging_clips_wraps_and_pushes() {
+    let shape = grid(4);
+    let bar = VoxelGrid::from(vec![(0, 0, 0), (1, 0, 0)]);
+
+    // Without a selection the whole model moves and the voxel leaving the grid is dropped
+    let mut voxels = bar.clone();
+    let mut none = Selection::default();
+    assert!(none.nudge(&mut voxels, &shape, (-1, 0, 0), MoveMode::Clip));
+    assert_eq!(voxels.to_sorted_vec(), vec![(0, 0, 0)]);
+    assert_eq!(none.bounds, None);
+
+    // The box goes along, cut down to the grid
+    let mut voxels = bar.clone();
+    voxels.insert((3, 3, 3));
+    let mut selection = Selection::from_corners((0, 0, 0), (1, 0, 0));
+    assert!(selection.nudge(&mut voxels, &shape, (3, 0, 0), MoveMode::Clip));
+    assert_eq!(voxels.to_sorted_vec(), vec![(3, 0, 0), (3, 3, 3)]);
+    assert_eq!(selection.bounds, Some(((3, 0, 0), (3, 0, 0))));
+
+    // Wrapping cycles the voxels inside the box, which stays put
+    let mut voxels = bar.clone();
+    voxels.paint(&(1, 0, 0), 4);
+    let mut selection = Selection::from_corners((0, 0, 0), (2, 0, 0));
+    assert!(selection.nudge(&mut voxels, &shape, (2, 0, 0), MoveMode::Wrap));
+    assert_eq!(voxels.to_sorted_vec(), vec![(0, 0, 0), (2, 0, 0)]);
+    assert_eq!(voxels.color(&(0, 0, 0)), Some(4));
+    assert_eq!(selection.bounds, Some(((0, 0, 0), (2, 0, 0))));
+
+    // Pushing refuses to leave the grid or land on voxels outside the box, but may
+    // move onto cells the selection is vacating
+    let mut voxels = bar.clone();
+    voxels.insert((3, 0, 0));
+    let mut selection = Selection::from_corners((0, 0, 0), (1, 0, 0));
+    assert!(!selection.nudge(&mut voxels, &shape, (0, -1, 0), MoveMode::Push));
+    assert!(selection.nudge(&mut voxels, &shape, (1, 0, 0), MoveMode::Push));
+    assert_eq!(
+        voxels.to_sorted_vec(),
+        vec![(1, 0, 0), (2, 0, 0), (3, 0, 0)]
+    );
+    assert!(!selection.nudge(&mut voxels, &shape, (1, 0, 0), MoveMode::Push));
+    assert_eq!(selection.bounds, Some(((1, 0, 0), (2, 0, 0))));
+}
+
+#[test]
+fn copies_paste_relative_to_the_box_corner() {
+    let shape = grid(6);
+    let mut voxels = VoxelGrid::from(vec![(1, 0, 1), (2, 1, 1), (5, 5, 5)]);
+    voxels.paint(&(2, 1, 1), 7);
+    let selection = Selection::from_corners((1, 0, 1), (2, 1, 1));
+    let clipboard = selection.copy(&voxels);
+    assert_eq!(clipboard.size, (2, 2, 1));
+    assert_eq!(clipboard.voxels.to_sorted_vec(), vec![(0, 0, 0), (1, 1, 0)]);
+    assert!(Selection::default().copy(&voxels).is_empty());
+
+    // Pasted voxels replace what they land on and those past the grid are dropped
+    let mut pasted = voxels.clone();
+    assert_eq!(clipboard.paste(&mut pasted, &shape, (4, 4, 5)), 2);
+    assert_eq!(pasted.color(&(5, 5, 5)), Some(7));
+    assert_eq!(clipboard.paste(&mut pasted, &shape, (5, 0, 0)), 1);
+    assert_eq!(
+        clipboard.selection_at((5, 0, 0)).bounds,
+        Some(((5, 0, 0), (6, 1, 0)))
+    );
+
+    // Duplicating selects the copy
+    let mut selection = selection;
+    assert_eq!(selection.duplicate(&mut voxels, &shape, (0, 0, 2)), 2);
+    assert_eq!(selection.bounds, Some(((1, 0, 3), (2, 1, 3))));
+    assert_eq!(voxels.color(&(2, 1, 3)), Some(7));
+    assert_eq!(voxels.len(), 5);
+}
+
+#[test]
+fn extrusion_grows_flat_patches_of_faces() {
+    // A 3x1x3 slab with a bump on top: the top faces around the bump form one patch
+    let mut voxels = generate_shape(&Shape {
+        kind: GeometricShape::Cube,
+        width: 3,
+        height: 1,
+        depth: 3,
+        ..Shape::default()
+    });
+    voxels.insert((1, 1, 1));
+    voxels.paint(&(0, 0, 0), 2);
+    let top = face_region(&voxels, (0, 0, 0), (0, 1, 0));
+    assert_eq!(top.len(), 8);
+    assert!(!top.contains(&(1, 0, 1)));
+    assert_eq!(face_region(&voxels, (1, 0, 1), (0, 1, 0)), Vec::new());
+    assert_eq!(face_region(&voxels, (1, 1, 1), (0, 1, 0)), vec![(1, 1, 1)]);
+
+    let grown = extrusion(&voxels, &top, (0, 1, 0), 2);
+    assert_eq!(grown.len(), 16);
+    assert_eq!(grown.color(&(0, 2, 0)), Some(2));
+    assert_eq!(grown.bounding_box(), Some(((0, 1, 0), (2, 2, 2))));
+
+    // A selection's side repeats the voxels on it and the box grows along
+    let shape = grid(6);
+    let mut voxels = VoxelGrid::from(vec![(1, 1, 1), (2, 1, 1), (2, 2, 1)]);
+    let mut selection = Selection::from_corners((1, 1, 1), (2, 2, 1));
+    assert_eq!(selection.extrude(&mut voxels, &shape, (1, 0, 0), 3), 6);
+    assert!(voxels.contains(&(5, 2, 1)));
+    assert_eq!(selection.bounds, Some(((1, 1, 1), (5, 2, 1))));
+    // Layers past the grid are dropped
+    assert_eq!(selection.extrude(&mut voxels, &shape, (1, 0, 0), 3), 0);
+}
@@ -0,0 +1,100 @@
+use bevy::input::keyboard::KeyCode;
+use strum::IntoEnumIterator;
+use voxel_sculptor::keymap::{Action, KeyBinding, KeyMap};
+use voxel_sculptor::presets::{default_presets, presets_from_json, presets_to_json};
+use voxel_sculptor::{GeometricShape, Shape};
+
+#[test]
+fn keymap_reads_single_keys_and_lists() {
+    let (keymap, warnings) = KeyMap::parse(
+        r#"
+regenerate = "Ctrl+G"
+zoom_in = ["Equal", "PageUp"]
+"#,
+    );
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(
+        keymap.bindings(Action::Regenerate),
+        &[KeyBinding {
+            key: KeyCode::KeyG,
+            ctrl: true,
+        }]
+    );
+    assert_eq!(keymap.describe(Action::ZoomIn), "Equal / PageUp");
+    // Actions not in the file keep their defaults
+    assert_eq!(
+        keymap.bindings(Action::Frame),
+        KeyMap::default().bindings(Action::Frame)
+    );
+}
+
+#[test]
+fn keymap_warns_and_keeps_going() {
+    let (keymap, warnings) = KeyMap::parse(
+        r#"
+no_such_action = "A"
+frame = "NotAKey"
+screenshot = 12
+export = []
+"#,
+    );
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+    assert!(warnings
+        .iter()
+        .any(|w| w.contains("unknown action 'no_such_action'")));
+    assert!(warnings.iter().any(|w| w.contains("unknown key 'NotAKey'")));
+    assert_eq!(
+        keymap.bindings(Action::Frame),
+        KeyMap::default().bindings(Action::Frame)
+    );
+    assert_eq!(keymap.describe(Action::Export), "unbound");
+
+    let (keymap, warnings) = KeyMap::parse("this is not toml");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(keymap, KeyMap::default());
+}
+
+#[test]
+fn presets_round_trip_through_json() {
+    let presets = default_presets();
+    assert_eq!(presets.len(), GeometricShape::iter().count());
+    let json = presets_to_json(&presets);
+    assert_eq!(presets_from_json(&json).unwrap(), presets);
+}
+
+#[test]
+fn presets_fill_missing_fields_with_defaults() {
+    let presets = presets_from_json(
+        r#"[{ "name": "Tall", "shape": { "kind": "Cylinder", "height": 20 }, "hollow": true }]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        presets[0].shape,
+        Shape {
+            kind: GeometricShape::Cylinder,
+            height: 20,
+            ..Shape::default()
+        }
+    );
+    assert!(presets[0].hollow);
+}
+
+#[test]
+fn corrupt_presets_are_an_error() {
+    assert!(presets_from_json("[{ \"name\": ").is_err());
+    assert!(presets_from_json("{}").is_err());
+}
+
+#[test]
+fn default_keys_are_not_shared_between_actions() {
+    let keymap = KeyMap::default();
+    let mut seen = Vec::new();
+    for action in Action::iter() {
+        for binding in keymap.bindings(action) {
+            assert!(!seen.contains(binding), "{} is bound twice", binding);
+            seen.push(*binding);
+        }
+    }
+    assert_eq!(keymap.describe(Action::Regenerate), "G / Enter");
+    assert_eq!(keymap.describe(Action::ToggleHollow), "H");
+}
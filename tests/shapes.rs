@@ -0,0 +1,661 @@
+use voxel_sculptor::shapes::{
+    generate_formula, generate_noise_blob, generate_text, hollow_voxels, stair_steps, Axis,
+    ClimbDirection,
+};
+use voxel_sculptor::{generate_shape, GeometricShape, Shape, VoxelCoord, VoxelData, VoxelGrid};
+
+fn shape(kind: GeometricShape, width: u32, height: u32, depth: u32) -> Shape {
+    Shape {
+        kind,
+        width,
+        height,
+        depth,
+        ..Shape::default()
+    }
+}
+
+fn mirrored_x(voxels: &VoxelGrid, width: u32) -> VoxelGrid {
+    voxels
+        .iter()
+        .map(|&(x, y, z)| (width as i32 - 1 - x, y, z))
+        .collect()
+}
+
+fn mirrored_z(voxels: &VoxelGrid, depth: u32) -> VoxelGrid {
+    voxels
+        .iter()
+        .map(|&(x, y, z)| (x, y, depth as i32 - 1 - z))
+        .collect()
+}
+
+// Voxels of layer `y` seen from the side: (x, z) pairs
+fn layer(voxels: &VoxelGrid, y: i32) -> Vec<(i32, i32)> {
+    let mut cells: Vec<(i32, i32)> = voxels
+        .iter()
+        .filter(|v| v.1 == y)
+        .map(|&(x, _, z)| (x, z))
+        .collect();
+    cells.sort_unstable();
+    cells
+}
+
+#[test]
+fn cube_fills_the_grid() {
+    let voxels = generate_shape(&shape(GeometricShape::Cube, 3, 4, 5));
+    assert_eq!(voxels.len(), 60);
+    assert_eq!(voxels.bounding_box(), Some(((0, 0, 0), (2, 3, 4))));
+}
+
+#[test]
+fn round_shapes_are_mirror_symmetric() {
+    for kind in [
+        GeometricShape::Sphere,
+        GeometricShape::Cylinder,
+        GeometricShape::Cone,
+        GeometricShape::SquarePyramid,
+        GeometricShape::Tube,
+    ] {
+        for size in [7, 8] {
+            let voxels = generate_shape(&shape(kind, size, size, size));
+            assert!(!voxels.is_empty(), "{} {}", kind, size);
+            assert_eq!(
+                mirrored_x(&voxels, size),
+                voxels,
+                "{} {} across X",
+                kind,
+                size
+            );
+            assert_eq!(
+                mirrored_z(&voxels, size),
+                voxels,
+                "{} {} across Z",
+                kind,
+                size
+            );
+        }
+    }
+}
+
+#[test]
+fn sphere_volume_is_close_to_the_ideal_ball() {
+    let voxels = generate_shape(&shape(GeometricShape::Sphere, 64, 64, 64));
+    // 4/3 * pi * 32^3
+    let ideal = 137_258.0;
+    let ratio = voxels.len() as f64 / ideal;
+    assert!((0.9..=1.1).contains(&ratio), "{} voxels", voxels.len());
+}
+
+#[test]
+fn sphere_shell_is_hollow() {
+    let solid = generate_shape(&shape(GeometricShape::Sphere, 12, 12, 12));
+    let shell = generate_shape(&Shape {
+        wall_thickness: 2,
+        ..shape(GeometricShape::Sphere, 12, 12, 12)
+    });
+    assert!(shell.len() < solid.len());
+    assert!(!shell.contains(&(6, 6, 6)));
+    assert!(shell.iter().all(|voxel| solid.contains(voxel)));
+}
+
+#[test]
+fn cone_narrows_towards_the_top() {
+    let voxels = generate_shape(&shape(GeometricShape::Cone, 10, 10, 10));
+    let widths: Vec<usize> = (0..10).map(|y| layer(&voxels, y).len()).collect();
+    assert!(
+        widths.windows(2).all(|pair| pair[0] >= pair[1]),
+        "{:?}",
+        widths
+    );
+    assert!(widths[0] > widths[9]);
+}
+
+#[test]
+fn cone_tip_offset_leans_the_cone() {
+    let voxels = generate_shape(&Shape {
+        tip_offset_x: 0.5,
+        ..shape(GeometricShape::Cone, 10, 10, 10)
+    });
+    let right = voxels.iter().filter(|v| v.0 >= 5).count();
+    let left = voxels.iter().filter(|v| v.0 < 5).count();
+    assert!(right > left, "{} at x >= 5, {} at x < 5", right, left);
+}
+
+#[test]
+fn four_sided_prism_matches_the_cube_footprint() {
+    let prism = generate_shape(&Shape {
+        sides: 4,
+        ..shape(GeometricShape::Prism, 8, 3, 8)
+    });
+    let cube = generate_shape(&shape(GeometricShape::Cube, 8, 3, 8));
+    assert_eq!(prism, cube);
+}
+
+#[test]
+fn hexagonal_prism_is_symmetric() {
+    let voxels = generate_shape(&Shape {
+        sides: 6,
+        ..shape(GeometricShape::Prism, 12, 2, 12)
+    });
+    assert!(!voxels.is_empty());
+    assert!(voxels.len() < 12 * 2 * 12);
+    assert_eq!(mirrored_x(&voxels, 12), voxels);
+    assert_eq!(mirrored_z(&voxels, 12), voxels);
+}
+
+#[test]
+fn formula_fills_where_positive() {
+    let formula = |expression: &str| Shape {
+        expression: expression.into(),
+        ..shape(GeometricShape::Formula, 10, 10, 10)
+    };
+    let voxels = generate_formula(&formula("x*x+y*y+z*z-0.9")).unwrap();
+    assert!(!voxels.is_empty());
+    assert!(voxels.len() < 1000);
+    // Outside the ball: the corners are filled, the center is not
+    assert!(voxels.contains(&(0, 0, 0)));
+    assert!(!voxels.contains(&(5, 5, 5)));
+
+    assert!(generate_formula(&formula("x * (y + ")).is_err());
+    assert!(generate_formula(&formula("foo(x)")).is_err());
+    assert!(generate_formula(&formula("")).is_err());
+    // Invalid formulas generate nothing instead of failing
+    assert!(generate_shape(&formula("x +* y")).is_empty());
+}
+
+#[test]
+fn noise_blob_is_deterministic_per_seed() {
+    let blob = shape(GeometricShape::NoiseBlob, 16, 16, 16);
+    let first = generate_noise_blob(&blob, 42);
+    assert!(!first.is_empty());
+    assert_eq!(generate_noise_blob(&blob, 42), first);
+    assert_ne!(generate_noise_blob(&blob, 43), first);
+}
+
+#[test]
+fn text_i_is_a_centered_bar() {
+    let voxels = generate_text(&Shape {
+        text: "I".into(),
+        ..shape(GeometricShape::Text, 9, 9, 2)
+    });
+    // The glyph is one column 7 tall, centered in the 9x9 face and extruded through the depth
+    assert_eq!(voxels.len(), 7 * 2);
+    assert_eq!(voxels.bounding_box(), Some(((4, 1, 0), (4, 7, 1))));
+}
+
+#[test]
+fn axis_turns_the_cylinder_on_its_side() {
+    let lying = generate_shape(&Shape {
+        axis: Axis::X,
+        ..shape(GeometricShape::Cylinder, 8, 5, 5)
+    });
+    let upright = generate_shape(&shape(GeometricShape::Cylinder, 5, 8, 5));
+    assert_eq!(lying.len(), upright.len());
+    assert_eq!(lying.bounding_box(), Some(((0, 0, 0), (7, 4, 4))));
+}
+
+#[test]
+fn staircase_layers_advance_evenly() {
+    let voxels = generate_shape(&shape(GeometricShape::Stairs, 4, 4, 8));
+    for y in 0..4 {
+        let zs: Vec<i32> = layer(&voxels, y).iter().map(|&(_, z)| z).collect();
+        assert_eq!(zs.iter().min(), Some(&(2 * y)), "layer {}", y);
+        assert_eq!(zs.iter().max(), Some(&7), "layer {}", y);
+        assert_eq!(zs.len() as i32, 4 * (8 - 2 * y), "layer {}", y);
+    }
+}
+
+#[test]
+fn stairs_and_ramps_have_no_gaps() {
+    for kind in [GeometricShape::Stairs, GeometricShape::Ramp] {
+        for (height, depth) in [(1, 5), (3, 5), (6, 3), (8, 8)] {
+            for climb in [ClimbDirection::PlusZ, ClimbDirection::MinusX] {
+                let voxels = generate_shape(&Shape {
+                    climb,
+                    ..shape(kind, 4, height, depth)
+                });
+                // Every layer is present and rests on the one below
+                for y in 0..height as i32 {
+                    let cells = layer(&voxels, y);
+                    assert!(
+                        !cells.is_empty(),
+                        "{} {}x{} layer {}",
+                        kind,
+                        height,
+                        depth,
+                        y
+                    );
+                    if y > 0 {
+                        let below = layer(&voxels, y - 1);
+                        assert!(cells.iter().all(|cell| below.contains(cell)));
+                    }
+                }
+                if height == 1 {
+                    assert_eq!(voxels.len() as u32, 4 * depth);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn voxel_grid_keeps_each_cell_once() {
+    let mut grid = VoxelGrid::new();
+    assert!(grid.insert((1, 2, 3)));
+    assert!(!grid.insert((1, 2, 3)));
+    grid.extend([(1, 2, 3), (0, 0, 0)]);
+    assert_eq!(grid.len(), 2);
+
+    let from_vec = VoxelGrid::from(vec![(0, 0, 0), (0, 0, 0), (1, 0, 0)]);
+    assert_eq!(from_vec.len(), 2);
+    assert_eq!(from_vec.to_sorted_vec(), vec![(0, 0, 0), (1, 0, 0)]);
+}
+
+#[test]
+fn neighbors_empty_on_boundary_voxels() {
+    let grid = VoxelGrid::from(vec![(0, 0, 0), (1, 0, 0)]);
+    // +X, -X, +Y, -Y, +Z, -Z; cells outside the grid count as empty
+    assert_eq!(
+        grid.neighbors_empty((0, 0, 0)),
+        [false, true, true, true, true, true]
+    );
+    assert_eq!(
+        grid.neighbors_empty((1, 0, 0)),
+        [true, false, true, true, true, true]
+    );
+    assert!(grid.is_exposed((0, 0, 0)));
+}
+
+#[test]
+fn symmetric_cube_is_centered_on_the_origin() {
+    let cube = shape(GeometricShape::Cube, 4, 4, 4);
+    let data = VoxelData {
+        voxels: generate_shape(&cube),
+        shape: cube,
+    };
+    let sum = data
+        .voxels
+        .iter()
+        .map(|&voxel: &VoxelCoord| data.centered_position(voxel))
+        .sum::<bevy::math::Vec3>();
+    assert!((sum / data.voxels.len() as f32).length() < 1e-5);
+}
+
+#[test]
+fn torus_is_a_ring_with_a_hole() {
+    let voxels = generate_shape(&shape(GeometricShape::Torus, 16, 4, 16));
+    assert!(!voxels.is_empty());
+    assert_eq!(voxels.bounding_box(), Some(((0, 0, 0), (15, 3, 15))));
+    assert_eq!(mirrored_x(&voxels, 16), voxels);
+    assert_eq!(mirrored_z(&voxels, 16), voxels);
+    // The middle of the ring is open on every layer
+    for y in 0..4 {
+        assert!(!voxels.contains(&(7, y, 7)) && !voxels.contains(&(8, y, 8)));
+    }
+
+    // A grid taller than the ring keeps its hole
+    let tall = generate_shape(&shape(GeometricShape::Torus, 10, 10, 10));
+    assert!(!tall.contains(&(5, 5, 5)));
+    assert!(tall.contains(&(0, 5, 5)));
+}
+
+#[test]
+fn capsule_has_a_straight_body_and_rounded_caps() {
+    let voxels = generate_shape(&shape(GeometricShape::Capsule, 8, 20, 6));
+    assert_eq!(voxels.bounding_box(), Some(((0, 0, 0), (7, 19, 5))));
+    assert_eq!(mirrored_x(&voxels, 8), voxels);
+    assert_eq!(mirrored_z(&voxels, 6), voxels);
+
+    // Layers outside the caps match a cylinder's, the caps narrow towards the ends
+    let cylinder = generate_shape(&shape(GeometricShape::Cylinder, 8, 20, 6));
+    for y in 3..17 {
+        assert_eq!(layer(&voxels, y), layer(&cylinder, y), "layer {}", y);
+    }
+    assert!(layer(&voxels, 0).len() < layer(&voxels, 3).len());
+    assert_eq!(layer(&voxels, 0), layer(&voxels, 19));
+}
+
+#[test]
+fn wall_thickness_hollows_cubes_cylinders_and_cones() {
+    let walled = |kind, wall_thickness| {
+        generate_shape(&Shape {
+            wall_thickness,
+            ..shape(kind, 10, 10, 10)
+        })
+    };
+    assert_eq!(walled(GeometricShape::Cube, 2).len(), 1000 - 6 * 6 * 6);
+    assert_eq!(
+        walled(GeometricShape::Cube, 1),
+        hollow_voxels(&walled(GeometricShape::Cube, 0))
+    );
+
+    for kind in [GeometricShape::Cylinder, GeometricShape::Cone] {
+        let solid = walled(kind, 0);
+        let shell = walled(kind, 1);
+        assert!(shell.len() < solid.len(), "{}", kind);
+        assert!(shell.iter().all(|voxel| solid.contains(voxel)));
+        // The bottom layer is kept, so the shell stays closed
+        assert_eq!(layer(&shell, 0), layer(&solid, 0), "{}", kind);
+        assert!(!shell.contains(&(5, 2, 5)), "{}", kind);
+    }
+}
+
+#[test]
+fn octahedron_narrows_to_a_point_at_both_ends() {
+    let voxels = generate_shape(&shape(GeometricShape::Octahedron, 9, 9, 9));
+    assert_eq!(mirrored_x(&voxels, 9), voxels);
+    assert_eq!(mirrored_z(&voxels, 9), voxels);
+    assert_eq!(layer(&voxels, 0), vec![(4, 4)]);
+    assert_eq!(layer(&voxels, 8), vec![(4, 4)]);
+    // The widest layer is the middle one, a diamond rather than a square
+    let middle = layer(&voxels, 4);
+    assert!(middle.contains(&(0, 4)) && middle.contains(&(4, 0)));
+    assert!(!middle.contains(&(0, 0)));
+}
+
+#[test]
+fn tetrahedron_shrinks_its_triangle_towards_the_apex() {
+    let voxels = generate_shape(&shape(GeometricShape::Tetrahedron, 12, 12, 12));
+    // The bottom layer is nearly the full triangle of a 3-sided prism
+    let triangle = generate_shape(&Shape {
+        sides: 3,
+        ..shape(GeometricShape::Prism, 12, 1, 12)
+    });
+    let base = layer(&voxels, 0);
+    assert!(base.iter().all(|cell| layer(&triangle, 0).contains(cell)));
+    assert!(base.len() * 10 >= triangle.len() * 8);
+    let widths: Vec<usize> = (0..12).map(|y| layer(&voxels, y).len()).collect();
+    assert!(
+        widths.windows(2).all(|pair| pair[0] >= pair[1]),
+        "{:?}",
+        widths
+    );
+    // Triangles are symmetric across Z only
+    assert_eq!(mirrored_z(&voxels, 12), voxels);
+    assert_ne!(mirrored_x(&voxels, 12), voxels);
+
+    let lying = generate_shape(&Shape {
+        axis: Axis::X,
+        ..shape(GeometricShape::Tetrahedron, 12, 12, 12)
+    });
+    assert_eq!(lying.len(), voxels.len());
+}
+
+#[test]
+fn helix_winds_around_an_empty_core() {
+    let helix = |turns| {
+        generate_shape(&Shape {
+            turns,
+            coil_radius: 1.5,
+            ..shape(GeometricShape::Helix, 16, 24, 16)
+        })
+    };
+    let voxels = helix(3.0);
+    assert!(!voxels.is_empty());
+    let (min, max) = voxels.bounding_box().unwrap();
+    assert!(min.1 == 0 && max.1 == 23, "{:?} {:?}", min, max);
+    assert!(min.0 >= 0 && max.0 < 16 && min.2 >= 0 && max.2 < 16);
+    // The axis is open all the way up
+    assert!((0..24).all(|y| !voxels.contains(&(8, y, 8))));
+    // Every layer is reached and more turns need more tube
+    assert!((0..24).all(|y| !layer(&voxels, y).is_empty()));
+    assert!(helix(6.0).len() > voxels.len());
+}
+
+#[test]
+fn stairs_step_count_sets_step_size() {
+    let stairs = |steps| {
+        generate_shape(&Shape {
+            steps,
+            ..shape(GeometricShape::Stairs, 2, 6, 12)
+        })
+    };
+    // Three steps, each two layers high and four cells deep
+    let voxels = stairs(3);
+    assert_eq!(stair_steps(&shape(GeometricShape::Stairs, 2, 6, 12)), 6);
+    for y in 0..6 {
+        let front = layer(&voxels, y).iter().map(|&(_, z)| z).min();
+        assert_eq!(front, Some(y / 2 * 4), "layer {}", y);
+    }
+    // 0 and too many steps both give one step per layer
+    assert_eq!(stairs(0), stairs(6));
+    assert_eq!(stairs(50), stairs(6));
+}
+
+#[test]
+fn arch_opening_runs_through_the_chosen_axis() {
+    let voxels = generate_shape(&shape(GeometricShape::Arch, 12, 12, 3));
+    // Pillars three wide, a three voxel top and an opening six wide at the bottom
+    assert_eq!(layer(&voxels, 0).len(), 6 * 3);
+    assert_eq!(layer(&voxels, 11).len(), 12 * 3);
+    assert!(!voxels.contains(&(5, 0, 1)) && !voxels.contains(&(6, 5, 1)));
+    assert_eq!(mirrored_x(&voxels, 12), voxels);
+    // Every slice through the depth looks the same
+    for z in 1..3 {
+        assert!(voxels.iter().all(|&(x, y, _)| voxels.contains(&(x, y, z))));
+    }
+    // The rounded top narrows the opening towards the top
+    let opening = |y| 12 - layer(&voxels, y).len() / 3;
+    assert!(opening(8) < opening(0));
+
+    let along_x = generate_shape(&Shape {
+        axis: Axis::X,
+        ..shape(GeometricShape::Arch, 3, 12, 12)
+    });
+    let turned: VoxelGrid = voxels.iter().map(|&(x, y, z)| (z, y, x)).collect();
+    assert_eq!(along_x, turned);
+}
+
+#[test]
+fn torus_knot_fills_the_grid_around_an_open_center() {
+    let knot = |knot_p, knot_q| {
+        generate_shape(&Shape {
+            knot_p,
+            knot_q,
+            coil_radius: 1.5,
+            ..shape(GeometricShape::TorusKnot, 24, 8, 24)
+        })
+    };
+    let trefoil = knot(2, 3);
+    let (min, max) = trefoil.bounding_box().unwrap();
+    // The curve reaches at least two thirds of the way out in every direction
+    assert!(
+        min.0 <= 4 && max.0 >= 19 && min.2 <= 4 && max.2 >= 19,
+        "{:?} {:?}",
+        min,
+        max
+    );
+    assert!(min.1 >= 0 && max.1 <= 7);
+    assert!((0..8).all(|y| !trefoil.contains(&(12, y, 12))));
+    // Windings change the shape; a 2,3 knot is not a 3,2 knot
+    assert_ne!(knot(3, 2), trefoil);
+}
+
+#[test]
+fn fractals_follow_their_iteration_count() {
+    let fractal = |kind, size, iterations| {
+        generate_shape(&Shape {
+            iterations,
+            ..shape(kind, size, size, size)
+        })
+    };
+    // 20 of 27 cubes survive each step of the sponge
+    for iterations in 0..=3 {
+        let sponge = fractal(GeometricShape::MengerSponge, 27, iterations);
+        assert_eq!(
+            sponge.len(),
+            27usize.pow(3) / 27usize.pow(iterations) * 20usize.pow(iterations)
+        );
+    }
+    let sponge = fractal(GeometricShape::MengerSponge, 27, 1);
+    assert!(!sponge.contains(&(13, 13, 13)) && !sponge.contains(&(13, 13, 0)));
+    assert!(sponge.contains(&(13, 0, 0)));
+
+    // 4 of 8 octants survive each step of the tetrahedron
+    for iterations in 0..=4 {
+        let tetrahedron = fractal(GeometricShape::Sierpinski, 16, iterations);
+        assert_eq!(tetrahedron.len(), 16usize.pow(3) / 2usize.pow(iterations));
+    }
+}
+
+#[test]
+fn superellipsoid_morphs_between_box_sphere_and_octahedron() {
+    let superellipsoid = |roundness| {
+        generate_shape(&Shape {
+            roundness_y: roundness,
+            roundness_xz: roundness,
+            ..shape(GeometricShape::Superellipsoid, 12, 12, 12)
+        })
+    };
+    let boxy = superellipsoid(0.05);
+    assert_eq!(
+        boxy,
+        generate_shape(&shape(GeometricShape::Cube, 12, 12, 12))
+    );
+    assert_eq!(
+        superellipsoid(1.0),
+        generate_shape(&shape(GeometricShape::Sphere, 12, 12, 12))
+    );
+    assert_eq!(
+        superellipsoid(2.0),
+        generate_shape(&shape(GeometricShape::Octahedron, 12, 12, 12))
+    );
+    // Rounder is smaller
+    let sizes: Vec<usize> = [0.3, 0.6, 1.0, 1.5]
+        .map(|e| superellipsoid(e).len())
+        .to_vec();
+    assert!(
+        sizes.windows(2).all(|pair| pair[0] > pair[1]),
+        "{:?}",
+        sizes
+    );
+}
+
+#[test]
+fn letter_spacing_widens_the_line() {
+    let text = |letter_spacing| {
+        generate_text(&Shape {
+            text: "II".into(),
+            letter_spacing,
+            ..shape(GeometricShape::Text, 20, 9, 3)
+        })
+    };
+    let columns = |voxels: &VoxelGrid| {
+        let mut xs: Vec<i32> = voxels.iter().map(|v| v.0).collect();
+        xs.sort_unstable();
+        xs.dedup();
+        xs
+    };
+    // The I is the glyph's middle column, so the bars are one glyph width plus the spacing apart
+    let tight = columns(&text(0));
+    let wide = columns(&text(4));
+    assert_eq!(tight.len(), 2);
+    assert_eq!(tight[1] - tight[0], 5);
+    assert_eq!(wide[1] - wide[0], 9);
+    assert_eq!(text(4).len(), text(0).len());
+}
+
+#[test]
+fn terrain_is_a_seeded_heightfield() {
+    let terrain = |seed, amplitude| {
+        generate_shape(&Shape {
+            seed,
+            amplitude,
+            ..shape(GeometricShape::Terrain, 16, 12, 16)
+        })
+    };
+    let ground = terrain(5, 0.8);
+    assert_eq!(terrain(5, 0.8), ground);
+    assert_ne!(terrain(6, 0.8), ground);
+    // Every column is filled from the bottom up without gaps
+    assert_eq!(layer(&ground, 0).len(), 16 * 16);
+    for &(x, y, z) in ground.iter() {
+        assert!(y == 0 || ground.contains(&(x, y - 1, z)));
+    }
+    assert!(layer(&ground, 11).len() < 16 * 16);
+    // No amplitude gives a flat block
+    assert_eq!(terrain(5, 0.0).len(), 16 * 12 * 16);
+}
+
+#[test]
+fn cone_top_radius_makes_a_frustum() {
+    let cone = |top_radius| {
+        generate_shape(&Shape {
+            top_radius,
+            ..shape(GeometricShape::Cone, 10, 10, 10)
+        })
+    };
+    let point = cone(0.0);
+    assert_eq!(
+        point,
+        generate_shape(&shape(GeometricShape::Cone, 10, 10, 10))
+    );
+    let frustum = cone(0.5);
+    assert!(layer(&frustum, 9).len() > layer(&point, 9).len());
+    assert_eq!(layer(&frustum, 0), layer(&point, 0));
+    assert_eq!(
+        cone(1.0),
+        generate_shape(&shape(GeometricShape::Cylinder, 10, 10, 10))
+    );
+}
+
+#[test]
+fn gear_has_teeth_and_a_bore() {
+    let gear = |teeth, bore_radius| {
+        generate_shape(&Shape {
+            teeth,
+            tooth_depth: 3,
+            bore_radius,
+            ..shape(GeometricShape::Gear, 32, 2, 32)
+        })
+    };
+    let voxels = gear(8, 4);
+    let disc = generate_shape(&shape(GeometricShape::Cylinder, 32, 2, 32));
+    assert!(voxels.iter().all(|voxel| disc.contains(voxel)));
+    assert!(!voxels.contains(&(16, 0, 16)));
+    assert!(gear(8, 0).contains(&(16, 0, 16)));
+    assert_eq!(layer(&voxels, 0), layer(&voxels, 1));
+
+    // Walking around the rim crosses a tooth and a gap for every tooth
+    let rim: Vec<bool> = (0..360)
+        .map(|degree| {
+            let angle = (degree as f32 + 0.5).to_radians();
+            let x = (16.0 + 14.5 * angle.cos()).floor() as i32;
+            let z = (16.0 + 14.5 * angle.sin()).floor() as i32;
+            voxels.contains(&(x, 0, z))
+        })
+        .collect();
+    let edges = rim.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    assert!((15..=17).contains(&edges), "{} edges", edges);
+}
+
+#[test]
+fn star_points_reach_the_rim_between_notches() {
+    let star = |sides| {
+        generate_shape(&Shape {
+            sides,
+            ..shape(GeometricShape::Star, 21, 1, 21)
+        })
+    };
+    let five = star(5);
+    // A point at +X reaches the rim, the notch beside it doesn't
+    assert!(five.contains(&(20, 0, 10)));
+    assert!(!five.contains(&(17, 0, 16)));
+    assert_eq!(mirrored_z(&five, 21), five);
+    assert!(five.len() < generate_shape(&shape(GeometricShape::Cylinder, 21, 1, 21)).len() / 2);
+    // More points fill more of the disc
+    assert!(star(12).len() > five.len());
+}
+
+#[test]
+fn plus_is_three_crossing_bars() {
+    let voxels = generate_shape(&Shape {
+        arm_thickness: 2,
+        ..shape(GeometricShape::Plus, 8, 8, 8)
+    });
+    // Three 8x2x2 bars sharing a 2x2x2 center
+    assert_eq!(voxels.len(), 3 * 32 - 2 * 8);
+    assert_eq!(voxels.bounding_box(), Some(((0, 0, 0), (7, 7, 7))));
+    assert!(voxels.contains(&(0, 3, 4)) && voxels.contains(&(3, 7, 4)));
+    assert!(!voxels.contains(&(0, 0, 0)) && !voxels.contains(&(0, 0, 3)));
+    assert_eq!(mirrored_x(&voxels, 8), voxels);
+}